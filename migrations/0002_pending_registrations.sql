@@ -0,0 +1,14 @@
+-- 0002_pending_registrations.sql
+
+-- When enumeration resistance is on, registrations wait here until the
+-- owner of the email address clicks the confirmation link.
+-- The password is already hashed. Raw passwords never touch the DB.
+CREATE TABLE IF NOT EXISTS pending_registrations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    token VARCHAR(100) NOT NULL UNIQUE,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    expires_timestamp TIMESTAMP NOT NULL
+);
@@ -0,0 +1,20 @@
+-- 0003_device_sessions.sql
+
+-- A refresh token is now one session on one device.
+-- A user can have many of them per client site (phone, laptop, etc),
+-- so the old one-token-per-user-and-client rule goes.
+ALTER TABLE refresh_tokens DROP INDEX unique_user_client;
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN device_label VARCHAR(255) NOT NULL DEFAULT '',
+    ADD COLUMN user_agent VARCHAR(512) NOT NULL DEFAULT '',
+    ADD COLUMN ip_address VARCHAR(45) NOT NULL DEFAULT '',
+    ADD COLUMN last_used_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    ADD INDEX idx_user_client (user_id, client_id);
+
+
+-- The device that asked for an auth code is the device that gets the session.
+-- (The client site's server redeems the code, so we can't read it from that request.)
+ALTER TABLE auth_codes
+    ADD COLUMN user_agent VARCHAR(512) NOT NULL DEFAULT '',
+    ADD COLUMN ip_address VARCHAR(45) NOT NULL DEFAULT '';
@@ -0,0 +1,20 @@
+-- 0004_audit_events.sql
+
+-- Security audit log. One row per authentication event.
+-- user_id: whose account the event is about (NULL for client site events, unknown users)
+-- actor_id: who did it (the user themself, or an admin)
+-- Secrets, passwords and tokens are NEVER written here.
+CREATE TABLE IF NOT EXISTS audit_events (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    event_type VARCHAR(50) NOT NULL,
+    user_id INT,
+    actor_id INT,
+    client_id VARCHAR(100),
+    ip_address VARCHAR(45) NOT NULL DEFAULT '',
+    user_agent VARCHAR(512) NOT NULL DEFAULT '',
+    details VARCHAR(255) NOT NULL DEFAULT '',
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    INDEX idx_audit_user (user_id, created_timestamp),
+    INDEX idx_audit_type (event_type, created_timestamp),
+    INDEX idx_audit_created (created_timestamp)
+);
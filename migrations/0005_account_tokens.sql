@@ -0,0 +1,14 @@
+-- 0005_account_tokens.sql
+
+-- Single-use tokens for links we email to users:
+-- purpose 'not_me'         : "this wasn't me" link in a login alert
+-- purpose 'password_reset' : set a new password
+CREATE TABLE IF NOT EXISTS account_tokens (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    token VARCHAR(100) NOT NULL UNIQUE,
+    purpose VARCHAR(20) NOT NULL,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    expires_timestamp TIMESTAMP NOT NULL,
+    INDEX idx_account_tokens_user (user_id, purpose)
+);
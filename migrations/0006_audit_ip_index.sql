@@ -0,0 +1,5 @@
+-- 0006_audit_ip_index.sql
+
+-- Counting recent failed logins per IP (for the proof-of-work challenge)
+ALTER TABLE audit_events
+    ADD INDEX idx_audit_ip (ip_address, event_type, created_timestamp);
@@ -0,0 +1,17 @@
+-- 0007_user_suspensions.sql
+
+-- An admin stopping a player from logging in.
+-- end_timestamp NULL: until further notice (a ban).
+-- Lifting a suspension early fills in lifted_by / lifted_timestamp.
+-- Old suspensions stay, as the user's record.
+CREATE TABLE IF NOT EXISTS user_suspensions (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    reason VARCHAR(500) NOT NULL,
+    issued_by INT NOT NULL,
+    start_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    end_timestamp TIMESTAMP NULL DEFAULT NULL,
+    lifted_by INT NULL DEFAULT NULL,
+    lifted_timestamp TIMESTAMP NULL DEFAULT NULL,
+    INDEX idx_suspensions_user (user_id, lifted_timestamp)
+);
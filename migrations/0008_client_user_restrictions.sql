@@ -0,0 +1,17 @@
+-- 0008_client_user_restrictions.sql
+
+-- Block one user from one client site (ie: caught cheating in one game)
+-- without touching the rest of their account.
+-- issued_by: the admin who set it. NULL when the client site's backend set it.
+-- expires_timestamp NULL: until further notice.
+-- Removing a restriction deletes the row (the audit log keeps the history).
+CREATE TABLE IF NOT EXISTS client_user_restrictions (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    client_id VARCHAR(100) NOT NULL,
+    user_id INT NOT NULL,
+    reason VARCHAR(500) NOT NULL,
+    issued_by INT NULL DEFAULT NULL,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    expires_timestamp TIMESTAMP NULL DEFAULT NULL,
+    INDEX idx_restrictions_client_user (client_id, user_id)
+);
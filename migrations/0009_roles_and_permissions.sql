@@ -0,0 +1,56 @@
+-- 0009_roles_and_permissions.sql
+
+-- Role-based access control.
+-- A role is a named bundle of permissions (ie: "moderator" can suspend users
+-- and read the audit log, but can't touch client sites).
+-- A user has the role in users.role (their base role, which also goes in the JWT),
+-- plus any extra roles granted in user_roles.
+-- Permission names must match rbac::Permission.
+CREATE TABLE IF NOT EXISTS roles (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    description VARCHAR(255) NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS permissions (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    description VARCHAR(255) NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id INT NOT NULL,
+    permission_id INT NOT NULL,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INT NOT NULL,
+    role_id INT NOT NULL,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    PRIMARY KEY (user_id, role_id)
+);
+
+
+INSERT IGNORE INTO roles (name, description) VALUES
+    ('admin', 'Everything'),
+    ('moderator', 'Suspends players and reads the audit log'),
+    ('player', 'A regular account. No admin permissions.');
+
+INSERT IGNORE INTO permissions (name, description) VALUES
+    ('admin.access', 'Open the admin dashboard'),
+    ('clients.read', 'See client sites'),
+    ('clients.write', 'Create and edit client sites, rotate secrets, block users from a site'),
+    ('users.suspend', 'Suspend users and lift suspensions'),
+    ('audit.read', 'Read the audit log'),
+    ('system.read', 'See server stats (ie: the hash pool)');
+
+-- admin gets every permission
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'admin';
+
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'moderator'
+    AND permissions.name IN ('admin.access', 'users.suspend', 'audit.read');
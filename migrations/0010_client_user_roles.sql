@@ -0,0 +1,16 @@
+-- 0010_client_user_roles.sql
+
+-- Roles a user has on ONE client site (ie: moderator in one game only).
+-- The client site makes up its own role names (moderator, beta_tester, ...).
+-- They mean nothing to the auth site: we only store them and hand them over
+-- with the user's data (AuthCodeSuccess, refresh checks, JWT claims).
+-- granted_by: the admin who added it. NULL when the client site's backend did.
+CREATE TABLE IF NOT EXISTS client_user_roles (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    client_id VARCHAR(100) NOT NULL,
+    user_id INT NOT NULL,
+    role_name VARCHAR(50) NOT NULL,
+    granted_by INT NULL DEFAULT NULL,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    UNIQUE KEY unique_client_user_role (client_id, user_id, role_name)
+);
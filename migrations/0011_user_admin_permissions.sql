@@ -0,0 +1,18 @@
+-- 0011_user_admin_permissions.sql
+
+-- Permissions for the admin users console (/admin/users).
+-- users.read: search accounts and see their details, sessions and history
+-- users.write: edit names and role, verify emails, force password resets, revoke sessions
+INSERT IGNORE INTO permissions (name, description) VALUES
+    ('users.read', 'Search users and see their account details'),
+    ('users.write', 'Edit users, verify emails, force password resets and revoke sessions');
+
+-- admin gets every permission (again, for the new ones)
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'admin';
+
+-- moderators need to find the people they suspend
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'moderator' AND permissions.name = 'users.read';
@@ -0,0 +1,11 @@
+-- 0012_impersonation_permission.sql
+
+-- Log in as a user to see what they see (/admin/users/impersonate).
+-- Admins only: moderators don't get it.
+INSERT IGNORE INTO permissions (name, description) VALUES
+    ('users.impersonate', 'Log in as another user for a limited time');
+
+-- admin gets every permission (again, for the new one)
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'admin';
@@ -0,0 +1,47 @@
+-- 0013_client_archive_and_foreign_keys.sql
+
+-- Removing a client site.
+-- Archive: the client is switched off (is_active = 0), its sessions and auth codes go,
+-- and it drops out of the admin's client list. It can be restored later.
+-- Delete: the client_sites row goes, and the foreign keys below take everything
+-- that belongs to it along (sessions, auth codes, role grants, restrictions).
+-- Its redirect_uri lives on the client_sites row itself, so it goes with it.
+-- audit_events keeps its client_id on purpose (no foreign key): the history stays.
+ALTER TABLE client_sites
+    ADD COLUMN archived_timestamp TIMESTAMP NULL DEFAULT NULL;
+
+
+-- Rows pointing at clients that no longer exist would make the foreign keys fail
+DELETE FROM refresh_tokens
+    WHERE client_id NOT IN (SELECT client_id FROM client_sites);
+DELETE FROM auth_codes
+    WHERE client_id NOT IN (SELECT client_id FROM client_sites);
+DELETE FROM client_user_restrictions
+    WHERE client_id NOT IN (SELECT client_id FROM client_sites);
+DELETE FROM client_user_roles
+    WHERE client_id NOT IN (SELECT client_id FROM client_sites);
+
+
+-- refresh_tokens and auth_codes had no index starting with client_id
+ALTER TABLE refresh_tokens
+    ADD INDEX idx_refresh_tokens_client (client_id),
+    ADD CONSTRAINT fk_refresh_tokens_client FOREIGN KEY (client_id)
+        REFERENCES client_sites (client_id) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE auth_codes
+    ADD INDEX idx_auth_codes_client (client_id),
+    ADD CONSTRAINT fk_auth_codes_client FOREIGN KEY (client_id)
+        REFERENCES client_sites (client_id) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE client_user_restrictions
+    ADD CONSTRAINT fk_restrictions_client FOREIGN KEY (client_id)
+        REFERENCES client_sites (client_id) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE client_user_roles
+    ADD CONSTRAINT fk_client_roles_client FOREIGN KEY (client_id)
+        REFERENCES client_sites (client_id) ON DELETE CASCADE ON UPDATE CASCADE;
+
+
+-- An archived (or deleted) client's history in the audit log
+ALTER TABLE audit_events
+    ADD INDEX idx_audit_client (client_id, created_timestamp);
@@ -0,0 +1,33 @@
+-- 0014_developer_portal.sql
+
+-- Developers register and manage their own client sites (/developer).
+-- owner_user_id: the developer who registered it. NULL for clients an admin made.
+-- status: 'pending' until an admin approves it in the review queue,
+-- then 'approved' (or 'rejected'). Only approved clients can be logged in to.
+-- Clients that already exist were made by admins: they're approved.
+ALTER TABLE client_sites
+    ADD COLUMN owner_user_id INT NULL DEFAULT NULL,
+    ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'approved',
+    ADD COLUMN reviewed_by INT NULL DEFAULT NULL,
+    ADD COLUMN reviewed_timestamp TIMESTAMP NULL DEFAULT NULL,
+    ADD INDEX idx_client_sites_owner (owner_user_id),
+    ADD INDEX idx_client_sites_status (status),
+    ADD CONSTRAINT fk_client_sites_owner FOREIGN KEY (owner_user_id)
+        REFERENCES users (id) ON DELETE SET NULL;
+
+
+INSERT IGNORE INTO roles (name, description) VALUES
+    ('developer', 'Registers and manages their own client sites');
+
+INSERT IGNORE INTO permissions (name, description) VALUES
+    ('developer.access', 'Open the developer portal and register client sites'),
+    ('clients.review', 'Approve or reject client sites registered by developers');
+
+-- admin gets every permission (again, for the new ones)
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'admin';
+
+INSERT IGNORE INTO role_permissions (role_id, permission_id)
+    SELECT roles.id, permissions.id FROM roles, permissions
+    WHERE roles.name = 'developer' AND permissions.name = 'developer.access';
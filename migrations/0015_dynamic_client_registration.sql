@@ -0,0 +1,29 @@
+-- 0015_dynamic_client_registration.sql
+
+-- Clients registering themselves with POST /oauth/register (RFC 7591).
+-- An admin issues an initial access token (for a test environment, a game jam...).
+-- It allows max_uses registrations until it expires or is revoked.
+-- We only keep the SHA-256 of a token: the raw one is shown once.
+CREATE TABLE IF NOT EXISTS initial_access_tokens (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    label VARCHAR(100) NOT NULL DEFAULT '',
+    issued_by INT NULL DEFAULT NULL,
+    max_uses INT NOT NULL DEFAULT 1,
+    use_count INT NOT NULL DEFAULT 0,
+    expires_timestamp TIMESTAMP NOT NULL,
+    revoked_timestamp TIMESTAMP NULL DEFAULT NULL,
+    created_timestamp TIMESTAMP NOT NULL DEFAULT UTC_TIMESTAMP,
+    CONSTRAINT fk_initial_access_tokens_issuer FOREIGN KEY (issued_by)
+        REFERENCES users (id) ON DELETE SET NULL
+);
+
+
+-- A registered client manages itself at /oauth/register/{client_id} (RFC 7592)
+-- with the registration access token it got back. Again, only its SHA-256.
+-- initial_access_token_id: which token registered it (NULL: an admin or developer made it).
+ALTER TABLE client_sites
+    ADD COLUMN registration_token_hash CHAR(64) NULL DEFAULT NULL,
+    ADD COLUMN initial_access_token_id INT NULL DEFAULT NULL,
+    ADD CONSTRAINT fk_client_sites_initial_access_token FOREIGN KEY (initial_access_token_id)
+        REFERENCES initial_access_tokens (id) ON DELETE SET NULL;
@@ -0,0 +1,16 @@
+-- 0016_client_type_and_category_enums.sql
+
+-- client_type and category were free-form VARCHARs.
+-- Clean up what's there (the new client form sent "tool " with a space),
+-- fall back to the defaults for anything else, then only allow the known values.
+UPDATE client_sites SET client_type = LOWER(TRIM(client_type)), category = LOWER(TRIM(category));
+
+UPDATE client_sites SET client_type = 'confidential'
+    WHERE client_type NOT IN ('confidential', 'public', 'native');
+
+UPDATE client_sites SET category = 'game'
+    WHERE category NOT IN ('game', 'tool', 'service');
+
+ALTER TABLE client_sites
+    MODIFY client_type ENUM('confidential', 'public', 'native') NOT NULL DEFAULT 'confidential',
+    MODIFY category ENUM('game', 'tool', 'service') NOT NULL DEFAULT 'game';
@@ -0,0 +1,9 @@
+-- 0017_used_challenges.sql
+
+-- Proof-of-work challenges that have been used, so each one only works once.
+-- challenge_id is the token's signature. A row can go once the challenge has expired.
+CREATE TABLE IF NOT EXISTS used_challenges (
+    challenge_id CHAR(64) PRIMARY KEY,
+    expires_timestamp TIMESTAMP NOT NULL,
+    INDEX idx_used_challenges_expires (expires_timestamp)
+);
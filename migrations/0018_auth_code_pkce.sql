@@ -0,0 +1,7 @@
+-- 0018_auth_code_pkce.sql
+
+-- PKCE (RFC 7636) for auth codes. Clients without a secret send a code_challenge
+-- to the login page, and must send the matching code_verifier to redeem the code.
+-- S256 only: this is BASE64URL(SHA256(code_verifier)), 43 chars.
+ALTER TABLE auth_codes
+    ADD COLUMN code_challenge VARCHAR(128) NULL;
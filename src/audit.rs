@@ -0,0 +1,205 @@
+/*
+ *
+ *
+ *
+ *
+ * ===================
+ * ===================
+ * =====         =====
+ * =====  AUDIT  =====
+ * =====         =====
+ * ===================
+ * ===================
+ *
+ *
+ * The security audit log.
+ * Every authentication event gets a row in audit_events: who did it,
+ * whose account it was, which client site, and from which IP / user agent.
+ * Admins browse it at /admin/audit. Users see their own on the dashboard.
+ *
+ * NEVER put a password, secret, token or auth code in the details.
+ *
+ *
+*/
+
+use actix_web::HttpRequest;
+use sqlx::MySqlPool;
+
+use crate::{ auth, db, routes_utils };
+
+
+pub enum AuditEvent {
+    LoginSuccess,
+    LoginFailure,
+    Registration,
+    RegistrationPending,
+    RegistrationConfirmed,
+    PasswordChange,
+    PasswordChangeFailure,
+    NamesChange,
+    Logout,
+    SessionRevoked,
+    OtherSessionsRevoked,
+    ClientCreated,
+    ClientUpdated,
+    SecretRotated,
+    TokenRedeemed,
+    TokenRedemptionFailure,
+    LoginAlert,
+    AccountSecured,
+    PasswordReset,
+    UserSuspended,
+    SuspensionLifted,
+    ClientRestrictionAdded,
+    ClientRestrictionRemoved,
+    ClientRoleGranted,
+    ClientRoleRevoked,
+    UserUpdated,
+    PasswordResetForced,
+    AllSessionsRevoked,
+    ImpersonationStarted,
+    ImpersonationEnded,
+    StepUpSuccess,
+    StepUpFailure,
+    ClientArchived,
+    ClientRestored,
+    ClientDeleted,
+    ClientApproved,
+    ClientRejected,
+    ClientRegistered,
+    RegistrationTokenIssued,
+    RegistrationTokenRevoked,
+}
+
+
+impl AuditEvent {
+    // What gets stored in the event_type column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::LoginSuccess => "login_success",
+            AuditEvent::LoginFailure => "login_failure",
+            AuditEvent::Registration => "registration",
+            AuditEvent::RegistrationPending => "registration_pending",
+            AuditEvent::RegistrationConfirmed => "registration_confirmed",
+            AuditEvent::PasswordChange => "password_change",
+            AuditEvent::PasswordChangeFailure => "password_change_failure",
+            AuditEvent::NamesChange => "names_change",
+            AuditEvent::Logout => "logout",
+            AuditEvent::SessionRevoked => "session_revoked",
+            AuditEvent::OtherSessionsRevoked => "other_sessions_revoked",
+            AuditEvent::ClientCreated => "client_created",
+            AuditEvent::ClientUpdated => "client_updated",
+            AuditEvent::SecretRotated => "secret_rotated",
+            AuditEvent::TokenRedeemed => "token_redeemed",
+            AuditEvent::TokenRedemptionFailure => "token_redemption_failure",
+            AuditEvent::LoginAlert => "login_alert",
+            AuditEvent::AccountSecured => "account_secured",
+            AuditEvent::PasswordReset => "password_reset",
+            AuditEvent::UserSuspended => "user_suspended",
+            AuditEvent::SuspensionLifted => "suspension_lifted",
+            AuditEvent::ClientRestrictionAdded => "client_restriction_added",
+            AuditEvent::ClientRestrictionRemoved => "client_restriction_removed",
+            AuditEvent::ClientRoleGranted => "client_role_granted",
+            AuditEvent::ClientRoleRevoked => "client_role_revoked",
+            AuditEvent::UserUpdated => "user_updated",
+            AuditEvent::PasswordResetForced => "password_reset_forced",
+            AuditEvent::AllSessionsRevoked => "all_sessions_revoked",
+            AuditEvent::ImpersonationStarted => "impersonation_started",
+            AuditEvent::ImpersonationEnded => "impersonation_ended",
+            AuditEvent::StepUpSuccess => "step_up_success",
+            AuditEvent::StepUpFailure => "step_up_failure",
+            AuditEvent::ClientArchived => "client_archived",
+            AuditEvent::ClientRestored => "client_restored",
+            AuditEvent::ClientDeleted => "client_deleted",
+            AuditEvent::ClientApproved => "client_approved",
+            AuditEvent::ClientRejected => "client_rejected",
+            AuditEvent::ClientRegistered => "client_registered",
+            AuditEvent::RegistrationTokenIssued => "registration_token_issued",
+            AuditEvent::RegistrationTokenRevoked => "registration_token_revoked",
+        }
+    }
+
+    // Every event type, for the admin page's filter list
+    pub fn all_types() -> Vec<&'static str> {
+        vec![
+            AuditEvent::LoginSuccess.as_str(),
+            AuditEvent::LoginFailure.as_str(),
+            AuditEvent::Registration.as_str(),
+            AuditEvent::RegistrationPending.as_str(),
+            AuditEvent::RegistrationConfirmed.as_str(),
+            AuditEvent::PasswordChange.as_str(),
+            AuditEvent::PasswordChangeFailure.as_str(),
+            AuditEvent::NamesChange.as_str(),
+            AuditEvent::Logout.as_str(),
+            AuditEvent::SessionRevoked.as_str(),
+            AuditEvent::OtherSessionsRevoked.as_str(),
+            AuditEvent::ClientCreated.as_str(),
+            AuditEvent::ClientUpdated.as_str(),
+            AuditEvent::SecretRotated.as_str(),
+            AuditEvent::TokenRedeemed.as_str(),
+            AuditEvent::TokenRedemptionFailure.as_str(),
+            AuditEvent::LoginAlert.as_str(),
+            AuditEvent::AccountSecured.as_str(),
+            AuditEvent::PasswordReset.as_str(),
+            AuditEvent::UserSuspended.as_str(),
+            AuditEvent::SuspensionLifted.as_str(),
+            AuditEvent::ClientRestrictionAdded.as_str(),
+            AuditEvent::ClientRestrictionRemoved.as_str(),
+            AuditEvent::ClientRoleGranted.as_str(),
+            AuditEvent::ClientRoleRevoked.as_str(),
+            AuditEvent::UserUpdated.as_str(),
+            AuditEvent::PasswordResetForced.as_str(),
+            AuditEvent::AllSessionsRevoked.as_str(),
+            AuditEvent::ImpersonationStarted.as_str(),
+            AuditEvent::ImpersonationEnded.as_str(),
+            AuditEvent::StepUpSuccess.as_str(),
+            AuditEvent::StepUpFailure.as_str(),
+            AuditEvent::ClientArchived.as_str(),
+            AuditEvent::ClientRestored.as_str(),
+            AuditEvent::ClientDeleted.as_str(),
+            AuditEvent::ClientApproved.as_str(),
+            AuditEvent::ClientRejected.as_str(),
+            AuditEvent::ClientRegistered.as_str(),
+            AuditEvent::RegistrationTokenIssued.as_str(),
+            AuditEvent::RegistrationTokenRevoked.as_str(),
+        ]
+    }
+}
+
+
+/**
+ * Write one event to the audit log.
+ * The actor is whoever is logged in on this request. If nobody is
+ * (ie: logging in, registering) then the actor is the user themself.
+ * While an admin impersonates someone, the actor is the admin.
+ * A failed write is logged, never returned: auditing must not break logins.
+ */
+pub async fn record(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    event: AuditEvent,
+    user_id: Option<i32>,
+    client_id: Option<&String>,
+    details: &str
+) {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(req);
+    let actor_id: Option<i32> = match &user_req_data.impersonator {
+        Some(actor) => Some(actor.sub),
+        None => user_req_data.id.or(user_id)
+    };
+    let device: db::SessionDevice = routes_utils::session_device(req);
+
+    let new_event: db::NewAuditEvent = db::NewAuditEvent {
+        event_type: event.as_str().to_string(),
+        user_id,
+        actor_id,
+        client_id: client_id.map(|id| id.to_owned()),
+        ip_address: device.ip_address,
+        user_agent: device.user_agent,
+        details: details.chars().take(255).collect(),
+    };
+
+    if let Err(e) = db::add_audit_event(pool, &new_event).await {
+        eprintln!("Failed to record audit event {}: {e}", event.as_str());
+    }
+}
@@ -6,11 +6,20 @@ use time::{ Duration, OffsetDateTime };
 use actix_web::{ HttpMessage, HttpRequest, cookie::{Cookie, SameSite}};
 use rand::{distr::Alphanumeric, Rng};
 use std::fmt;
-use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use std::sync::LazyLock;
+use sha2::{ Digest, Sha256 };
+use base64::{ Engine, engine::general_purpose::URL_SAFE_NO_PAD };
+use argon2::{
+    Argon2, PasswordHasher, PasswordVerifier, Params, Version,
+    Algorithm as Argon2Algorithm };
 use rand_core::OsRng;
 use password_hash::{SaltString, PasswordHash};
 
-use crate::utils::{self, SupportedLangs};
+use crate::{
+    config, db, password_policy, rbac,
+    legacy_hashes::{ self, HashFormat },
+    utils::{self, SupportedLangs}
+};
 
 /* 
  * 
@@ -100,6 +109,22 @@ pub struct Claims {
     role: String,
     username: String,
     exp: usize, // expiration as a timestamp (seconds since epoch)
+    #[serde(default)]
+    client_roles: Vec<String>, // roles on the client this token is for (this auth site)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>, // only while an admin is impersonating this user
+    #[serde(default)]
+    auth_time: usize, // when the user last typed their password, as a timestamp (OIDC auth_time)
+}
+
+/*
+ * The "act" (actor) claim from RFC 8693: who is really using this token.
+ * While an admin impersonates a user, sub is the user and act is the admin.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: i32,
+    pub username: String,
 }
 
 pub enum JwtVerification {
@@ -114,6 +139,28 @@ pub enum AuthError {
     MissingJwtSecret,
 }
 
+/*
+ * Hashing can fail if the Argon2 settings in the env are impossible,
+ * or if the hasher itself errors. Either way we report it instead of panicking.
+ */
+#[derive(Debug, Clone)]
+pub enum PasswordError {
+    Params(argon2::Error),
+    Hash(password_hash::Error),
+}
+
+/*
+ * Result of checking a password (or client secret) against a stored hash.
+ * MatchOutdated means the password is right but the hash was made with
+ * different Argon2 settings than we use now, so it should be rehashed.
+ */
+#[derive(Debug, PartialEq)]
+pub enum PasswordVerification {
+    Mismatch,
+    Match,
+    MatchOutdated,
+}
+
 /* 
  * Middleware will insert this struct into every request so the routes
  * know who they're dealing with.
@@ -127,6 +174,11 @@ pub struct UserReqData {
     pub role: String, // guest, player, admin
     pub logged_in: bool,
     pub lang: utils::SupportedLangs,
+    pub csrf_token: String, // for templates to render, filled in by the middleware
+    pub csp_nonce: String, // for inline <script nonce="...">, filled in by the middleware
+    pub permissions: Vec<String>, // from the DB on every request (see rbac), filled in by the middleware
+    pub impersonator: Option<Actor>, // the admin behind this request, if they're impersonating
+    pub auth_time: usize, // when they last typed their password (0 for guests)
 }
 
 
@@ -162,6 +214,23 @@ impl std::error::Error for AuthError {
     }
 }
 
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Params(err) => write!(f, "Argon2 parameter error: {}", err),
+            PasswordError::Hash(err) => write!(f, "Password hash error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+impl PasswordVerification {
+    pub fn matches(&self) -> bool {
+        *self != PasswordVerification::Mismatch
+    }
+}
+
 /**
  * User Data for middleware to put into the Request, for the route functions to use.
  * UserReqData is built from a Claims object (taken from the JWT).
@@ -183,6 +252,11 @@ impl UserReqData {
                     role: claims.get_role().to_owned(),
                     logged_in: true,
                     lang: utils::SupportedLangs::English,
+                    csrf_token: String::new(),
+                    csp_nonce: String::new(),
+                    permissions: Vec::new(),
+                    auth_time: claims.auth_time,
+                    impersonator: claims.act,
                 }
             },
             None => {
@@ -192,6 +266,11 @@ impl UserReqData {
                     role: String::from("guest"),
                     logged_in: false,
                     lang: utils::SupportedLangs::English,
+                    csrf_token: String::new(),
+                    csp_nonce: String::new(),
+                    permissions: Vec::new(),
+                    impersonator: None,
+                    auth_time: 0,
                 }
             }
         }
@@ -202,7 +281,32 @@ impl UserReqData {
     }
 
     pub fn is_admin(&self) -> bool {
-        &self.role == "admin"
+        self.role == db::UserRole::Admin.as_str()
+    }
+
+    pub fn is_impersonated(&self) -> bool {
+        self.impersonator.is_some()
+    }
+
+    // Did they type their password in the last few minutes? (step-up, see routes_utils)
+    pub fn authenticated_within(&self, minutes: i64) -> bool {
+        let since: usize = (OffsetDateTime::now_utc() - Duration::minutes(minutes))
+            .unix_timestamp() as usize;
+        self.auth_time >= since
+    }
+
+    pub fn has_permission(&self, permission: rbac::Permission) -> bool {
+        self.permissions.iter().any(|name| name == permission.as_str())
+    }
+
+    // For the templates: should this user see the link to the admin pages?
+    pub fn can_access_admin(&self) -> bool {
+        self.has_permission(rbac::Permission::AdminAccess)
+    }
+
+    // ...and to the developer portal?
+    pub fn can_access_developer(&self) -> bool {
+        self.has_permission(rbac::Permission::DeveloperAccess)
     }
 
     pub fn lang_suffix(&self) -> &'static str {
@@ -216,10 +320,25 @@ impl UserReqData {
 
 /* functions for the Claims struct */
 impl Claims {
+    pub fn new(
+        sub: i32,
+        username: String,
+        role: String,
+        client_roles: Vec<String>,
+        act: Option<Actor>,
+        auth_time: usize,
+        lifetime: Duration
+    ) -> Self {
+        let exp: usize = (OffsetDateTime::now_utc() + lifetime).unix_timestamp() as usize;
+        Claims { sub, role, username, exp, client_roles, act, auth_time }
+    }
+
     pub fn get_sub(&self) -> i32 { self.sub }
     pub fn get_role(&self) -> &String { &self.role }
     pub fn get_username(&self) -> &String { &self.username }
     pub fn get_exp(&self) -> usize { self.exp }
+    pub fn get_act(&self) -> &Option<Actor> { &self.act }
+    pub fn get_auth_time(&self) -> usize { self.auth_time }
 }
 
 
@@ -269,30 +388,64 @@ pub fn get_user_req_data(req: &HttpRequest) -> UserReqData {
  * 
  */
 
+// How long a regular JWT lasts. The refresh token makes a new one after that.
+pub const JWT_MINUTES: i64 = 30;
+
+
+// For the auth_time claim: the user just typed their password
+pub fn auth_time_now() -> usize {
+    OffsetDateTime::now_utc().unix_timestamp() as usize
+}
+
+
 /**
  * JSON Web Token generator.
  * Take some info about the user to create a Claims struct,
  * along with the JWT secret,
  * and generate an encoded JWT String
  * to use as an access token for the user.
+ * auth_time is when they last typed their password
+ * (auth_time_now() at login, 0 if they haven't on this JWT).
  */
 pub fn generate_jwt(
     user_id: i32,
     username: String,
     role: String,
+    client_roles: Vec<String>,
+    auth_time: usize,
     //secret: &[u8]
 ) -> Result<String, AuthError> {
-    // Set expiration for 1 hour from now
-    let exp: usize = (OffsetDateTime::now_utc() + Duration::minutes(30))
-        .unix_timestamp() as usize;
-
-    let claims: Claims = Claims {
-        sub: user_id,
-        username,
-        role,
-        exp,
-    };
+    let claims: Claims = Claims::new(
+        user_id, username, role, client_roles, None, auth_time, Duration::minutes(JWT_MINUTES));
+    encode_jwt(&claims)
+}
+
+
+/**
+ * A JWT for an admin impersonating a user: the user's identity,
+ * with the admin in the act claim.
+ * It can't be refreshed. When it expires the middleware
+ * turns the admin back into themself.
+ * auth_time is 0: nobody typed this user's password.
+ */
+pub fn generate_impersonation_jwt(
+    user_id: i32,
+    username: String,
+    role: String,
+    client_roles: Vec<String>,
+    actor: Actor
+) -> Result<String, AuthError> {
+    let claims: Claims = Claims::new(
+        user_id, username, role, client_roles, Some(actor), 0,
+        Duration::minutes(config::impersonation_minutes()));
+    encode_jwt(&claims)
+}
+
 
+/**
+ * Sign the claims into a JWT String.
+ */
+pub fn encode_jwt(claims: &Claims) -> Result<String, AuthError> {
     // Get JWT secret from env. Return err if missing.
     let jwt_secret: String = get_jwt_secret()
         .map_err(|_| AuthError::MissingJwtSecret)?;
@@ -302,7 +455,7 @@ pub fn generate_jwt(
     let jwt_result: Result<String, Error> =
         encode(
             &Header::default(),
-            &claims,
+            claims,
             &EncodingKey::from_secret(jwt_secret.as_bytes())
         );
     
@@ -346,6 +499,44 @@ pub fn build_token_cookie(token: String, name: String) -> Cookie<'static> {
 
 
 
+// Where the CSRF token travels: in this cookie, and back in this request header
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+
+/**
+ * Random token for CSRF protection (double submit).
+ * The browser holds it in a cookie, and our pages send it back in a header.
+ */
+pub fn generate_csrf_token() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(43) // ~256 bits
+        .map(char::from)
+        .collect()
+}
+
+
+/**
+ * The CSRF cookie never needs to go to another site, so it's Strict.
+ * Pages read the token from a meta tag, never from this cookie,
+ * so it can be http_only like the others.
+ */
+pub fn build_csrf_cookie(token: String) -> Cookie<'static> {
+    let secure: bool = std::env::var("COOKIE_SECURE")
+        .map(|value: String| value == "true")
+        .unwrap_or(false);
+
+    Cookie::build(CSRF_COOKIE, token)
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Strict)
+        .path("/")
+        .finish()
+}
+
+
+
 /**
  * Decode the jwt string, check it against the Claims struct.
  * If the JWT is expired, we will still return the Claims (using insecure_decode)
@@ -415,43 +606,134 @@ pub fn get_jwt_secret() -> Result<String, std::env::VarError> {
 
 
 
+/**
+ * Argon2 memory, time and parallelism costs come from the env
+ * (see config). Always Argon2id, version 0x13.
+ */
+fn configured_argon2_params() -> Result<Params, PasswordError> {
+    Params::new(
+        config::argon2_memory_kib(),
+        config::argon2_time_cost(),
+        config::argon2_parallelism(),
+        None
+    ).map_err(PasswordError::Params)
+}
+
+fn configured_argon2() -> Result<Argon2<'static>, PasswordError> {
+    Ok(Argon2::new(
+        Argon2Algorithm::Argon2id,
+        Version::V0x13,
+        configured_argon2_params()?
+    ))
+}
+
+
 /**
  * Used when a user registers. We must hash their password so that the raw
  * password is never stored in the DB.
  * We take ownership of the input String so it's annihilated after fn runs.
+ * The password is NFKC-normalised first (see password_policy).
+ * Also used for client secrets.
  * @return String (hashed password)
  */
-pub fn hash_password(input_password: String) -> String {
+pub fn hash_password(input_password: String) -> Result<String, PasswordError> {
     let salt: SaltString = SaltString::generate(&mut OsRng);
+    let normalized_password: String = password_policy::normalize(&input_password);
 
     // Hash the password and return
-    Argon2::default().hash_password(
-        input_password.as_bytes(),
-        &salt
-    ).unwrap().to_string()
+    let password_hash: PasswordHash<'_> = configured_argon2()?
+        .hash_password(normalized_password.as_bytes(), &salt)
+        .map_err(PasswordError::Hash)?;
+
+    Ok(password_hash.to_string())
 }
 
 /**
  * When a user logs in we take their raw password string and verify it against
  * the stored hashed password.
- * @return bool (matches or does not match)
+ * Most hashes are ours (Argon2), but imported users may still have a bcrypt
+ * or PBKDF2-SHA256 hash from the old game servers. The prefix says which.
+ * A legacy hash that matches is always "outdated" so it gets rehashed to Argon2.
  */
-pub fn verify_password(input_password: &String, stored_hash: &String) -> bool {
+pub fn verify_password(input_password: &str, stored_hash: &str) -> PasswordVerification {
+    let legacy_matches: bool = match HashFormat::of(stored_hash) {
+        HashFormat::Argon2 => return verify_argon2_password(input_password, stored_hash),
+        // The old servers never normalised, so check the raw input
+        HashFormat::Bcrypt => legacy_hashes::verify_bcrypt(input_password, stored_hash),
+        HashFormat::Pbkdf2Sha256 => legacy_hashes::verify_pbkdf2_sha256(input_password, stored_hash),
+        HashFormat::Unknown => {
+            eprintln!("Password hash error: unknown hash format");
+            false
+        }
+    };
 
-    match PasswordHash::new(&stored_hash) {
+    if legacy_matches {
+        PasswordVerification::MatchOutdated
+    } else {
+        PasswordVerification::Mismatch
+    }
+}
+
+
+/**
+ * Verify against one of our own Argon2 hashes.
+ * The input is normalised the same way it was when it was hashed.
+ * The stored hash carries its own Argon2 settings, so old hashes still verify.
+ * If it matches we also say whether those settings are out of date.
+ */
+fn verify_argon2_password(input_password: &str, stored_hash: &str) -> PasswordVerification {
+    let normalized_password: String = password_policy::normalize(input_password);
+
+    match PasswordHash::new(stored_hash) {
         Ok(parsed_stored_hash) => {
             let argon2: Argon2<'_> = Argon2::default();
 
-            // returns a bool
-            argon2.verify_password(
-                input_password.as_bytes(),
+            let matches: bool = argon2.verify_password(
+                normalized_password.as_bytes(),
                 &parsed_stored_hash
-            ).is_ok()
+            ).is_ok();
+
+            if !matches {
+                PasswordVerification::Mismatch
+            } else if hash_is_outdated(&parsed_stored_hash) {
+                PasswordVerification::MatchOutdated
+            } else {
+                PasswordVerification::Match
+            }
         },
         Err(e) => {
             eprintln!("Password hash error: {:?}", e);
-            false
+            PasswordVerification::Mismatch
+        }
+    }
+}
+
+
+/**
+ * Was this hash made with anything other than our current Argon2 settings?
+ */
+fn hash_is_outdated(parsed_hash: &PasswordHash<'_>) -> bool {
+    let wanted_params: Params = match configured_argon2_params() {
+        Ok(params) => params,
+        Err(e) => {
+            // Can't rehash with broken settings anyway
+            eprintln!("{e}");
+            return false;
         }
+    };
+
+    if parsed_hash.algorithm != Argon2Algorithm::Argon2id.ident() ||
+        parsed_hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+
+    match Params::try_from(parsed_hash) {
+        Ok(stored_params) => {
+            stored_params.m_cost() != wanted_params.m_cost() ||
+            stored_params.t_cost() != wanted_params.t_cost() ||
+            stored_params.p_cost() != wanted_params.p_cost()
+        },
+        Err(_e) => true
     }
 }
 
@@ -462,6 +744,37 @@ pub fn verify_password(input_password: &String, stored_hash: &String) -> bool {
 
 
 
+/**
+ * A throwaway hash of a random password, made once with the configured
+ * Argon2 settings the first time somebody tries to log in as a user who
+ * doesn't exist. If hashing fails it fails for real users too, so keep
+ * the error and report it every time rather than verify against nothing.
+ */
+static DUMMY_HASH: LazyLock<Result<String, PasswordError>> = LazyLock::new(|| {
+    hash_password(generate_refresh_token())
+        .inspect_err(|e| eprintln!("Could not make the dummy password hash: {:?}", e))
+});
+
+/**
+ * For enumeration resistance: when the user doesn't exist we still run
+ * a full Argon2 verification so the response takes as long as it would
+ * for a real user with a wrong password.
+ * The result is always thrown away.
+ */
+pub fn dummy_verify_password(input_password: &str) -> Result<(), PasswordError> {
+    let dummy_hash: &String = DUMMY_HASH.as_ref().map_err(|e| e.clone())?;
+
+    let _verification: PasswordVerification = verify_password(input_password, dummy_hash);
+    Ok(())
+}
+
+
+
+
+
+
+
+
 /* 
  * 
  * 
@@ -494,6 +807,32 @@ pub fn verify_password(input_password: &String, stored_hash: &String) -> bool {
 
 
 
+/**
+ * Initial access tokens and registration access tokens (/oauth/register).
+ * Same size as a refresh token.
+ */
+pub fn generate_registration_token() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+
+/**
+ * What we store for a registration token: its SHA-256, in hex.
+ * The tokens are long and random, so a plain hash is enough (no Argon2),
+ * and we can look them up by it.
+ */
+pub fn hash_registration_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+
 /**
  * Make a totally random refresh token to save to DB.
  * When user logs in from external client, we redirect them BACK to the client
@@ -506,4 +845,81 @@ pub fn generate_auth_code() -> String {
         .take(32) // 32 chars
         .map(char::from)
         .collect()
-}
\ No newline at end of file
+}
+
+
+/**
+ * PKCE (RFC 7636), S256 only.
+ * A code_challenge is BASE64URL(SHA256(code_verifier)) with no padding: always 43 chars.
+ */
+pub fn is_valid_code_challenge(code_challenge: &str) -> bool {
+    code_challenge.len() == 43 &&
+        code_challenge.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+
+/**
+ * Does the code_verifier sent to redeem an auth code hash to the code_challenge
+ * sent with the login? Verifiers are 43 to 128 unreserved chars.
+ */
+pub fn verify_code_verifier(code_verifier: &str, code_challenge: &str) -> bool {
+    let verifier_is_valid: bool = (43..=128).contains(&code_verifier.len()) &&
+        code_verifier.bytes().all(|b| b.is_ascii_alphanumeric() || b"-._~".contains(&b));
+    if !verifier_is_valid {
+        return false;
+    }
+
+    let computed: String = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+    utils::constant_time_eq(computed.as_bytes(), code_challenge.as_bytes())
+}
+
+
+/**
+ * Random token for links we send by email (confirm registration etc).
+ * Same size as a refresh token, and just as unguessable.
+ */
+pub fn generate_email_token() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7636, appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    #[test]
+    fn rfc_verifier_matches_its_challenge() {
+        assert!(is_valid_code_challenge(CHALLENGE));
+        assert!(verify_code_verifier(VERIFIER, CHALLENGE));
+    }
+
+    #[test]
+    fn other_verifier_does_not_match() {
+        let other: String = VERIFIER.replace('d', "e");
+        assert!(!verify_code_verifier(&other, CHALLENGE));
+    }
+
+    #[test]
+    fn challenge_is_not_its_own_verifier() {
+        // ie: "plain" method is not accepted
+        assert!(!verify_code_verifier(CHALLENGE, CHALLENGE));
+    }
+
+    #[test]
+    fn malformed_verifiers_and_challenges_are_refused() {
+        assert!(!verify_code_verifier("", CHALLENGE));
+        assert!(!verify_code_verifier(&"a".repeat(129), CHALLENGE));
+        assert!(!verify_code_verifier(&format!("{VERIFIER}="), CHALLENGE));
+        assert!(!is_valid_code_challenge(""));
+        assert!(!is_valid_code_challenge(&format!("{CHALLENGE}=")));
+        assert!(!is_valid_code_challenge(&CHALLENGE.replace('-', "+")));
+    }
+}
@@ -40,8 +40,11 @@ use serde::{ Serialize, Deserialize };
 #[derive(Serialize, Deserialize)]
 pub struct AuthCodeRequest {
     pub client_id: String,
-    pub client_secret: String,
+    #[serde(default)]
+    pub client_secret: String, // public and native clients have none
     pub code: String,
+    #[serde(default)]
+    pub code_verifier: String, // PKCE, if the login sent a code_challenge
 }
 
 
@@ -51,6 +54,8 @@ pub struct AuthCodeSuccess {
     pub username: String,
     pub user_role: String,
     pub refresh_token: String,
+    #[serde(default)]
+    pub client_roles: Vec<String>, // the user's roles on THIS client site only
 }
 
 
@@ -94,6 +99,8 @@ pub struct RefreshCheckRequest {
 #[derive(Serialize, Deserialize)]
 pub struct RefreshCheckSuccess {
     is_valid: bool,
+    #[serde(default)]
+    client_roles: Vec<String>, // current roles on this client site (empty if not valid)
 }
 
 
@@ -114,6 +121,149 @@ pub enum RefreshCheckResponse {
 
 impl RefreshCheckSuccess {
     pub fn new(is_valid: bool) -> Self {
-        RefreshCheckSuccess { is_valid }
+        RefreshCheckSuccess { is_valid, client_roles: Vec::new() }
+    }
+
+    pub fn with_client_roles(is_valid: bool, client_roles: Vec<String>) -> Self {
+        RefreshCheckSuccess { is_valid, client_roles }
     }
-}
\ No newline at end of file
+}
+
+
+/* 
+ * For a client app's backend to block one of its users
+ * from the client site (or lift the block).
+ * The user keeps their account and every other site.
+ * expires_in_days: None means until further notice.
+ */
+
+#[derive(Serialize, Deserialize)]
+pub struct RestrictUserRequest {
+    pub client_id: String,
+    pub client_secret: String,
+    pub user_id: i32,
+    pub reason: String,
+    pub expires_in_days: Option<i64>,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct UnrestrictUserRequest {
+    pub client_id: String,
+    pub client_secret: String,
+    pub user_id: i32,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct RestrictionUpdated {
+    pub success: bool,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct ClientApiError {
+    pub error_code: u16,
+    pub message: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)] 
+pub enum RestrictionResponse {
+    Ok(RestrictionUpdated),
+    Err(ClientApiError)
+}
+
+
+
+/* 
+ * For a client app's backend to give one of its users
+ * a role on the client site (or take it away).
+ * Role names are up to the client app (ie: moderator, beta_tester).
+ * The response has the user's roles after the change.
+ */
+
+#[derive(Serialize, Deserialize)]
+pub struct ClientRoleRequest {
+    pub client_id: String,
+    pub client_secret: String,
+    pub user_id: i32,
+    pub role_name: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct ClientRolesUpdated {
+    pub success: bool,
+    pub client_roles: Vec<String>,
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)] 
+pub enum ClientRoleResponse {
+    Ok(ClientRolesUpdated),
+    Err(ClientApiError)
+}
+
+
+
+/* 
+ * Dynamic client registration (RFC 7591, and RFC 7592 to manage it).
+ * POST /oauth/register with "Authorization: Bearer <initial access token>".
+ * Then GET/PUT/DELETE the registration_client_uri with
+ * "Authorization: Bearer <registration_access_token>".
+ * Field names are the RFC's. description, category and client_type are ours.
+ * We have one redirect URI per client, so redirect_uris has exactly one.
+ */
+
+#[derive(Serialize, Deserialize)]
+pub struct ClientRegistrationRequest {
+    pub redirect_uris: Vec<String>,
+    pub client_name: String,
+    pub client_uri: String, // the site's domain
+    #[serde(default)]
+    pub logo_uri: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_category")]
+    pub category: String,
+    #[serde(default = "default_client_type")]
+    pub client_type: String,
+}
+
+fn default_category() -> String { String::from("game") }
+fn default_client_type() -> String { String::from("confidential") }
+
+
+/*
+ * client_secret and registration_access_token are only there
+ * when the client registers: we keep hashes, never the raw ones.
+ */
+#[derive(Serialize, Deserialize)]
+pub struct ClientRegistrationResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub client_id_issued_at: i64,
+    pub client_secret_expires_at: i64, // 0: never
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    pub registration_client_uri: String,
+    pub redirect_uris: Vec<String>,
+    pub client_name: String,
+    pub client_uri: String,
+    pub logo_uri: String,
+    pub description: String,
+    pub category: String,
+    pub client_type: String,
+}
+
+
+// error: "invalid_token", "invalid_redirect_uri" or "invalid_client_metadata"
+#[derive(Serialize, Deserialize)]
+pub struct ClientRegistrationError {
+    pub error: String,
+    pub error_description: String,
+}
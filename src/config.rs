@@ -0,0 +1,365 @@
+/*
+ *
+ *
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  CONFIGURATION  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * Per-deployment settings.
+ * Everything here is read from env variables (loaded by dotenvy in main)
+ * and falls back to a sensible default when the variable is missing
+ * or can't be parsed.
+ *
+ *
+*/
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+
+/**
+ * Read an env variable and parse it into whatever type we need.
+ * Missing or unparseable values give back the default.
+ */
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse::<T>().unwrap_or(default),
+        Err(_e) => default
+    }
+}
+
+/**
+ * Booleans are "true" or "false" in the .env file (like COOKIE_SECURE).
+ */
+pub fn env_bool(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => value.trim() == "true",
+        Err(_e) => default
+    }
+}
+
+
+/**
+ * The public base URL of this site (ie: https://auth.crankade.com).
+ * Used to build links that we send out by email.
+ */
+pub fn auth_domain() -> String {
+    std::env::var("AUTH_DOMAIN")
+        .unwrap_or(String::from("http://127.0.0.1:3000"))
+        .trim_end_matches('/')
+        .to_string()
+}
+
+
+/*
+ *
+ *
+ * ===================================
+ * ===================================
+ * =====                         =====
+ * =====  ENUMERATION RESISTANCE  =====
+ * =====                         =====
+ * ===================================
+ * ===================================
+ *
+ *
+ * When this is on, login failures all look the same (whether or not the
+ * user exists) and registration never tells anyone that an email address
+ * is already in the DB. Instead the registration is confirmed by email.
+ *
+*/
+
+pub fn enumeration_resistance() -> bool {
+    env_bool("ENUMERATION_RESISTANCE", false)
+}
+
+// How long a pending (unconfirmed) registration lives before it's useless
+pub fn pending_registration_hours() -> i64 {
+    env_or("PENDING_REGISTRATION_HOURS", 24)
+}
+
+
+/*
+ *
+ *
+ * ============================
+ * ============================
+ * =====                  =====
+ * =====  ARGON2 HASHING  =====
+ * =====                  =====
+ * ============================
+ * ============================
+ *
+ *
+ * Costs for new password (and client secret) hashes.
+ * Defaults are the argon2 crate's defaults (OWASP minimum).
+ * Changing them makes old hashes "outdated", and they get rehashed
+ * the next time the password is verified successfully.
+ *
+*/
+
+// memory cost in KiB
+pub fn argon2_memory_kib() -> u32 {
+    env_or("ARGON2_MEMORY_KIB", 19456)
+}
+
+// number of iterations
+pub fn argon2_time_cost() -> u32 {
+    env_or("ARGON2_TIME_COST", 2)
+}
+
+// degree of parallelism (lanes)
+pub fn argon2_parallelism() -> u32 {
+    env_or("ARGON2_PARALLELISM", 1)
+}
+
+
+/*
+ *
+ *
+ * ==========================
+ * ==========================
+ * =====                =====
+ * =====  LOGIN ALERTS  =====
+ * =====                =====
+ * ==========================
+ * ==========================
+ *
+ *
+ * When to email a user about a login (new device, new IP range,
+ * or a login right after a burst of failed attempts),
+ * and how long the links in those emails work.
+ *
+*/
+
+// This many failed logins...
+pub fn failure_burst_count() -> i64 {
+    env_or("FAILURE_BURST_COUNT", 3)
+}
+
+// ...within this many minutes before a successful one is suspicious
+pub fn failure_burst_minutes() -> i64 {
+    env_or("FAILURE_BURST_MINUTES", 15)
+}
+
+// How long the "this wasn't me" link in a login alert works
+pub fn not_me_link_days() -> i64 {
+    env_or("NOT_ME_LINK_DAYS", 7)
+}
+
+// How long a password reset link works
+pub fn password_reset_minutes() -> i64 {
+    env_or("PASSWORD_RESET_MINUTES", 60)
+}
+
+/**
+ * Reverse proxies (comma-separated IP addresses) whose Forwarded and
+ * X-Forwarded-For headers we believe. Empty means we take the client's
+ * IP from the socket and ignore those headers, since anyone can send them.
+ */
+pub fn trusted_proxies() -> Vec<IpAddr> {
+    std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|proxy| proxy.trim().parse::<IpAddr>().ok())
+        .collect()
+}
+
+// A header our reverse proxy / CDN sets with the client's location
+// (ie: Cloudflare's CF-IPCountry). Empty means we don't have one.
+pub fn location_header() -> String {
+    env_or("LOCATION_HEADER", String::from("CF-IPCountry"))
+}
+
+
+/*
+ *
+ *
+ * ==============================
+ * ==============================
+ * =====                    =====
+ * =====  SECURITY HEADERS  =====
+ * =====                    =====
+ * ==============================
+ * ==============================
+ *
+ *
+ * Defaults for the headers security_headers_middleware adds to every response.
+ * A route can still override the policy for itself (see middleware::SecurityPolicy).
+ *
+*/
+
+// Strict-Transport-Security max-age in seconds. 0 turns HSTS off (ie: local http).
+pub fn hsts_max_age() -> i64 {
+    env_or("HSTS_MAX_AGE", 31536000)
+}
+
+// Who may put our pages in a frame: 'none', 'self', or a list of origins
+pub fn frame_ancestors() -> String {
+    env_or("FRAME_ANCESTORS", String::from("'none'"))
+}
+
+// Origins (space separated) that may frame the login page, ie: a game's own site.
+// Empty keeps the login page under FRAME_ANCESTORS like everything else.
+pub fn login_frame_ancestors() -> String {
+    env_or("LOGIN_FRAME_ANCESTORS", String::new())
+}
+
+pub fn referrer_policy() -> String {
+    env_or("REFERRER_POLICY", String::from("strict-origin-when-cross-origin"))
+}
+
+// Browser features our pages never need
+pub fn permissions_policy() -> String {
+    env_or(
+        "PERMISSIONS_POLICY",
+        String::from("camera=(), microphone=(), geolocation=(), payment=(), usb=()"))
+}
+
+
+/*
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  PROOF OF WORK  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * The bot challenge on registration and on logins after repeated failures.
+ * Each extra bit of difficulty doubles the average work in the browser.
+ *
+*/
+
+// Leading zero bits the solution's SHA-256 needs. 18 is a second or two on a laptop.
+pub fn pow_difficulty() -> u32 {
+    env_or("POW_DIFFICULTY", 18)
+}
+
+// How long a challenge can be solved and used
+pub fn pow_challenge_minutes() -> i64 {
+    env_or("POW_CHALLENGE_MINUTES", 5)
+}
+
+// This many failed logins (per account or per IP)...
+pub fn pow_login_failures() -> i64 {
+    env_or("POW_LOGIN_FAILURES", 3)
+}
+
+// ...within this many minutes, and logins need a challenge
+pub fn pow_failure_minutes() -> i64 {
+    env_or("POW_FAILURE_MINUTES", 15)
+}
+
+
+
+/*
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  IMPERSONATION  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * Admins logging in as a user to see what they see.
+ *
+*/
+
+// How long an impersonation lasts before the admin is back to themself
+pub fn impersonation_minutes() -> i64 {
+    env_or("IMPERSONATION_MINUTES", 15)
+}
+
+
+
+/*
+ *
+ *
+ * =====================
+ * =====================
+ * =====           =====
+ * =====  STEP-UP  =====
+ * =====           =====
+ * =====================
+ * =====================
+ *
+ *
+ * Sensitive admin actions (client secrets, client edits, role changes)
+ * want a recent password, not just a valid (maybe refreshed for days) JWT.
+ *
+*/
+
+// How recently the admin must have typed their password
+pub fn step_up_minutes() -> i64 {
+    env_or("STEP_UP_MINUTES", 10)
+}
+
+// This many wrong passwords at the step-up...
+pub fn step_up_failures() -> i64 {
+    env_or("STEP_UP_FAILURES", 5)
+}
+
+// ...within this many minutes, and it stops checking passwords for a while
+pub fn step_up_lockout_minutes() -> i64 {
+    env_or("STEP_UP_LOCKOUT_MINUTES", 15)
+}
+
+
+
+/*
+ *
+ *
+ * ==============================
+ * ==============================
+ * =====                    =====
+ * =====  DEVELOPER PORTAL  =====
+ * =====                    =====
+ * ==============================
+ * ==============================
+ *
+ *
+ * Developers registering and running their own client sites.
+ *
+*/
+
+// How far back the portal's usage numbers (logins, users) look
+pub fn developer_usage_days() -> i64 {
+    env_or("DEVELOPER_USAGE_DAYS", 30)
+}
+
+
+
+/*
+ *
+ *
+ * =================================
+ * =================================
+ * =====                       =====
+ * =====  CLIENT REGISTRATION  =====
+ * =====                       =====
+ * =================================
+ * =================================
+ *
+ *
+ * Clients registering themselves at /oauth/register (RFC 7591).
+ *
+*/
+
+// How long an initial access token (issued by an admin) can be used
+pub fn initial_access_token_days() -> i64 {
+    env_or("INITIAL_ACCESS_TOKEN_DAYS", 7)
+}
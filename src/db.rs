@@ -3,11 +3,10 @@
 use sqlx::{MySqlPool };
 use time::{ OffsetDateTime, Duration };
 use anyhow::{ Result, anyhow };
-use serde;
 
 use crate::{
     utils,
-    auth,
+    config,
 };
 
 /* 
@@ -35,11 +34,116 @@ pub enum UserRole {
     Player,
 }
 
+impl UserRole {
+    // What's stored in users.role (and roles.name)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::Admin => "admin",
+            UserRole::Player => "player",
+        }
+    }
+}
+
+
+/**
+ * client_sites.client_type.
+ * Confidential: a backend that can keep a secret. The only kind that gets one.
+ * Public: a browser app. Native: mobile/desktop, may redirect to a loopback address.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq,
+    serde::Serialize, serde::Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ClientType {
+    #[default]
+    Confidential,
+    Public,
+    Native,
+}
+
+impl ClientType {
+    // What's stored in client_sites.client_type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientType::Confidential => "confidential",
+            ClientType::Public => "public",
+            ClientType::Native => "native",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ClientType> {
+        match value {
+            "confidential" => Some(ClientType::Confidential),
+            "public" => Some(ClientType::Public),
+            "native" => Some(ClientType::Native),
+            _ => None
+        }
+    }
+
+    pub fn has_secret(&self) -> bool { *self == ClientType::Confidential }
+    pub fn allows_loopback(&self) -> bool { *self == ClientType::Native }
+}
+
+impl std::fmt::Display for ClientType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+// client_sites.category
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq,
+    serde::Serialize, serde::Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ClientCategory {
+    #[default]
+    Game,
+    Tool,
+    Service,
+}
+
+impl ClientCategory {
+    // What's stored in client_sites.category
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientCategory::Game => "game",
+            ClientCategory::Tool => "tool",
+            ClientCategory::Service => "service",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ClientCategory> {
+        match value {
+            "game" => Some(ClientCategory::Game),
+            "tool" => Some(ClientCategory::Tool),
+            "service" => Some(ClientCategory::Service),
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for ClientCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug)]
 struct Count {
     count: i64,
 }
 
+// One name column (ie: a role or permission name)
+struct NameRow {
+    name: String,
+}
+
+// One id column
+struct IdRow {
+    id: i32,
+}
+
 
 
 #[derive(serde::Serialize)]
@@ -48,6 +152,9 @@ pub struct AuthCodeData {
     pub user_id: i32,
     pub client_id: String,
     pub code: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub code_challenge: Option<String>, // PKCE, S256
     pub expires_timestamp: OffsetDateTime
 }
 
@@ -79,17 +186,218 @@ pub struct UsernameAndRole {
 }
 
 
+/**
+ * One session: a refresh token held by one device for one client site.
+ */
 #[derive(serde::Serialize)]
 pub struct RefreshToken {
     id: i32,
     user_id: i32,
     client_id: String,
     token: String,
+    device_label: String,
+    user_agent: String,
+    ip_address: String,
     created_timestamp: OffsetDateTime,
+    last_used_timestamp: OffsetDateTime,
     expires_timestamp: OffsetDateTime
 }
 
 
+/**
+ * A session as shown to its owner on the dashboard,
+ * with the name and logo of the client site it's for.
+ */
+pub struct SessionRow {
+    pub id: i32,
+    pub client_id: String,
+    pub client_name: String,
+    pub client_logo_url: String,
+    pub token: String,
+    pub device_label: String,
+    pub ip_address: String,
+    pub created_timestamp: OffsetDateTime,
+    pub last_used_timestamp: OffsetDateTime,
+    pub expires_timestamp: OffsetDateTime,
+}
+
+
+/**
+ * A row for the audit_events table (see the audit module).
+ */
+pub struct NewAuditEvent {
+    pub event_type: String,
+    pub user_id: Option<i32>,
+    pub actor_id: Option<i32>,
+    pub client_id: Option<String>,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub details: String,
+}
+
+
+/**
+ * An audit event read back from the DB, with the username
+ * of the account it's about (if there is one).
+ */
+pub struct AuditEventRow {
+    pub id: i32,
+    pub event_type: String,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub actor_id: Option<i32>,
+    pub client_id: Option<String>,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub details: String,
+    pub created_timestamp: OffsetDateTime,
+}
+
+
+/**
+ * A suspension that is in force right now.
+ */
+pub struct Suspension {
+    pub id: i32,
+    pub user_id: i32,
+    pub reason: String,
+    pub issued_by: i32,
+    pub start_timestamp: OffsetDateTime,
+    pub end_timestamp: Option<OffsetDateTime>, // None: until further notice
+}
+
+
+/**
+ * A suspension for the admin's list, with the usernames filled in.
+ */
+pub struct SuspensionRow {
+    pub id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub reason: String,
+    pub issued_by_username: Option<String>,
+    pub start_timestamp: OffsetDateTime,
+    pub end_timestamp: Option<OffsetDateTime>,
+}
+
+
+/**
+ * A user blocked from one client site.
+ */
+pub struct ClientRestriction {
+    pub id: i32,
+    pub client_id: String,
+    pub user_id: i32,
+    pub reason: String,
+    pub issued_by: Option<i32>, // None: set by the client site's backend
+    pub created_timestamp: OffsetDateTime,
+    pub expires_timestamp: Option<OffsetDateTime>, // None: until further notice
+}
+
+
+/**
+ * A client restriction for the edit client page, with the usernames filled in.
+ */
+pub struct ClientRestrictionRow {
+    pub user_id: i32,
+    pub username: String,
+    pub reason: String,
+    pub issued_by_username: Option<String>,
+    pub created_timestamp: OffsetDateTime,
+    pub expires_timestamp: Option<OffsetDateTime>,
+}
+
+
+/**
+ * A role one user has on one client site, for the edit client page.
+ */
+pub struct ClientUserRoleRow {
+    pub user_id: i32,
+    pub username: String,
+    pub role_name: String,
+    pub granted_by_username: Option<String>,
+    pub created_timestamp: OffsetDateTime,
+}
+
+
+/**
+ * One user in the admin's search results.
+ */
+pub struct UserListRow {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub email_verified: i8,
+    pub created_timestamp: OffsetDateTime,
+}
+
+
+/**
+ * Filters for the admin's audit log page. None means "any".
+ */
+pub struct AuditFilter {
+    pub event_type: Option<String>,
+    pub username: Option<String>,
+    pub client_id: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+
+// account_tokens.purpose values
+pub const TOKEN_NOT_ME: &str = "not_me";
+pub const TOKEN_PASSWORD_RESET: &str = "password_reset";
+pub const TOKEN_SUSPENSION_NOTICE: &str = "suspended";
+
+
+// client_sites.status values (see CLIENT REVIEW)
+pub const CLIENT_PENDING: &str = "pending";
+pub const CLIENT_APPROVED: &str = "approved";
+pub const CLIENT_REJECTED: &str = "rejected";
+
+
+/**
+ * A single-use token from a link we emailed
+ * ("this wasn't me", password reset).
+ */
+pub struct AccountToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token: String,
+    pub purpose: String,
+    pub expires_timestamp: OffsetDateTime,
+}
+
+
+pub struct IpAddress {
+    pub ip_address: String,
+}
+
+
+/**
+ * The device a session (or auth code) belongs to.
+ * Built from the request headers when the user logs in.
+ */
+pub struct SessionDevice {
+    pub device_label: String,
+    pub user_agent: String,
+    pub ip_address: String,
+}
+
+
+/**
+ * A registration waiting for its email confirmation link to be clicked.
+ */
+pub struct PendingRegistration {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub password_hash: String,
+    pub token: String,
+    pub expires_timestamp: OffsetDateTime,
+}
+
+
 pub struct ClientLinkData {
     pub domain: String,
     pub logo_url: String,
@@ -108,8 +416,8 @@ pub struct UpdateClientData {
     pub redirect_uri: String,
     pub logo_url: String,
     pub description: String,
-    pub category: String,
-    pub client_type: String,
+    pub category: ClientCategory,
+    pub client_type: ClientType,
     pub is_active: bool,
 }
 
@@ -119,7 +427,8 @@ pub struct UpdateClientSecret {
 
 
 pub struct ClientSecret {
-    pub hashed_client_secret: String,
+    pub hashed_client_secret: String, // empty if it has none
+    pub client_type: ClientType,
 }
 
 /**
@@ -133,9 +442,13 @@ pub struct NewClientData {
     pub redirect_uri: String,
     pub logo_url: String,
     pub description: String,
-    pub category: String,
-    pub client_type: String,
+    pub category: ClientCategory,
+    pub client_type: ClientType,
     pub is_active: bool,
+    pub owner_user_id: Option<i32>,
+    pub status: String,
+    pub registration_token_hash: Option<String>, // only for /oauth/register
+    pub initial_access_token_id: Option<i32>,
 }
 
 /**
@@ -150,11 +463,63 @@ pub struct ClientData {
     pub redirect_uri: String,
     pub logo_url: String,
     pub description: String,
-    pub category: String,
-    pub client_type: String,
+    pub category: ClientCategory,
+    pub client_type: ClientType,
     is_active: i8,
     is_internal: i8,
     pub created_timestamp: OffsetDateTime,
+    pub archived_timestamp: Option<OffsetDateTime>,
+    pub owner_user_id: Option<i32>,
+    pub status: String,
+}
+
+
+/**
+ * A developer's own client site, for the developer portal.
+ * The counts are its usage: sessions still open, and logins
+ * (auth codes redeemed) over the last USAGE_DAYS days.
+ */
+pub struct DeveloperClientRow {
+    pub client_id: String,
+    pub name: String,
+    pub domain: String,
+    pub redirect_uri: String,
+    pub logo_url: String,
+    pub description: String,
+    pub category: ClientCategory,
+    pub client_type: ClientType,
+    pub status: String,
+    is_active: i8,
+    pub created_timestamp: OffsetDateTime,
+    pub open_sessions: i64,
+    pub recent_logins: i64,
+    pub recent_users: i64,
+}
+
+
+// An initial access token, for the admin's list (never the token itself)
+pub struct InitialAccessTokenRow {
+    pub id: i32,
+    pub label: String,
+    pub issued_by_username: Option<String>,
+    pub max_uses: i32,
+    pub use_count: i32,
+    pub expires_timestamp: OffsetDateTime,
+    pub created_timestamp: OffsetDateTime,
+}
+
+
+// A client site waiting in the admin's review queue
+pub struct PendingClientRow {
+    pub client_id: String,
+    pub name: String,
+    pub domain: String,
+    pub redirect_uri: String,
+    pub description: String,
+    pub category: ClientCategory,
+    pub client_type: ClientType,
+    pub owner_username: Option<String>,
+    pub created_timestamp: OffsetDateTime,
 }
 
 
@@ -178,13 +543,25 @@ pub struct RedirectUri {
 impl ClientData {
     pub fn get_is_active(&self) -> bool { self.is_active == 1 }
     pub fn get_is_internal(&self) -> bool { self.is_internal == 1 }
+    pub fn is_archived(&self) -> bool { self.archived_timestamp.is_some() }
+    pub fn is_approved(&self) -> bool { self.status == CLIENT_APPROVED }
+}
+
+
+impl DeveloperClientRow {
+    pub fn get_is_active(&self) -> bool { self.is_active == 1 }
 }
 
 
 impl RefreshToken {
+    pub fn get_id(&self) -> i32 { self.id }
     pub fn get_token(&self) -> &String { &self.token }
     pub fn get_client_id(&self) -> &String { &self.client_id }
     pub fn get_user_id(&self) -> i32 { self.user_id }
+    pub fn get_device_label(&self) -> &String { &self.device_label }
+    pub fn get_user_agent(&self) -> &String { &self.user_agent }
+    pub fn get_ip_address(&self) -> &String { &self.ip_address }
+    pub fn get_last_used_timestamp(&self) -> &OffsetDateTime { &self.last_used_timestamp }
     pub fn get_created_timestamp(&self) -> &OffsetDateTime { &self.created_timestamp }
     pub fn get_expires_timestamp(&self) -> &OffsetDateTime { &self.expires_timestamp }
 
@@ -193,10 +570,46 @@ impl RefreshToken {
     }
 }
 
+impl AccountToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_timestamp < OffsetDateTime::now_utc()
+    }
+}
+
+impl PendingRegistration {
+    pub fn is_expired(&self) -> bool {
+        self.expires_timestamp < OffsetDateTime::now_utc()
+    }
+}
+
 impl AuthCodeData {
     pub fn is_expired(&self) -> bool {
         self.expires_timestamp < OffsetDateTime::now_utc()
     }
+
+    // The device that asked for this code
+    pub fn get_session_device(&self) -> SessionDevice {
+        SessionDevice::new(self.user_agent.to_owned(), self.ip_address.to_owned())
+    }
+}
+
+
+impl SessionDevice {
+    /**
+     * The label is a short readable name (ie: "Firefox on Windows")
+     * worked out from the user agent.
+     */
+    pub fn new(user_agent: String, ip_address: String) -> Self {
+        // Keep within the column sizes
+        let user_agent: String = user_agent.chars().take(512).collect();
+        let ip_address: String = ip_address.chars().take(45).collect();
+
+        SessionDevice {
+            device_label: utils::device_label(&user_agent),
+            user_agent,
+            ip_address
+        }
+    }
 }
 
 
@@ -206,6 +619,7 @@ impl User {
         self.email_verified != 0
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: i32,
         username: String,
@@ -231,6 +645,8 @@ impl User {
     pub fn get_id(&self) -> i32 { self.id }
     pub fn get_role(&self) -> &String { &self.role }
     pub fn get_username(&self) -> &String { &self.username }
+    pub fn get_email(&self) -> &String { &self.email }
+    pub fn get_created_timestamp(&self) -> &OffsetDateTime { &self.created_timestamp }
 
     pub fn get_first_name(&self) -> String {
         match self.first_name.clone() {
@@ -280,7 +696,8 @@ pub async fn get_auth_code_data(
 ) -> Result<Option<AuthCodeData>> {
     Ok(sqlx::query_as!(
             AuthCodeData,
-            "SELECT id, user_id, client_id, code, expires_timestamp
+            "SELECT id, user_id, client_id, code,
+                user_agent, ip_address, code_challenge, expires_timestamp
             FROM auth_codes WHERE code = ?",
             code
         ).fetch_optional(pool).await?)
@@ -301,13 +718,15 @@ pub async fn get_user_by_username(
 }
 
 
+// None for an unknown, inactive OR unapproved client: nobody gets sent to a disabled site
 pub async fn get_redirect_uri(
     pool: &MySqlPool,
     client_id: &String
 ) -> Result<Option<String>> {
     let redirect_option: Option<RedirectUri> = sqlx::query_as!(
             RedirectUri,
-            "SELECT redirect_uri FROM client_sites WHERE client_id = ?",
+            "SELECT redirect_uri FROM client_sites
+                WHERE client_id = ? AND is_active = 1 AND status = 'approved'",
             client_id
         ).fetch_optional(pool).await?;
     
@@ -376,90 +795,397 @@ pub async fn get_user_by_id(
 
 
 /**
- * Get a refresh token for specified user and client site.
+ * Get the session a refresh token belongs to.
+ * Callers must still check that the user and client site are the ones they expect.
  */
 pub async fn get_refresh_token(
     pool: &MySqlPool,
-    user_id: i32,
-    client_id: String
+    token: &String
 ) -> Result<Option<RefreshToken>> {
     Ok(sqlx::query_as!(
         RefreshToken,
-        "SELECT id, user_id, client_id,
-            token, created_timestamp, expires_timestamp
-            FROM refresh_tokens WHERE user_id = ? AND client_id = ?",
-        user_id, client_id
+        "SELECT id, user_id, client_id, token,
+            device_label, user_agent, ip_address,
+            created_timestamp, last_used_timestamp, expires_timestamp
+            FROM refresh_tokens WHERE token = ?",
+        token
     ).fetch_optional(pool).await?)
 }
 
 /**
- * Get a collection of all the client_ids and names in the client_sites table.
- * These are references for the sake of lists, where the client_id can also
- * provide a handle for a link to an edit page (or whatever)
+ * Every live session (refresh token) the user has, on every device and client site.
+ * Most recently used first.
  */
-pub async fn get_client_refs(pool: &MySqlPool) -> Result<Vec<ClientRef>> {
-    let client_refs: Vec<ClientRef> = sqlx::query_as!(
-        ClientRef,
-        "SELECT client_id, name, logo_url FROM client_sites"
+pub async fn get_sessions_for_user(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<Vec<SessionRow>> {
+    let sessions: Vec<SessionRow> = sqlx::query_as!(
+        SessionRow,
+        "SELECT refresh_tokens.id, refresh_tokens.client_id,
+            client_sites.name AS client_name,
+            client_sites.logo_url AS client_logo_url,
+            refresh_tokens.token, refresh_tokens.device_label,
+            refresh_tokens.ip_address, refresh_tokens.created_timestamp,
+            refresh_tokens.last_used_timestamp, refresh_tokens.expires_timestamp
+            FROM refresh_tokens
+            JOIN client_sites ON client_sites.client_id = refresh_tokens.client_id
+            WHERE refresh_tokens.user_id = ? AND refresh_tokens.expires_timestamp > ?
+            ORDER BY refresh_tokens.last_used_timestamp DESC",
+        user_id, OffsetDateTime::now_utc()
     ).fetch_all(pool).await?;
 
-    Ok(client_refs)
+    Ok(sessions)
 }
 
 
 /**
- * Get a collection of link data (name, domain, description, logo) for sites
- * that are ACTIVE and are NOT the auth site.
+ * One page of users for the admin, found by part of their username or email.
+ * No search means everyone. Newest accounts first.
  */
-pub async fn get_client_links(pool: &MySqlPool) -> Result<Vec<ClientLinkData>> {
-    let client_refs: Vec<ClientLinkData> = sqlx::query_as!(
-        ClientLinkData,
-        "SELECT name, logo_url, domain, description FROM client_sites 
-        WHERE is_active = 1 AND is_internal != 1"
+pub async fn search_users(
+    pool: &MySqlPool,
+    search: &Option<String>,
+    limit: i64,
+    offset: i64
+) -> Result<Vec<UserListRow>> {
+    // % and _ typed in the search box are literal, not wildcards
+    let pattern: Option<String> = search.as_ref().map(|text| {
+        format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+    });
+
+    let users: Vec<UserListRow> = sqlx::query_as!(
+        UserListRow,
+        "SELECT id, username, email, role, email_verified, created_timestamp
+            FROM users
+            WHERE (? IS NULL OR username LIKE ? OR email LIKE ?)
+            ORDER BY created_timestamp DESC, id DESC
+            LIMIT ? OFFSET ?",
+        pattern, pattern, pattern,
+        limit, offset
     ).fetch_all(pool).await?;
 
-    Ok(client_refs)
+    Ok(users)
+}
+
+
+// Every role there is (for the admin's role <select>)
+pub async fn get_role_names(pool: &MySqlPool) -> Result<Vec<String>> {
+    let rows: Vec<NameRow> = sqlx::query_as!(
+        NameRow,
+        "SELECT name FROM roles ORDER BY name"
+    ).fetch_all(pool).await?;
+
+    Ok(rows.into_iter().map(|row| row.name).collect())
 }
 
 
+pub async fn get_permission_names(pool: &MySqlPool) -> Result<Vec<String>> {
+    let rows: Vec<NameRow> = sqlx::query_as!(
+        NameRow,
+        "SELECT name FROM permissions"
+    ).fetch_all(pool).await?;
 
-pub async fn get_client_by_client_id(
+    Ok(rows.into_iter().map(|row| row.name).collect())
+}
+
+
+// Extra roles granted in user_roles (not their base role in users.role)
+pub async fn get_extra_role_names(pool: &MySqlPool, user_id: i32) -> Result<Vec<String>> {
+    let rows: Vec<NameRow> = sqlx::query_as!(
+        NameRow,
+        "SELECT roles.name FROM roles
+            JOIN user_roles ON user_roles.role_id = roles.id
+            WHERE user_roles.user_id = ?
+            ORDER BY roles.name",
+        user_id
+    ).fetch_all(pool).await?;
+
+    Ok(rows.into_iter().map(|row| row.name).collect())
+}
+
+
+/**
+ * One page of the audit log, newest first, for the admin.
+ */
+pub async fn get_audit_events(
     pool: &MySqlPool,
-    client_id: &String
-) -> Result<Option<ClientData>> {
-    Ok(sqlx::query_as!(
-        ClientData,
-        "SELECT id, client_id, hashed_client_secret,
-            name, domain, redirect_uri,
-            description, category, logo_url, is_active,
-            client_type, is_internal, created_timestamp
-            FROM client_sites WHERE client_id = ?",
-        client_id
-    ).fetch_optional(pool).await?)
+    filter: &AuditFilter,
+    limit: i64,
+    offset: i64
+) -> Result<Vec<AuditEventRow>> {
+    let events: Vec<AuditEventRow> = sqlx::query_as!(
+        AuditEventRow,
+        "SELECT audit_events.id, audit_events.event_type, audit_events.user_id,
+            users.username AS `username?`, audit_events.actor_id,
+            audit_events.client_id, audit_events.ip_address,
+            audit_events.user_agent, audit_events.details,
+            audit_events.created_timestamp
+            FROM audit_events
+            LEFT JOIN users ON users.id = audit_events.user_id
+            WHERE (? IS NULL OR audit_events.event_type = ?)
+                AND (? IS NULL OR users.username = ?)
+                AND (? IS NULL OR audit_events.client_id = ?)
+                AND (? IS NULL OR audit_events.ip_address = ?)
+            ORDER BY audit_events.created_timestamp DESC, audit_events.id DESC
+            LIMIT ? OFFSET ?",
+        filter.event_type, filter.event_type,
+        filter.username, filter.username,
+        filter.client_id, filter.client_id,
+        filter.ip_address, filter.ip_address,
+        limit, offset
+    ).fetch_all(pool).await?;
+
+    Ok(events)
 }
 
 
-pub async fn get_client_secret(
+/**
+ * A user's own security history, newest first.
+ */
+pub async fn get_audit_events_for_user(
     pool: &MySqlPool,
-    client_id: &String
-) -> Result<Option<ClientSecret>> {
-    Ok(sqlx::query_as!(
-        ClientSecret,
-        "SELECT hashed_client_secret
-            FROM client_sites WHERE client_id = ?",
-        client_id
-    ).fetch_optional(pool).await?)
+    user_id: i32,
+    limit: i64
+) -> Result<Vec<AuditEventRow>> {
+    let events: Vec<AuditEventRow> = sqlx::query_as!(
+        AuditEventRow,
+        "SELECT audit_events.id, audit_events.event_type, audit_events.user_id,
+            users.username AS `username?`, audit_events.actor_id,
+            audit_events.client_id, audit_events.ip_address,
+            audit_events.user_agent, audit_events.details,
+            audit_events.created_timestamp
+            FROM audit_events
+            LEFT JOIN users ON users.id = audit_events.user_id
+            WHERE audit_events.user_id = ?
+            ORDER BY audit_events.created_timestamp DESC, audit_events.id DESC
+            LIMIT ?",
+        user_id, limit
+    ).fetch_all(pool).await?;
+
+    Ok(events)
 }
 
 
-/* 
- * 
- * 
- * 
- * 
- * 
- * 
- * ==============================
+/**
+ * Has this user logged in (or held a session) from this exact user agent before?
+ */
+pub async fn user_agent_known(
+    pool: &MySqlPool,
+    user_id: i32,
+    user_agent: &String
+) -> Result<bool> {
+    let sessions: Option<Count> = sqlx::query_as!(
+        Count,
+        "SELECT COUNT(*) as count FROM refresh_tokens WHERE user_id = ? AND user_agent = ?",
+        user_id, user_agent
+    ).fetch_optional(pool).await?;
+
+    if sessions.map(|c| c.count).unwrap_or(0) > 0 {
+        return Ok(true);
+    }
+
+    let logins: Option<Count> = sqlx::query_as!(
+        Count,
+        "SELECT COUNT(*) as count FROM audit_events
+            WHERE user_id = ? AND event_type = 'login_success' AND user_agent = ?",
+        user_id, user_agent
+    ).fetch_optional(pool).await?;
+
+    Ok(logins.map(|c| c.count).unwrap_or(0) > 0)
+}
+
+
+/**
+ * Every IP address this user has logged in or held a session from.
+ */
+pub async fn get_known_ip_addresses(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<Vec<String>> {
+    let addresses: Vec<IpAddress> = sqlx::query_as!(
+        IpAddress,
+        "SELECT ip_address FROM refresh_tokens WHERE user_id = ?
+        UNION
+        SELECT ip_address FROM audit_events
+            WHERE user_id = ? AND event_type = 'login_success'
+        LIMIT 500",
+        user_id, user_id
+    ).fetch_all(pool).await?;
+
+    Ok(addresses.into_iter().map(|a| a.ip_address).collect())
+}
+
+
+/**
+ * How many events of one type this user has had since a point in time.
+ * ie: failed logins in the last 15 minutes.
+ */
+pub async fn count_audit_events_since(
+    pool: &MySqlPool,
+    user_id: i32,
+    event_type: &str,
+    since: OffsetDateTime
+) -> Result<i64> {
+    let count_option: Option<Count> = sqlx::query_as!(
+        Count,
+        "SELECT COUNT(*) as count FROM audit_events
+            WHERE user_id = ? AND event_type = ? AND created_timestamp > ?",
+        user_id, event_type, since
+    ).fetch_optional(pool).await?;
+
+    Ok(count_option.map(|c| c.count).unwrap_or(0))
+}
+
+
+// Same, by IP address: catches failures against unknown usernames too
+pub async fn count_audit_events_from_ip_since(
+    pool: &MySqlPool,
+    ip_address: &String,
+    event_type: &str,
+    since: OffsetDateTime
+) -> Result<i64> {
+    let count_option: Option<Count> = sqlx::query_as!(
+        Count,
+        "SELECT COUNT(*) as count FROM audit_events
+            WHERE ip_address = ? AND event_type = ? AND created_timestamp > ?",
+        ip_address, event_type, since
+    ).fetch_optional(pool).await?;
+
+    Ok(count_option.map(|c| c.count).unwrap_or(0))
+}
+
+
+pub async fn get_account_token(
+    pool: &MySqlPool,
+    token: &String,
+    purpose: &str
+) -> Result<Option<AccountToken>> {
+    Ok(sqlx::query_as!(
+        AccountToken,
+        "SELECT id, user_id, token, purpose, expires_timestamp
+            FROM account_tokens WHERE token = ? AND purpose = ?",
+        token, purpose
+    ).fetch_optional(pool).await?)
+}
+
+
+/**
+ * Get a collection of all the client_ids and names in the client_sites table
+ * (archived ones aside, see get_archived_client_refs).
+ * These are references for the sake of lists, where the client_id can also
+ * provide a handle for a link to an edit page (or whatever)
+ */
+pub async fn get_client_refs(pool: &MySqlPool) -> Result<Vec<ClientRef>> {
+    let client_refs: Vec<ClientRef> = sqlx::query_as!(
+        ClientRef,
+        "SELECT client_id, name, logo_url FROM client_sites WHERE archived_timestamp IS NULL"
+    ).fetch_all(pool).await?;
+
+    Ok(client_refs)
+}
+
+
+/**
+ * Get a collection of link data (name, domain, description, logo) for sites
+ * that are ACTIVE (and approved) and are NOT the auth site.
+ */
+pub async fn get_client_links(pool: &MySqlPool) -> Result<Vec<ClientLinkData>> {
+    let client_refs: Vec<ClientLinkData> = sqlx::query_as!(
+        ClientLinkData,
+        "SELECT name, logo_url, domain, description FROM client_sites 
+        WHERE is_active = 1 AND status = 'approved' AND is_internal != 1"
+    ).fetch_all(pool).await?;
+
+    Ok(client_refs)
+}
+
+
+/**
+ * Client sites a user can log in to (the list on the login page).
+ * Inactive (and unapproved) sites are left out.
+ */
+pub async fn get_active_client_refs(pool: &MySqlPool) -> Result<Vec<ClientRef>> {
+    Ok(sqlx::query_as!(
+        ClientRef,
+        "SELECT client_id, name, logo_url FROM client_sites
+            WHERE is_active = 1 AND status = 'approved'"
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * Is this client site switched on? An unknown client_id is not,
+ * and neither is one still waiting for (or refused at) review.
+ */
+pub async fn is_client_active(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<bool> {
+    let active: Option<Count> = sqlx::query_as!(
+        Count,
+        "SELECT COUNT(*) as count FROM client_sites
+            WHERE client_id = ? AND is_active = 1 AND status = 'approved'",
+        client_id
+    ).fetch_optional(pool).await?;
+
+    Ok(active.map(|c| c.count).unwrap_or(0) > 0)
+}
+
+
+
+pub async fn get_client_by_client_id(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<Option<ClientData>> {
+    Ok(sqlx::query_as!(
+        ClientData,
+        "SELECT id, client_id, hashed_client_secret,
+            name, domain, redirect_uri,
+            description, category AS `category: ClientCategory`, logo_url, is_active,
+            client_type AS `client_type: ClientType`, is_internal,
+            created_timestamp, archived_timestamp,
+            owner_user_id, status
+            FROM client_sites WHERE client_id = ?",
+        client_id
+    ).fetch_optional(pool).await?)
+}
+
+
+pub async fn get_pending_registration(
+    pool: &MySqlPool,
+    token: &String
+) -> Result<Option<PendingRegistration>> {
+    Ok(sqlx::query_as!(
+        PendingRegistration,
+        "SELECT id, username, email, password_hash, token, expires_timestamp
+            FROM pending_registrations WHERE token = ?",
+        token
+    ).fetch_optional(pool).await?)
+}
+
+
+pub async fn get_client_secret(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<Option<ClientSecret>> {
+    Ok(sqlx::query_as!(
+        ClientSecret,
+        "SELECT hashed_client_secret, client_type AS `client_type: ClientType`
+            FROM client_sites WHERE client_id = ?",
+        client_id
+    ).fetch_optional(pool).await?)
+}
+
+
+/* 
+ * 
+ * 
+ * 
+ * 
+ * 
+ * 
+ * ==============================
  * ==============================
  * =====                    =====
  * =====  INSERT FUNCTIONS  =====
@@ -476,7 +1202,8 @@ pub async fn get_client_secret(
 
  /**
   * Add a refresh token to the database.
-  * for a particular user and particular client site.
+  * Each one is a new session for a particular user, client site and device.
+  * Other sessions (other devices) are left alone.
   * Take ownership of token, because it should ONLY be given back
   * if it's saved successfully to the DB.
   */
@@ -484,28 +1211,37 @@ pub async fn get_client_secret(
     pool: &MySqlPool,
     user_id: i32,
     client_id: String,
-    refresh_token: String
+    refresh_token: String,
+    device: &SessionDevice
 ) -> Result<String, anyhow::Error> {
     let expires_timestamp: OffsetDateTime =
         OffsetDateTime::now_utc() + Duration::days(14); // TODO: put this in resources?
     let created_timestamp: OffsetDateTime = OffsetDateTime::now_utc();
 
+    // Sessions pile up now, so clear out this user's dead ones first
+    if let Err(e) = delete_expired_refresh_tokens(pool, user_id).await {
+        eprintln!("Failed to delete expired refresh_tokens: {:?}", e);
+    }
+
     let _result: sqlx::mysql::MySqlQueryResult = sqlx::query(
         "INSERT INTO refresh_tokens (
             user_id,
             client_id,
             token,
+            device_label,
+            user_agent,
+            ip_address,
             created_timestamp,
+            last_used_timestamp,
             expires_timestamp)
-        VALUES (?, ?, ?, ?, ?)
-        ON DUPLICATE KEY UPDATE
-            token = VALUES(token),
-            created_timestamp = VALUES(created_timestamp),
-            expires_timestamp = VALUES(expires_timestamp);
-            ")
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
     .bind(user_id)
     .bind(client_id)
     .bind(&refresh_token)
+    .bind(&device.device_label)
+    .bind(&device.user_agent)
+    .bind(&device.ip_address)
+    .bind(created_timestamp)
     .bind(created_timestamp)
     .bind(expires_timestamp)
     .execute(pool).await.map_err(|e| {
@@ -529,7 +1265,9 @@ pub async fn get_client_secret(
     pool: &MySqlPool,
     user_id: i32,
     client_id: &String,
-    auth_code: String
+    auth_code: String,
+    code_challenge: Option<&String>,
+    device: &SessionDevice
 ) -> Result<String, anyhow::Error> {
     let expires_timestamp: OffsetDateTime =
         OffsetDateTime::now_utc() + Duration::minutes(1);
@@ -540,12 +1278,18 @@ pub async fn get_client_secret(
             user_id,
             client_id,
             code,
+            user_agent,
+            ip_address,
+            code_challenge,
             created_timestamp,
             expires_timestamp)
-        VALUES (?, ?, ?, ?, ?)")
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
     .bind(user_id)
     .bind(client_id)
     .bind(&auth_code)
+    .bind(&device.user_agent)
+    .bind(&device.ip_address)
+    .bind(code_challenge)
     .bind(created_timestamp)
     .bind(expires_timestamp)
     .execute(pool).await.map_err(|e| {
@@ -558,23 +1302,28 @@ pub async fn get_client_secret(
  }
 
 
-// Add new user to database
-pub async fn add_user(
+/**
+ * Add new user to database.
+ * Password must ALREADY be hashed (on the hash pool).
+ */
+pub async fn add_user_with_hash(
     pool: &MySqlPool,
     username: &String,
     email: &String,
-    password: String
+    password_hash: &String,
+    email_verified: bool
 ) -> Result<i32, anyhow::Error> {
-    let password_hash: String = auth::hash_password(password);
     let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
         "INSERT INTO users (
             username,
             email,
-            password_hash)
-        VALUES (?, ?, ?)")
+            password_hash,
+            email_verified)
+        VALUES (?, ?, ?, ?)")
     .bind(username)
     .bind(email)
-    .bind(&password_hash)
+    .bind(password_hash)
+    .bind(email_verified)
     .execute(pool).await.map_err(|e| {
         eprintln!("Failed to save user to database: {:?}", e);
         anyhow!("Could not save user to database: {e}")
@@ -584,6 +1333,148 @@ pub async fn add_user(
 }
 
 
+/**
+ * Save a token for a link we're about to email.
+ */
+pub async fn add_account_token(
+    pool: &MySqlPool,
+    user_id: i32,
+    token: &String,
+    purpose: &str,
+    lifetime: Duration
+) -> Result<u64, anyhow::Error> {
+    let created_timestamp: OffsetDateTime = OffsetDateTime::now_utc();
+
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "INSERT INTO account_tokens (
+            user_id,
+            token,
+            purpose,
+            created_timestamp,
+            expires_timestamp)
+        VALUES (?, ?, ?, ?, ?)")
+    .bind(user_id)
+    .bind(token)
+    .bind(purpose)
+    .bind(created_timestamp)
+    .bind(created_timestamp + lifetime)
+    .execute(pool).await.map_err(|e| {
+        eprintln!("Failed to save account token to database: {:?}", e);
+        anyhow!("Could not save account token to database: {e}")
+    })?;
+
+    Ok(result.rows_affected())
+}
+
+
+/**
+ * Write one row to the audit log.
+ */
+pub async fn add_audit_event(
+    pool: &MySqlPool,
+    event: &NewAuditEvent
+) -> Result<u64, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "INSERT INTO audit_events (
+            event_type,
+            user_id,
+            actor_id,
+            client_id,
+            ip_address,
+            user_agent,
+            details,
+            created_timestamp)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+    .bind(&event.event_type)
+    .bind(event.user_id)
+    .bind(event.actor_id)
+    .bind(&event.client_id)
+    .bind(&event.ip_address)
+    .bind(&event.user_agent)
+    .bind(&event.details)
+    .bind(OffsetDateTime::now_utc())
+    .execute(pool).await.map_err(|e| {
+        anyhow!("Could not save audit event to database: {e}")
+    })?;
+
+    Ok(result.rows_affected())
+}
+
+
+/**
+ * Add a user migrated from one of the old game servers.
+ * The hash is whatever that server stored (bcrypt or PBKDF2-SHA256)
+ * and gets upgraded to Argon2 on their first login.
+ */
+pub async fn add_imported_user(
+    pool: &MySqlPool,
+    username: &String,
+    email: &String,
+    password_hash: &String,
+    first_name: &Option<String>,
+    last_name: &Option<String>,
+    email_verified: bool
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "INSERT INTO users (
+            username,
+            email,
+            password_hash,
+            first_name,
+            last_name,
+            email_verified)
+        VALUES (?, ?, ?, ?, ?, ?)")
+    .bind(username)
+    .bind(email)
+    .bind(password_hash)
+    .bind(first_name)
+    .bind(last_name)
+    .bind(email_verified)
+    .execute(pool).await.map_err(|e| {
+        eprintln!("Failed to save imported user to database: {:?}", e);
+        anyhow!("Could not save imported user to database: {e}")
+    })?;
+
+    Ok(result.last_insert_id() as i32)
+}
+
+
+/**
+ * Save a registration that must be confirmed by email before
+ * the user is created. Password must already be hashed.
+ */
+pub async fn add_pending_registration(
+    pool: &MySqlPool,
+    username: &String,
+    email: &String,
+    password_hash: &String,
+    token: &String
+) -> Result<u64, anyhow::Error> {
+    let expires_timestamp: OffsetDateTime = OffsetDateTime::now_utc() +
+        Duration::hours(config::pending_registration_hours());
+
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "INSERT INTO pending_registrations (
+            username,
+            email,
+            password_hash,
+            token,
+            expires_timestamp)
+        VALUES (?, ?, ?, ?, ?)")
+    .bind(username)
+    .bind(email)
+    .bind(password_hash)
+    .bind(token)
+    .bind(expires_timestamp)
+    .execute(pool).await.map_err(|e| {
+        eprintln!("Failed to save pending registration to database: {:?}", e);
+        anyhow!("Could not save pending registration to database: {e}")
+    })?;
+
+    Ok(result.rows_affected())
+}
+
+
 /**
  * When the server starts up we make sure there is an admin.
  * Their default pre-hashed password is saved in an env variable.
@@ -594,7 +1485,7 @@ pub async fn create_primary_admin(pool: &MySqlPool) -> Result<bool, anyhow::Erro
     let count_option: Option<Count> = match sqlx::query_as!(
         Count,
         "SELECT COUNT(*) as count FROM users WHERE role = ?",
-        "admin"
+        UserRole::Admin.as_str()
     ).fetch_optional(pool).await {
         Ok(count) => count,
         Err(e) => {
@@ -615,7 +1506,7 @@ pub async fn create_primary_admin(pool: &MySqlPool) -> Result<bool, anyhow::Erro
 
     let username: &str = "pattmayne";
     let email: &str = "pattmayne@gmail.com";
-    let role: &str = "admin";
+    let role: &str = UserRole::Admin.as_str();
     let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
             "INSERT INTO users (
                 username,
@@ -669,8 +1560,8 @@ pub async fn create_self_client(pool: &MySqlPool) -> Result<bool, anyhow::Error>
     let client_secret: &str = "CLIENT_SECRET_PLACEHOLDER";
     let name: &str = "Auth Site";
     let redirect_uri: &str = "127.0.0.1:8080/dashboard";
-    let client_type: &str = "confidential";
-    let category: &str = "service";
+    let client_type: ClientType = ClientType::Confidential;
+    let category: ClientCategory = ClientCategory::Service;
     let is_internal: bool = true;
 
 
@@ -690,8 +1581,8 @@ pub async fn create_self_client(pool: &MySqlPool) -> Result<bool, anyhow::Error>
         .bind(name)
         .bind(domain)
         .bind(redirect_uri)
-        .bind(client_type)
-        .bind(category)
+        .bind(client_type.as_str())
+        .bind(category.as_str())
         .bind(is_internal)
         .execute(pool).await.map_err(|e| {
             eprintln!("Failed to save FIRST AUTH client to database: {:?}", e);
@@ -727,19 +1618,27 @@ pub async fn add_external_client(
             description,
             category,
             is_internal,
-            is_active
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            is_active,
+            owner_user_id,
+            status,
+            registration_token_hash,
+            initial_access_token_id
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
         .bind(new_client_data.client_id)
         .bind(new_client_data.hashed_client_secret)
         .bind(new_client_data.site_name)
         .bind(new_client_data.site_domain)
         .bind(new_client_data.redirect_uri)
         .bind(new_client_data.logo_url)
-        .bind(new_client_data.client_type)
+        .bind(new_client_data.client_type.as_str())
         .bind(new_client_data.description)
-        .bind(new_client_data.category)
+        .bind(new_client_data.category.as_str())
         .bind(0)
         .bind(new_client_data.is_active)
+        .bind(new_client_data.owner_user_id)
+        .bind(new_client_data.status)
+        .bind(new_client_data.registration_token_hash)
+        .bind(new_client_data.initial_access_token_id)
         .execute(pool).await.map_err(|e| {
             eprintln!("Failed to save EXTERNAL CLIENT to database: {:?}", e);
             anyhow!("Could not save EXTERNAL CLIENT to database: {e}")
@@ -773,6 +1672,27 @@ pub async fn add_external_client(
  */
 
 
+/**
+ * A session's refresh token was just used. Remember when, and from where.
+ */
+pub async fn touch_refresh_token(
+    pool: &MySqlPool,
+    id: i32,
+    ip_address: &String
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "UPDATE refresh_tokens SET last_used_timestamp = ?, ip_address = ? WHERE id = ?")
+            .bind(OffsetDateTime::now_utc())
+            .bind(ip_address)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+// An archived client is never touched: it has to be restored first
 pub async fn update_external_client(
     pool: &MySqlPool,
     update_client_data: UpdateClientData
@@ -782,15 +1702,16 @@ pub async fn update_external_client(
     let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
     "UPDATE client_sites SET name = ?, domain = ?, redirect_uri = ?,
             description = ?, logo_url = ?, is_active = ?,
-            client_type = ?, category = ? WHERE client_id = ?")
+            client_type = ?, category = ?
+            WHERE client_id = ? AND archived_timestamp IS NULL")
         .bind(update_client_data.site_name)
         .bind(update_client_data.site_domain)
         .bind(update_client_data.redirect_uri)
         .bind(update_client_data.description)
         .bind(update_client_data.logo_url)
         .bind(update_client_data.is_active)
-        .bind(update_client_data.client_type)
-        .bind(update_client_data.category)
+        .bind(update_client_data.client_type.as_str())
+        .bind(update_client_data.category.as_str())
         .bind(update_client_data.client_id)
         .execute(pool)
         .await?;
@@ -816,6 +1737,41 @@ pub async fn update_real_names(
 }
 
 
+// An admin setting a user's base role. The role must exist in the roles table.
+pub async fn update_user_role(
+    pool: &MySqlPool,
+    user_id: i32,
+    role: &String
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "UPDATE users SET role = ? WHERE id = ?
+            AND EXISTS (SELECT 1 FROM roles WHERE name = ?)")
+            .bind(role)
+            .bind(user_id)
+            .bind(role)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+pub async fn set_email_verified(
+    pool: &MySqlPool,
+    user_id: i32,
+    email_verified: bool
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "UPDATE users SET email_verified = ? WHERE id = ?")
+            .bind(email_verified)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
 pub async fn update_client_secret(
     pool: &MySqlPool,
     client_id: &String,
@@ -833,21 +1789,18 @@ pub async fn update_client_secret(
 
 
 /**
- * User is updating password.
- * Route has already confirmed that it's an acceptable password.
- * Hash it and save it to the database.
+ * User is updating password (or we're rehashing it with new Argon2 settings).
+ * Route has already confirmed that it's an acceptable password,
+ * and hashed it on the hash pool.
  */
-pub async fn update_password(
+pub async fn update_password_hash(
     pool: &MySqlPool,
-    password: &String,
+    password_hash: &String,
     id: i32
 )-> Result<i32, anyhow::Error> {
-
-    // save password to DB and return positive result
-    let hashed_password: String = auth::hash_password(password.to_owned());
     let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
         "UPDATE users SET password_hash = ? WHERE id = ?")
-            .bind(hashed_password)
+            .bind(password_hash)
             .bind(id)
             .execute(pool)
             .await?;
@@ -880,15 +1833,18 @@ pub async fn update_password(
 
 
 /**
- * When a user logs out of a site, delete all their refresh tokens
+ * When a user logs out, delete the refresh token for that one session.
+ * Their other devices stay logged in.
  */
 pub async fn delete_refresh_token(
     pool: &MySqlPool,
-    user_id: i32
+    user_id: i32,
+    token: &String
 ) -> Result<i32, anyhow::Error> {
     let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
-        "DELETE FROM refresh_tokens WHERE user_id = ?")
+        "DELETE FROM refresh_tokens WHERE user_id = ? AND token = ?")
             .bind(user_id)
+            .bind(token)
             .execute(pool)
             .await?;
 
@@ -896,8 +1852,189 @@ pub async fn delete_refresh_token(
 }
 
 
-
-/* 
+/**
+ * The user signed out one session (by its row id) from the dashboard.
+ * The user_id makes sure it's really theirs.
+ */
+pub async fn delete_session(
+    pool: &MySqlPool,
+    user_id: i32,
+    session_id: i32
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE user_id = ? AND id = ?")
+            .bind(user_id)
+            .bind(session_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Sign the user out everywhere: every device, every client site.
+ */
+pub async fn delete_all_refresh_tokens(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Expired sessions are useless. Remove them.
+ */
+pub async fn delete_expired_refresh_tokens(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE user_id = ? AND expires_timestamp < ?")
+            .bind(user_id)
+            .bind(OffsetDateTime::now_utc())
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+
+/**
+ * After a password change, sign out every session except the current one.
+ * That means all client site tokens too.
+ */
+pub async fn delete_other_refresh_tokens(
+    pool: &MySqlPool,
+    user_id: i32,
+    keep_token: &String
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE user_id = ? AND token != ?")
+            .bind(user_id)
+            .bind(keep_token)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Use up an auth code as it's redeemed.
+ * Only one caller can delete it, so false means it was already used
+ * (or a concurrent redemption got there first).
+ */
+pub async fn use_auth_code(
+    pool: &MySqlPool,
+    code: &String
+) -> Result<bool, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM auth_codes WHERE code = ?")
+            .bind(code)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+
+/**
+ * Throw away any auth codes the user hasn't redeemed yet.
+ */
+pub async fn delete_auth_codes_for_user(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM auth_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Throw away every emailed-link token of one kind for a user
+ * (ie: all their password reset links, once one has been used).
+ * Expired tokens for anyone are cleared out too.
+ */
+pub async fn delete_account_tokens(
+    pool: &MySqlPool,
+    user_id: i32,
+    purpose: &str
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM account_tokens
+            WHERE (user_id = ? AND purpose = ?) OR expires_timestamp < ?")
+            .bind(user_id)
+            .bind(purpose)
+            .bind(OffsetDateTime::now_utc())
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Pending registration has been confirmed (or has expired). Remove it.
+ * Also clears out any other stale pending registrations while we're here.
+ */
+pub async fn delete_pending_registration(
+    pool: &MySqlPool,
+    token: &String
+) -> Result<i32, anyhow::Error> {
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM pending_registrations
+            WHERE token = ? OR expires_timestamp < ?")
+            .bind(token)
+            .bind(OffsetDateTime::now_utc())
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+/**
+ * Mark a solved proof-of-work challenge as used (by its signature).
+ * False if it already was. Expired ones are cleared out on the way:
+ * they can't be replayed anyway.
+ */
+pub async fn use_challenge(
+    pool: &MySqlPool,
+    challenge_id: &String,
+    expires: OffsetDateTime
+) -> Result<bool> {
+    sqlx::query("DELETE FROM used_challenges WHERE expires_timestamp < ?")
+        .bind(OffsetDateTime::now_utc())
+        .execute(pool)
+        .await?;
+
+    let result: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "INSERT IGNORE INTO used_challenges (challenge_id, expires_timestamp) VALUES (?, ?)")
+        .bind(challenge_id)
+        .bind(expires)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+
+/* 
  * 
  * 
  * 
@@ -960,4 +2097,807 @@ pub async fn email_taken(pool: &MySqlPool, email: &String) -> bool {
 
     let count: i64 = count_option.unwrap_or(Count{count: 0}).count;
     count > 0
-}
\ No newline at end of file
+}
+
+
+
+/*
+ *
+ *
+ * =========================
+ * =========================
+ * =====               =====
+ * =====  SUSPENSIONS  =====
+ * =====               =====
+ * =========================
+ * =========================
+ *
+ *
+ * A suspension is in force when it has started, hasn't ended,
+ * and nobody has lifted it.
+ *
+*/
+
+
+pub async fn get_active_suspension(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<Option<Suspension>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    // If there are several, the one that lasts longest
+    Ok(sqlx::query_as!(
+        Suspension,
+        "SELECT id, user_id, reason, issued_by, start_timestamp, end_timestamp
+            FROM user_suspensions
+            WHERE user_id = ? AND lifted_timestamp IS NULL
+            AND start_timestamp <= ?
+            AND (end_timestamp IS NULL OR end_timestamp > ?)
+            ORDER BY end_timestamp IS NULL DESC, end_timestamp DESC
+            LIMIT 1",
+        user_id, now, now
+    ).fetch_optional(pool).await?)
+}
+
+
+pub async fn get_active_suspensions(pool: &MySqlPool) -> Result<Vec<SuspensionRow>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    Ok(sqlx::query_as!(
+        SuspensionRow,
+        "SELECT user_suspensions.id, user_suspensions.user_id, users.username,
+            user_suspensions.reason, admins.username AS `issued_by_username?`,
+            user_suspensions.start_timestamp, user_suspensions.end_timestamp
+            FROM user_suspensions
+            JOIN users ON users.id = user_suspensions.user_id
+            LEFT JOIN users AS admins ON admins.id = user_suspensions.issued_by
+            WHERE user_suspensions.lifted_timestamp IS NULL
+            AND (user_suspensions.end_timestamp IS NULL OR user_suspensions.end_timestamp > ?)
+            ORDER BY user_suspensions.start_timestamp DESC",
+        now
+    ).fetch_all(pool).await?)
+}
+
+
+pub async fn add_suspension(
+    pool: &MySqlPool,
+    user_id: i32,
+    reason: &String,
+    issued_by: i32,
+    end_timestamp: Option<OffsetDateTime>
+) -> Result<i32> {
+    let result = sqlx::query(
+        "INSERT INTO user_suspensions (user_id, reason, issued_by, start_timestamp, end_timestamp)
+            VALUES (?, ?, ?, ?, ?)")
+        .bind(user_id)
+        .bind(reason)
+        .bind(issued_by)
+        .bind(OffsetDateTime::now_utc())
+        .bind(end_timestamp)
+        .execute(pool).await?;
+
+    Ok(result.last_insert_id() as i32)
+}
+
+
+// Lift every suspension in force for this user
+pub async fn lift_suspensions(
+    pool: &MySqlPool,
+    user_id: i32,
+    lifted_by: i32
+) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE user_suspensions SET lifted_by = ?, lifted_timestamp = ?
+            WHERE user_id = ? AND lifted_timestamp IS NULL")
+        .bind(lifted_by)
+        .bind(OffsetDateTime::now_utc())
+        .bind(user_id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected())
+}
+
+
+
+/*
+ *
+ *
+ * =================================
+ * =================================
+ * =====                       =====
+ * =====  CLIENT RESTRICTIONS  =====
+ * =====                       =====
+ * =================================
+ * =================================
+ *
+ *
+ * Per-client bans: no auth codes or refresh tokens for
+ * this user on this client site until the restriction expires.
+ *
+*/
+
+
+pub async fn get_active_client_restriction(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32
+) -> Result<Option<ClientRestriction>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    // If there are several, the one that lasts longest
+    Ok(sqlx::query_as!(
+        ClientRestriction,
+        "SELECT id, client_id, user_id, reason, issued_by,
+            created_timestamp, expires_timestamp
+            FROM client_user_restrictions
+            WHERE client_id = ? AND user_id = ?
+            AND (expires_timestamp IS NULL OR expires_timestamp > ?)
+            ORDER BY expires_timestamp IS NULL DESC, expires_timestamp DESC
+            LIMIT 1",
+        client_id, user_id, now
+    ).fetch_optional(pool).await?)
+}
+
+
+pub async fn get_client_restrictions(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<Vec<ClientRestrictionRow>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    Ok(sqlx::query_as!(
+        ClientRestrictionRow,
+        "SELECT client_user_restrictions.user_id, users.username,
+            client_user_restrictions.reason, admins.username AS `issued_by_username?`,
+            client_user_restrictions.created_timestamp,
+            client_user_restrictions.expires_timestamp
+            FROM client_user_restrictions
+            JOIN users ON users.id = client_user_restrictions.user_id
+            LEFT JOIN users AS admins ON admins.id = client_user_restrictions.issued_by
+            WHERE client_user_restrictions.client_id = ?
+            AND (client_user_restrictions.expires_timestamp IS NULL
+                OR client_user_restrictions.expires_timestamp > ?)
+            ORDER BY client_user_restrictions.created_timestamp DESC",
+        client_id, now
+    ).fetch_all(pool).await?)
+}
+
+
+pub async fn add_client_restriction(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32,
+    reason: &String,
+    issued_by: Option<i32>,
+    expires_timestamp: Option<OffsetDateTime>
+) -> Result<i32> {
+    let result = sqlx::query(
+        "INSERT INTO client_user_restrictions
+            (client_id, user_id, reason, issued_by, created_timestamp, expires_timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)")
+        .bind(client_id)
+        .bind(user_id)
+        .bind(reason)
+        .bind(issued_by)
+        .bind(OffsetDateTime::now_utc())
+        .bind(expires_timestamp)
+        .execute(pool).await?;
+
+    Ok(result.last_insert_id() as i32)
+}
+
+
+// Remove every restriction on this user for this client site (and expired ones for anyone)
+pub async fn delete_client_restrictions(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32
+) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM client_user_restrictions
+            WHERE (client_id = ? AND user_id = ?) OR expires_timestamp < ?")
+        .bind(client_id)
+        .bind(user_id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(pool).await?;
+
+    Ok(result.rows_affected())
+}
+
+
+/**
+ * Sign the user out of one client site: its refresh tokens
+ * and any auth codes it hasn't redeemed yet.
+ */
+pub async fn delete_client_sessions(
+    pool: &MySqlPool,
+    user_id: i32,
+    client_id: &String
+) -> Result<i32, anyhow::Error> {
+    let tokens: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE user_id = ? AND client_id = ?")
+            .bind(user_id)
+            .bind(client_id)
+            .execute(pool)
+            .await?;
+
+    sqlx::query("DELETE FROM auth_codes WHERE user_id = ? AND client_id = ?")
+        .bind(user_id)
+        .bind(client_id)
+        .execute(pool)
+        .await?;
+
+    Ok(tokens.rows_affected() as i32)
+}
+
+
+/**
+ * A client site was deactivated: every session and auth code for it goes,
+ * for every user. Returns how many sessions (refresh tokens) were deleted.
+ */
+pub async fn delete_all_client_sessions(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<i32, anyhow::Error> {
+    let tokens: sqlx::mysql::MySqlQueryResult = sqlx::query(
+        "DELETE FROM refresh_tokens WHERE client_id = ?")
+            .bind(client_id)
+            .execute(pool)
+            .await?;
+
+    sqlx::query("DELETE FROM auth_codes WHERE client_id = ?")
+        .bind(client_id)
+        .execute(pool)
+        .await?;
+
+    Ok(tokens.rows_affected() as i32)
+}
+
+
+
+/*
+ *
+ *
+ * =================================
+ * =================================
+ * =====                       =====
+ * =====  ROLES & PERMISSIONS  =====
+ * =====                       =====
+ * =================================
+ * =================================
+ *
+ *
+ * See rbac.rs
+ *
+*/
+
+
+/**
+ * Every permission this user has: from their base role (users.role)
+ * and from any extra roles granted in user_roles.
+ */
+pub async fn get_user_permissions(
+    pool: &MySqlPool,
+    user_id: i32
+) -> Result<Vec<String>> {
+    let rows: Vec<NameRow> = sqlx::query_as!(
+        NameRow,
+        "SELECT DISTINCT permissions.name FROM permissions
+            JOIN role_permissions ON role_permissions.permission_id = permissions.id
+            JOIN roles ON roles.id = role_permissions.role_id
+            WHERE roles.name = (SELECT role FROM users WHERE id = ?)
+            OR roles.id IN (SELECT role_id FROM user_roles WHERE user_id = ?)",
+        user_id, user_id
+    ).fetch_all(pool).await?;
+
+    Ok(rows.into_iter().map(|row| row.name).collect())
+}
+
+
+/**
+ * Grant an extra role (by name). Granting one they already have is fine.
+ * False if nothing changed: no such role, or they already had it.
+ */
+pub async fn add_user_role(
+    pool: &MySqlPool,
+    user_id: i32,
+    role_name: &str
+) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT IGNORE INTO user_roles (user_id, role_id, created_timestamp)
+            SELECT ?, id, ? FROM roles WHERE name = ?")
+        .bind(user_id)
+        .bind(OffsetDateTime::now_utc())
+        .bind(role_name)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+// Take an extra role away. False if they didn't have it.
+pub async fn remove_user_role(
+    pool: &MySqlPool,
+    user_id: i32,
+    role_name: &str
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE user_roles FROM user_roles
+            JOIN roles ON roles.id = user_roles.role_id
+            WHERE user_roles.user_id = ? AND roles.name = ?")
+        .bind(user_id)
+        .bind(role_name)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+
+/*
+ *
+ *
+ * ===============================
+ * ===============================
+ * =====                     =====
+ * =====  CLIENT USER ROLES  =====
+ * =====                     =====
+ * ===============================
+ * ===============================
+ *
+ *
+ * Roles a client site gives its own users (ie: moderator in one game).
+ * The names belong to the client site. We just keep them.
+ *
+*/
+
+
+struct RoleName {
+    role_name: String,
+}
+
+
+// The user's role names on this client site, sorted
+pub async fn get_client_user_roles(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32
+) -> Result<Vec<String>> {
+    let rows: Vec<RoleName> = sqlx::query_as!(
+        RoleName,
+        "SELECT role_name FROM client_user_roles
+            WHERE client_id = ? AND user_id = ?
+            ORDER BY role_name",
+        client_id, user_id
+    ).fetch_all(pool).await?;
+
+    Ok(rows.into_iter().map(|row| row.role_name).collect())
+}
+
+
+// Everyone with a role on this client site
+pub async fn get_client_role_assignments(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<Vec<ClientUserRoleRow>> {
+    Ok(sqlx::query_as!(
+        ClientUserRoleRow,
+        "SELECT client_user_roles.user_id, users.username,
+            client_user_roles.role_name, admins.username AS `granted_by_username?`,
+            client_user_roles.created_timestamp
+            FROM client_user_roles
+            JOIN users ON users.id = client_user_roles.user_id
+            LEFT JOIN users AS admins ON admins.id = client_user_roles.granted_by
+            WHERE client_user_roles.client_id = ?
+            ORDER BY users.username, client_user_roles.role_name",
+        client_id
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * Give a user a role on a client site.
+ * False if they already had it.
+ */
+pub async fn add_client_user_role(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32,
+    role_name: &String,
+    granted_by: Option<i32>
+) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT IGNORE INTO client_user_roles
+            (client_id, user_id, role_name, granted_by, created_timestamp)
+            VALUES (?, ?, ?, ?, ?)")
+        .bind(client_id)
+        .bind(user_id)
+        .bind(role_name)
+        .bind(granted_by)
+        .bind(OffsetDateTime::now_utc())
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+// False if they didn't have it
+pub async fn remove_client_user_role(
+    pool: &MySqlPool,
+    client_id: &String,
+    user_id: i32,
+    role_name: &String
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM client_user_roles
+            WHERE client_id = ? AND user_id = ? AND role_name = ?")
+        .bind(client_id)
+        .bind(user_id)
+        .bind(role_name)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+
+/*
+ *
+ *
+ * ============================
+ * ============================
+ * =====                  =====
+ * =====  CLIENT ARCHIVE  =====
+ * =====                  =====
+ * ============================
+ * ============================
+ *
+ *
+ * Taking a client site away. Archived: switched off and out of the way,
+ * but restorable. Deleted: gone, along with everything that points at it
+ * (foreign keys, see migration 0013). The audit log keeps both histories.
+ * The auth site itself (is_internal) is never archived or deleted.
+ *
+*/
+
+
+pub async fn get_archived_client_refs(pool: &MySqlPool) -> Result<Vec<ClientRef>> {
+    Ok(sqlx::query_as!(
+        ClientRef,
+        "SELECT client_id, name, logo_url FROM client_sites
+            WHERE archived_timestamp IS NOT NULL
+            ORDER BY archived_timestamp DESC"
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * Archive a client: switch it off and throw away its sessions and auth codes.
+ * Its roles and restrictions stay, for when it's restored.
+ * False if there's no such (non-archived, non-internal) client.
+ */
+pub async fn archive_client(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE client_sites SET archived_timestamp = ?, is_active = 0
+            WHERE client_id = ? AND is_internal = 0 AND archived_timestamp IS NULL")
+        .bind(OffsetDateTime::now_utc())
+        .bind(client_id)
+        .execute(pool).await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    delete_all_client_sessions(pool, client_id).await?;
+    Ok(true)
+}
+
+
+/**
+ * Bring an archived client back. It stays inactive until an admin
+ * switches it on again (edit client page).
+ */
+pub async fn restore_client(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE client_sites SET archived_timestamp = NULL
+            WHERE client_id = ? AND archived_timestamp IS NOT NULL")
+        .bind(client_id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+// For good. The foreign keys take its sessions, auth codes, roles and restrictions.
+pub async fn delete_client(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM client_sites WHERE client_id = ? AND is_internal = 0")
+        .bind(client_id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+
+/*
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  CLIENT REVIEW  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * Client sites that developers register themselves (developer portal).
+ * They start 'pending' and switched off. An admin approves them
+ * (switched on) or rejects them in the review queue.
+ * A developer only ever touches clients with their owner_user_id.
+ *
+*/
+
+
+pub async fn get_developer_clients(
+    pool: &MySqlPool,
+    owner_user_id: i32,
+    usage_since: OffsetDateTime
+) -> Result<Vec<DeveloperClientRow>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    Ok(sqlx::query_as!(
+        DeveloperClientRow,
+        "SELECT client_sites.client_id, client_sites.name, client_sites.domain,
+            client_sites.redirect_uri, client_sites.logo_url, client_sites.description,
+            client_sites.category AS `category: ClientCategory`,
+            client_sites.client_type AS `client_type: ClientType`, client_sites.status,
+            client_sites.is_active, client_sites.created_timestamp,
+            (SELECT COUNT(*) FROM refresh_tokens
+                WHERE refresh_tokens.client_id = client_sites.client_id
+                AND refresh_tokens.expires_timestamp > ?) AS `open_sessions!`,
+            (SELECT COUNT(*) FROM audit_events
+                WHERE audit_events.client_id = client_sites.client_id
+                AND audit_events.event_type = 'token_redeemed'
+                AND audit_events.created_timestamp > ?) AS `recent_logins!`,
+            (SELECT COUNT(DISTINCT audit_events.user_id) FROM audit_events
+                WHERE audit_events.client_id = client_sites.client_id
+                AND audit_events.event_type = 'token_redeemed'
+                AND audit_events.created_timestamp > ?) AS `recent_users!`
+            FROM client_sites
+            WHERE client_sites.owner_user_id = ?
+            AND client_sites.archived_timestamp IS NULL
+            ORDER BY client_sites.created_timestamp DESC",
+        now, usage_since, usage_since, owner_user_id
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * A developer editing their own client.
+ * They can't switch it on or off (is_active is left alone).
+ * back_to_review: it goes back in the queue, switched off
+ * (the route decides: ie: a new redirect_uri needs a new look).
+ */
+pub async fn update_owned_client(
+    pool: &MySqlPool,
+    update_client_data: UpdateClientData,
+    owner_user_id: i32,
+    back_to_review: bool
+) -> Result<i32> {
+    let result = sqlx::query(
+        "UPDATE client_sites SET name = ?, domain = ?, redirect_uri = ?,
+            description = ?, logo_url = ?, client_type = ?, category = ?,
+            status = IF(?, 'pending', status), is_active = IF(?, 0, is_active)
+            WHERE client_id = ? AND owner_user_id = ? AND archived_timestamp IS NULL")
+        .bind(update_client_data.site_name)
+        .bind(update_client_data.site_domain)
+        .bind(update_client_data.redirect_uri)
+        .bind(update_client_data.description)
+        .bind(update_client_data.logo_url)
+        .bind(update_client_data.client_type.as_str())
+        .bind(update_client_data.category.as_str())
+        .bind(back_to_review)
+        .bind(back_to_review)
+        .bind(update_client_data.client_id)
+        .bind(owner_user_id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() as i32)
+}
+
+
+// The review queue, oldest first
+pub async fn get_pending_clients(pool: &MySqlPool) -> Result<Vec<PendingClientRow>> {
+    Ok(sqlx::query_as!(
+        PendingClientRow,
+        "SELECT client_sites.client_id, client_sites.name, client_sites.domain,
+            client_sites.redirect_uri, client_sites.description,
+            client_sites.category AS `category: ClientCategory`,
+            client_sites.client_type AS `client_type: ClientType`,
+            users.username AS `owner_username?`, client_sites.created_timestamp
+            FROM client_sites
+            LEFT JOIN users ON users.id = client_sites.owner_user_id
+            WHERE client_sites.status = 'pending'
+            AND client_sites.archived_timestamp IS NULL
+            ORDER BY client_sites.created_timestamp"
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * Approve (switch on) or reject a pending client.
+ * False if it isn't pending (already reviewed, or no such client).
+ */
+pub async fn review_client(
+    pool: &MySqlPool,
+    client_id: &String,
+    approve: bool,
+    reviewed_by: i32
+) -> Result<bool> {
+    let status: &str = if approve { CLIENT_APPROVED } else { CLIENT_REJECTED };
+
+    let result = sqlx::query(
+        "UPDATE client_sites SET status = ?, is_active = ?,
+            reviewed_by = ?, reviewed_timestamp = ?
+            WHERE client_id = ? AND status = 'pending'")
+        .bind(status)
+        .bind(approve)
+        .bind(reviewed_by)
+        .bind(OffsetDateTime::now_utc())
+        .bind(client_id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+
+/*
+ *
+ *
+ * =================================
+ * =================================
+ * =====                       =====
+ * =====  CLIENT REGISTRATION  =====
+ * =====                       =====
+ * =================================
+ * =================================
+ *
+ *
+ * Clients registering themselves (/oauth/register, RFC 7591)
+ * with an initial access token an admin issued,
+ * then managing themselves (RFC 7592) with their registration access token.
+ * Only the tokens' hashes are here (auth::hash_registration_token).
+ *
+*/
+
+
+pub async fn add_initial_access_token(
+    pool: &MySqlPool,
+    token_hash: &String,
+    label: &String,
+    issued_by: i32,
+    max_uses: i32,
+    lifetime: Duration
+) -> Result<i32> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    let result = sqlx::query(
+        "INSERT INTO initial_access_tokens
+            (token_hash, label, issued_by, max_uses, expires_timestamp, created_timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)")
+        .bind(token_hash)
+        .bind(label)
+        .bind(issued_by)
+        .bind(max_uses)
+        .bind(now + lifetime)
+        .bind(now)
+        .execute(pool).await?;
+
+    Ok(result.last_insert_id() as i32)
+}
+
+
+// The ones that can still register a client (not used up, expired or revoked)
+pub async fn get_usable_initial_access_tokens(
+    pool: &MySqlPool
+) -> Result<Vec<InitialAccessTokenRow>> {
+    let now: OffsetDateTime = OffsetDateTime::now_utc();
+
+    Ok(sqlx::query_as!(
+        InitialAccessTokenRow,
+        "SELECT initial_access_tokens.id, initial_access_tokens.label,
+            users.username AS `issued_by_username?`,
+            initial_access_tokens.max_uses, initial_access_tokens.use_count,
+            initial_access_tokens.expires_timestamp, initial_access_tokens.created_timestamp
+            FROM initial_access_tokens
+            LEFT JOIN users ON users.id = initial_access_tokens.issued_by
+            WHERE initial_access_tokens.revoked_timestamp IS NULL
+            AND initial_access_tokens.expires_timestamp > ?
+            AND initial_access_tokens.use_count < initial_access_tokens.max_uses
+            ORDER BY initial_access_tokens.created_timestamp DESC",
+        now
+    ).fetch_all(pool).await?)
+}
+
+
+/**
+ * Spend one use of an initial access token.
+ * Some(its id) if it was usable, None if it's unknown, used up, expired or revoked.
+ * One UPDATE, so two registrations at once can't both spend the last use.
+ */
+pub async fn use_initial_access_token(
+    pool: &MySqlPool,
+    token_hash: &String
+) -> Result<Option<i32>> {
+    let result = sqlx::query(
+        "UPDATE initial_access_tokens SET use_count = use_count + 1
+            WHERE token_hash = ? AND revoked_timestamp IS NULL
+            AND expires_timestamp > ? AND use_count < max_uses")
+        .bind(token_hash)
+        .bind(OffsetDateTime::now_utc())
+        .execute(pool).await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let token_id: Option<IdRow> = sqlx::query_as!(
+        IdRow,
+        "SELECT id FROM initial_access_tokens WHERE token_hash = ?",
+        token_hash
+    ).fetch_optional(pool).await?;
+
+    Ok(token_id.map(|row| row.id))
+}
+
+
+pub async fn revoke_initial_access_token(
+    pool: &MySqlPool,
+    id: i32
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE initial_access_tokens SET revoked_timestamp = ?
+            WHERE id = ? AND revoked_timestamp IS NULL")
+        .bind(OffsetDateTime::now_utc())
+        .bind(id)
+        .execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+/**
+ * The client this registration access token manages.
+ * None if the token isn't this client's, or it's archived.
+ */
+pub async fn get_client_by_registration_token(
+    pool: &MySqlPool,
+    client_id: &String,
+    token_hash: &String
+) -> Result<Option<ClientData>> {
+    Ok(sqlx::query_as!(
+        ClientData,
+        "SELECT id, client_id, hashed_client_secret,
+            name, domain, redirect_uri,
+            description, category AS `category: ClientCategory`, logo_url, is_active,
+            client_type AS `client_type: ClientType`, is_internal,
+            created_timestamp, archived_timestamp,
+            owner_user_id, status
+            FROM client_sites
+            WHERE client_id = ? AND registration_token_hash = ?
+            AND archived_timestamp IS NULL",
+        client_id, token_hash
+    ).fetch_optional(pool).await?)
+}
@@ -0,0 +1,374 @@
+/*
+ *
+ *
+ *
+ *
+ * =======================
+ * =======================
+ * =====             =====
+ * =====  HASH POOL  =====
+ * =====             =====
+ * =======================
+ * =======================
+ *
+ *
+ * Argon2 is deliberately slow and memory-hungry. Running it inside an
+ * async handler blocks that actix worker thread, and every other request
+ * on it waits. So all password (and client secret) hashing and verifying
+ * goes through here instead:
+ *
+ * -- a fixed number of dedicated OS threads do the Argon2 work
+ * -- (that number is the concurrency limit)
+ * -- jobs wait in a queue. If the queue is full we refuse the job right
+ * -- away (Saturated) so the route can shed load with a 503
+ * -- we keep simple metrics on how long jobs wait in the queue
+ *
+ *
+*/
+
+use std::sync::{ Arc, Mutex, OnceLock, mpsc };
+use std::sync::atomic::{ AtomicU64, AtomicUsize, Ordering };
+use std::panic::{ self, AssertUnwindSafe };
+use std::time::{ Duration, Instant };
+use std::fmt;
+use serde::Serialize;
+
+use crate::{
+    auth::{ self, PasswordError, PasswordVerification },
+    config
+};
+
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+static POOL: OnceLock<HashPool> = OnceLock::new();
+
+
+#[derive(Debug)]
+pub enum HashPoolError {
+    Saturated, // queue is full. Try again soon.
+    Unavailable, // pool isn't running, or the job died
+    Password(PasswordError),
+}
+
+
+/*
+ * Counters for the metrics. All atomic so the workers and the
+ * async handlers can touch them without locking.
+ */
+#[derive(Default)]
+struct Metrics {
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+
+/*
+ * The worker threads, their queue and its metrics.
+ * The app runs one (see start). Tests start their own small ones.
+ */
+struct HashPool {
+    sender: mpsc::Sender<Job>,
+    threads: usize,
+    queue_limit: usize,
+    metrics: Arc<Metrics>,
+}
+
+
+/**
+ * A snapshot of the metrics, for the admin stats endpoint.
+ */
+#[derive(Serialize)]
+pub struct HashPoolStats {
+    pub threads: usize,
+    pub queue_limit: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub rejected: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+
+impl fmt::Display for HashPoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashPoolError::Saturated => write!(f, "Hash pool queue is full"),
+            HashPoolError::Unavailable => write!(f, "Hash pool is unavailable"),
+            HashPoolError::Password(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HashPoolError {}
+
+
+impl Metrics {
+    fn record_wait(&self, wait: Duration) {
+        let micros: u64 = wait.as_micros() as u64;
+        self.total_wait_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, threads: usize, queue_limit: usize) -> HashPoolStats {
+        let completed: u64 = self.completed.load(Ordering::Relaxed);
+        let total_wait_micros: u64 = self.total_wait_micros.load(Ordering::Relaxed);
+        let avg_wait_ms: f64 = if completed > 0 {
+            total_wait_micros as f64 / completed as f64 / 1000.0
+        } else {
+            0.0
+        };
+
+        HashPoolStats {
+            threads,
+            queue_limit,
+            queued: self.queued.load(Ordering::SeqCst),
+            completed,
+            rejected: self.rejected.load(Ordering::Relaxed),
+            avg_wait_ms,
+            max_wait_ms: self.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+
+impl HashPool {
+    /**
+     * Start the worker threads. They stop once the pool is dropped.
+     */
+    fn new(threads: usize, queue_limit: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::new(Mutex::new(receiver));
+
+        for i in 0..threads {
+            let receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::clone(&receiver);
+
+            let spawn_result = std::thread::Builder::new()
+                .name(format!("argon2-{i}"))
+                .spawn(move || loop {
+                    // Only hold the lock long enough to take one job
+                    let job_result = match receiver.lock() {
+                        Ok(guard) => guard.recv(),
+                        Err(_poisoned) => break
+                    };
+
+                    match job_result {
+                        Ok(job) => job(),
+                        Err(_disconnected) => break
+                    }
+                });
+
+            if let Err(e) = spawn_result {
+                eprintln!("Could not start hash pool thread: {e}");
+            }
+        }
+
+        HashPool { sender, threads, queue_limit, metrics: Arc::new(Metrics::default()) }
+    }
+
+    /**
+     * Run one job on the pool and wait (asynchronously) for its result.
+     */
+    async fn run<T, F>(&self, job: F) -> Result<T, HashPoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static
+    {
+        let metrics: Arc<Metrics> = Arc::clone(&self.metrics);
+
+        // Shed load: refuse instead of letting the queue grow forever
+        if metrics.queued.fetch_add(1, Ordering::SeqCst) >= self.queue_limit {
+            metrics.queued.fetch_sub(1, Ordering::SeqCst);
+            metrics.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(HashPoolError::Saturated);
+        }
+
+        let (result_sender, result_receiver) = tokio::sync::oneshot::channel::<T>();
+        let queued_at: Instant = Instant::now();
+
+        let job_metrics: Arc<Metrics> = Arc::clone(&metrics);
+        let boxed_job: Job = Box::new(move || {
+            job_metrics.queued.fetch_sub(1, Ordering::SeqCst);
+            job_metrics.record_wait(queued_at.elapsed());
+
+            // A panicking job must not take the worker thread down with it.
+            // If it panics, the sender is dropped and the caller gets Unavailable.
+            let job_result = panic::catch_unwind(AssertUnwindSafe(job));
+            job_metrics.completed.fetch_add(1, Ordering::Relaxed);
+            if let Ok(result) = job_result {
+                let _ = result_sender.send(result);
+            }
+        });
+
+        if self.sender.send(boxed_job).is_err() {
+            metrics.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(HashPoolError::Unavailable);
+        }
+
+        result_receiver.await.map_err(|_| HashPoolError::Unavailable)
+    }
+
+    fn stats(&self) -> HashPoolStats {
+        self.metrics.snapshot(self.threads, self.queue_limit)
+    }
+}
+
+
+/*
+ * SETTINGS (from env variables)
+ */
+
+// How many Argon2 jobs can run at once
+pub fn thread_count() -> usize {
+    let default_threads: usize = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(2);
+    config::env_or("HASH_POOL_THREADS", default_threads).max(1)
+}
+
+// How many jobs may wait before we start refusing them
+pub fn queue_limit() -> usize {
+    config::env_or("HASH_QUEUE_LIMIT", 64)
+}
+
+
+/**
+ * Start the worker threads. Called once from main, before the server starts.
+ * @return number of threads started
+ */
+pub fn start() -> usize {
+    let threads: usize = thread_count();
+    let _ = POOL.set(HashPool::new(threads, queue_limit()));
+    threads
+}
+
+
+fn pool() -> Result<&'static HashPool, HashPoolError> {
+    POOL.get().ok_or(HashPoolError::Unavailable)
+}
+
+
+/*
+ * The async versions of the auth password functions.
+ * Routes should use these, never the auth ones directly.
+ */
+
+pub async fn hash_password(input_password: String) -> Result<String, HashPoolError> {
+    pool()?.run(move || auth::hash_password(input_password)).await?
+        .map_err(HashPoolError::Password)
+}
+
+pub async fn verify_password(
+    input_password: String,
+    stored_hash: String
+) -> Result<PasswordVerification, HashPoolError> {
+    pool()?.run(move || auth::verify_password(&input_password, &stored_hash)).await
+}
+
+pub async fn dummy_verify_password(input_password: String) -> Result<(), HashPoolError> {
+    pool()?.run(move || auth::dummy_verify_password(&input_password)).await?
+        .map_err(HashPoolError::Password)
+}
+
+
+pub fn stats() -> HashPoolStats {
+    match POOL.get() {
+        Some(pool) => pool.stats(),
+        None => Metrics::default().snapshot(0, queue_limit())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    /*
+     * Give the pool a job that holds a worker until `release` is sent.
+     * Returns once the job has started.
+     */
+    async fn block_worker(
+        pool: &Arc<HashPool>,
+        release: mpsc::Receiver<()>
+    ) -> tokio::task::JoinHandle<Result<(), HashPoolError>> {
+        let (started_sender, started_receiver) = oneshot::channel::<()>();
+        let pool: Arc<HashPool> = Arc::clone(pool);
+        let handle = tokio::spawn(async move {
+            pool.run(move || {
+                let _ = started_sender.send(());
+                let _ = release.recv();
+            }).await
+        });
+        started_receiver.await.unwrap();
+        handle
+    }
+
+    async fn wait_for_queued(pool: &HashPool, queued: usize) {
+        while pool.stats().queued != queued {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_jobs_and_returns_their_results() {
+        let pool: HashPool = HashPool::new(2, 4);
+        assert_eq!(pool.run(|| 2 + 2).await.unwrap(), 4);
+        assert_eq!(pool.run(|| "done").await.unwrap(), "done");
+        assert_eq!(pool.stats().completed, 2);
+    }
+
+    #[tokio::test]
+    async fn blocked_worker_makes_the_next_job_saturated() {
+        let pool: Arc<HashPool> = Arc::new(HashPool::new(1, 1));
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+        let blocked = block_worker(&pool, release_receiver).await;
+
+        // The only worker is busy, so this one waits in the queue...
+        let queued_pool: Arc<HashPool> = Arc::clone(&pool);
+        let queued = tokio::spawn(async move { queued_pool.run(|| 7).await });
+        wait_for_queued(&pool, 1).await;
+
+        // ...and the queue is full
+        assert!(matches!(pool.run(|| 8).await, Err(HashPoolError::Saturated)));
+
+        release_sender.send(()).unwrap();
+        assert!(blocked.await.unwrap().is_ok());
+        assert_eq!(queued.await.unwrap().unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn metrics_count_queued_completed_and_rejected_jobs() {
+        let pool: Arc<HashPool> = Arc::new(HashPool::new(1, 1));
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+        let blocked = block_worker(&pool, release_receiver).await;
+
+        let queued_pool: Arc<HashPool> = Arc::clone(&pool);
+        let queued = tokio::spawn(async move { queued_pool.run(|| ()).await });
+        wait_for_queued(&pool, 1).await;
+        assert!(matches!(pool.run(|| ()).await, Err(HashPoolError::Saturated)));
+
+        let stats: HashPoolStats = pool.stats();
+        assert_eq!(stats.threads, 1);
+        assert_eq!(stats.queue_limit, 1);
+        assert_eq!(stats.queued, 1);
+        assert_eq!(stats.completed, 0);
+        assert_eq!(stats.rejected, 1);
+
+        // The queued job waits at least this long
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        release_sender.send(()).unwrap();
+        blocked.await.unwrap().unwrap();
+        queued.await.unwrap().unwrap();
+
+        let stats: HashPoolStats = pool.stats();
+        assert_eq!(stats.queued, 0);
+        assert_eq!(stats.completed, 2);
+        assert_eq!(stats.rejected, 1);
+        assert!(stats.max_wait_ms >= 20.0);
+        assert!(stats.avg_wait_ms > 0.0);
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ *
+ *
+ *
+ *
+ * ==========================
+ * ==========================
+ * =====                =====
+ * =====  IMPORT USERS  =====
+ * =====                =====
+ * ==========================
+ * ==========================
+ *
+ *
+ * Load players from the old game servers, keeping their existing hashes.
+ * We never see (or need) their plaintext passwords: the legacy hash is
+ * verified at their first login and then replaced with an Argon2 hash.
+ *
+ * Run from the command line instead of starting the server:
+ *      pattmayne_auth import-users players.jsonl
+ *
+ * The file has one JSON object per line:
+ *      {"username": "bob", "email": "bob@example.com",
+ *       "password_hash": "$2b$12$...", "first_name": "Bob",
+ *       "last_name": null, "email_verified": true}
+ *
+ * first_name, last_name and email_verified are optional.
+ * Lines that are invalid, or whose username/email already exist, are
+ * skipped and reported. Nothing is ever overwritten.
+ *
+ *
+*/
+
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::io;
+
+use crate::{
+    db, utils,
+    legacy_hashes::HashFormat
+};
+
+
+#[derive(Deserialize)]
+struct ImportedUser {
+    username: String,
+    email: String,
+    password_hash: String,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    email_verified: Option<bool>,
+}
+
+
+/**
+ * Import every user in the file.
+ * Only fails if the file can't be read. Bad lines are counted and skipped.
+ */
+pub async fn run_import(pool: &MySqlPool, file_path: Option<&String>) -> io::Result<()> {
+    let file_path: &String = match file_path {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: pattmayne_auth import-users <file.jsonl>");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No import file given"));
+        }
+    };
+
+    let contents: String = tokio::fs::read_to_string(file_path).await?;
+    let mut imported: usize = 0;
+    let mut skipped: usize = 0;
+
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let line_number: usize = index + 1;
+
+        match import_line(pool, line).await {
+            Ok(user_id) => {
+                imported += 1;
+                println!("Line {line_number}: imported user {user_id}");
+            },
+            Err(reason) => {
+                skipped += 1;
+                eprintln!("Line {line_number}: skipped ({reason})");
+            }
+        }
+    }
+
+    println!("Import finished. {imported} imported, {skipped} skipped.");
+
+    Ok(())
+}
+
+
+/**
+ * Check and save one line.
+ * @return the new user's id, or the reason it was skipped
+ */
+async fn import_line(pool: &MySqlPool, line: &str) -> Result<i32, String> {
+    let user: ImportedUser = serde_json::from_str(line)
+        .map_err(|e| format!("bad JSON: {e}"))?;
+
+    let username: String = user.username.trim().to_string();
+    let email: String = user.email.trim().to_string();
+
+    if !utils::validate_username(&username) {
+        return Err(format!("invalid username '{username}'"));
+    }
+
+    if !utils::validate_email(&email) {
+        return Err(format!("invalid email for '{username}'"));
+    }
+
+    // We must be able to verify it later, or the user could never log in
+    if HashFormat::of(&user.password_hash) == HashFormat::Unknown {
+        return Err(format!("unsupported hash format for '{username}'"));
+    }
+
+    if db::username_taken(pool, &username).await {
+        return Err(format!("username '{username}' already exists"));
+    }
+
+    if db::email_taken(pool, &email).await {
+        return Err(format!("email for '{username}' already exists"));
+    }
+
+    db::add_imported_user(
+        pool,
+        &username,
+        &email,
+        &user.password_hash,
+        &user.first_name,
+        &user.last_name,
+        user.email_verified.unwrap_or(false)
+    ).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,219 @@
+/*
+ *
+ *
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  LEGACY HASHES  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * Players imported from the old game servers come with the password
+ * hashes those servers made. We can verify them, but we never make new ones.
+ * After a successful login they are rehashed with Argon2.
+ *
+ * The format is identified by the prefix of the stored hash:
+ * -- $argon2id$ (or $argon2i$ / $argon2d$)  : ours (PHC string)
+ * -- $2a$ $2b$ $2x$ $2y$                     : bcrypt (modular crypt)
+ * -- $pbkdf2-sha256$i=...,l=...$             : PBKDF2-SHA256 (PHC string)
+ * -- $pbkdf2-sha256$<rounds>$                : PBKDF2-SHA256 (passlib modular crypt,
+ * --                                           "adapted" base64 with . for +)
+ *
+ *
+*/
+
+use base64::{ Engine, engine::general_purpose::STANDARD_NO_PAD };
+use password_hash::{ PasswordHash, PasswordVerifier };
+use pbkdf2::Pbkdf2;
+use sha2::Sha256;
+
+use crate::utils;
+
+
+#[derive(Debug, PartialEq)]
+pub enum HashFormat {
+    Argon2,
+    Bcrypt,
+    Pbkdf2Sha256,
+    Unknown,
+}
+
+
+impl HashFormat {
+    pub fn of(stored_hash: &str) -> HashFormat {
+        if stored_hash.starts_with("$argon2") {
+            HashFormat::Argon2
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"].iter()
+            .any(|prefix| stored_hash.starts_with(prefix)) {
+            HashFormat::Bcrypt
+        } else if stored_hash.starts_with("$pbkdf2-sha256$") {
+            HashFormat::Pbkdf2Sha256
+        } else {
+            HashFormat::Unknown
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        *self == HashFormat::Bcrypt || *self == HashFormat::Pbkdf2Sha256
+    }
+}
+
+
+/**
+ * Check a raw password against a bcrypt hash.
+ */
+pub fn verify_bcrypt(input_password: &str, stored_hash: &str) -> bool {
+    match bcrypt::verify(input_password, stored_hash) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("bcrypt hash error: {:?}", e);
+            false
+        }
+    }
+}
+
+
+/**
+ * Check a raw password against a PBKDF2-SHA256 hash, in either format.
+ */
+pub fn verify_pbkdf2_sha256(input_password: &str, stored_hash: &str) -> bool {
+    // ["", "pbkdf2-sha256", params or rounds, salt, checksum]
+    let parts: Vec<&str> = stored_hash.split('$').collect();
+    if parts.len() != 5 {
+        eprintln!("PBKDF2 hash error: wrong number of fields");
+        return false;
+    }
+
+    if parts[2].starts_with("i=") {
+        verify_pbkdf2_phc(input_password, stored_hash)
+    } else {
+        verify_pbkdf2_passlib(input_password, &parts)
+    }
+}
+
+
+// $pbkdf2-sha256$i=600000,l=32$<salt>$<hash>
+fn verify_pbkdf2_phc(input_password: &str, stored_hash: &str) -> bool {
+    match PasswordHash::new(stored_hash) {
+        Ok(parsed_hash) => {
+            Pbkdf2.verify_password(input_password.as_bytes(), &parsed_hash).is_ok()
+        },
+        Err(e) => {
+            eprintln!("PBKDF2 hash error: {:?}", e);
+            false
+        }
+    }
+}
+
+
+// $pbkdf2-sha256$29000$<ab64 salt>$<ab64 checksum>
+fn verify_pbkdf2_passlib(input_password: &str, parts: &[&str]) -> bool {
+    let rounds: u32 = match parts[2].parse::<u32>() {
+        Ok(rounds) => rounds,
+        Err(_e) => {
+            eprintln!("PBKDF2 hash error: bad rounds");
+            return false;
+        }
+    };
+
+    let (salt, expected) = match (ab64_decode(parts[3]), ab64_decode(parts[4])) {
+        (Some(salt), Some(expected)) => (salt, expected),
+        _ => {
+            eprintln!("PBKDF2 hash error: bad base64");
+            return false;
+        }
+    };
+
+    if expected.is_empty() {
+        return false;
+    }
+
+    let mut derived: Vec<u8> = vec![0u8; expected.len()];
+    pbkdf2::pbkdf2_hmac::<Sha256>(input_password.as_bytes(), &salt, rounds, &mut derived);
+
+    utils::constant_time_eq(&derived, &expected)
+}
+
+
+// passlib's "adapted base64": standard alphabet, but . instead of +, and no padding
+fn ab64_decode(input: &str) -> Option<Vec<u8>> {
+    STANDARD_NO_PAD.decode(input.replace('.', "+")).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Openwall crypt_blowfish test vectors
+    const BCRYPT_U_U: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+    const BCRYPT_U_U_U: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.VGOzA784oUp/Z0DY336zx7pLYAy0lwK";
+
+    // PBKDF2-HMAC-SHA256("password", "salt", 1 and 4096 rounds, 32 bytes), passlib format
+    const PASSLIB_1: &str = "$pbkdf2-sha256$1$c2FsdA$Eg.2z/z4syxD5yJSVsT4N6hlSMkszDVICAWYfLcL4Xs";
+    const PASSLIB_4096: &str = "$pbkdf2-sha256$4096$c2FsdA$xeR41ZKIyEGqUw22hFxMjZYok6ABzk4RpJY4c6qYE0o";
+
+    // PBKDF2-HMAC-SHA256("hunter2", bytes 0..16, 1000 rounds, 32 bytes), PHC format
+    const PHC_1000: &str = "$pbkdf2-sha256$i=1000,l=32$AAECAwQFBgcICQoLDA0ODw$9VUOiRGfWTzTZixtfaW9P3qQ4lzS3CIfWKYWbHcnU9M";
+
+    #[test]
+    fn format_is_read_from_the_prefix() {
+        assert_eq!(HashFormat::of("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA"), HashFormat::Argon2);
+        assert_eq!(HashFormat::of(BCRYPT_U_U), HashFormat::Bcrypt);
+        assert_eq!(HashFormat::of("$2y$10$abc"), HashFormat::Bcrypt);
+        assert_eq!(HashFormat::of(PASSLIB_1), HashFormat::Pbkdf2Sha256);
+        assert_eq!(HashFormat::of(PHC_1000), HashFormat::Pbkdf2Sha256);
+        assert_eq!(HashFormat::of("5f4dcc3b5aa765d61d8327deb882cf99"), HashFormat::Unknown);
+    }
+
+    #[test]
+    fn only_bcrypt_and_pbkdf2_are_legacy() {
+        assert!(HashFormat::Bcrypt.is_legacy());
+        assert!(HashFormat::Pbkdf2Sha256.is_legacy());
+        assert!(!HashFormat::Argon2.is_legacy());
+        assert!(!HashFormat::Unknown.is_legacy());
+    }
+
+    #[test]
+    fn bcrypt_vectors_verify() {
+        assert!(verify_bcrypt("U*U", BCRYPT_U_U));
+        assert!(verify_bcrypt("U*U*", BCRYPT_U_U_U));
+        assert!(!verify_bcrypt("U*U*", BCRYPT_U_U));
+    }
+
+    #[test]
+    fn broken_bcrypt_hash_never_matches() {
+        assert!(!verify_bcrypt("U*U", "$2a$05$tooshort"));
+    }
+
+    #[test]
+    fn passlib_pbkdf2_vectors_verify() {
+        assert!(verify_pbkdf2_sha256("password", PASSLIB_1));
+        assert!(verify_pbkdf2_sha256("password", PASSLIB_4096));
+        assert!(!verify_pbkdf2_sha256("Password", PASSLIB_4096));
+    }
+
+    #[test]
+    fn phc_pbkdf2_vector_verifies() {
+        assert!(verify_pbkdf2_sha256("hunter2", PHC_1000));
+        assert!(!verify_pbkdf2_sha256("hunter3", PHC_1000));
+    }
+
+    #[test]
+    fn broken_pbkdf2_hashes_never_match() {
+        assert!(!verify_pbkdf2_sha256("password", "$pbkdf2-sha256$1$c2FsdA"));
+        assert!(!verify_pbkdf2_sha256("password", "$pbkdf2-sha256$lots$c2FsdA$Eg.2z"));
+        assert!(!verify_pbkdf2_sha256("password", "$pbkdf2-sha256$1$c2FsdA$!!!"));
+        assert!(!verify_pbkdf2_sha256("password", "$pbkdf2-sha256$1$c2FsdA$"));
+    }
+
+    #[test]
+    fn ab64_uses_dot_for_plus() {
+        assert_eq!(ab64_decode("..8"), Some(vec![0xfb, 0xef]));
+        assert_eq!(ab64_decode("c2FsdA"), Some(b"salt".to_vec()));
+    }
+}
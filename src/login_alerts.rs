@@ -0,0 +1,212 @@
+/*
+ *
+ *
+ *
+ *
+ * ==========================
+ * ==========================
+ * =====                =====
+ * =====  LOGIN ALERTS  =====
+ * =====                =====
+ * ==========================
+ * ==========================
+ *
+ *
+ * Email the user when a login looks unusual:
+ * -- a device (user agent) the account has never used
+ * -- an IP range the account has never used
+ * -- right after a burst of failed login attempts
+ *
+ * "Never used" comes from the user's sessions (refresh_tokens) and their
+ * successful logins in the audit log. An account with no history at all
+ * (brand new, or just imported) doesn't get alerts for its first login.
+ *
+ * The email has a "this wasn't me" link, which signs out every session
+ * and starts a password reset.
+ *
+ * MUST be checked BEFORE the new session is created and the login is audited,
+ * or every device would look known.
+ *
+ *
+*/
+
+use actix_web::HttpRequest;
+use sqlx::MySqlPool;
+use time::{ Duration, OffsetDateTime };
+
+use crate::{
+    auth, config, db, mailer, routes_utils, utils,
+    audit::{ self, AuditEvent },
+    resources::get_translation,
+};
+
+
+#[derive(Default)]
+pub struct LoginRisk {
+    pub new_device: bool,
+    pub new_ip_range: bool,
+    pub after_failures: bool,
+}
+
+
+impl LoginRisk {
+    pub fn is_suspicious(&self) -> bool {
+        self.new_device || self.new_ip_range || self.after_failures
+    }
+
+    // Translation keys for each reason, for the email
+    fn reason_keys(&self) -> Vec<&'static str> {
+        let mut keys: Vec<&'static str> = Vec::new();
+        if self.new_device { keys.push("mail.login_alert.new_device"); }
+        if self.new_ip_range { keys.push("mail.login_alert.new_location"); }
+        if self.after_failures { keys.push("mail.login_alert.after_failures"); }
+        keys
+    }
+
+    // Short version for the audit log
+    fn summary(&self) -> String {
+        let mut reasons: Vec<&str> = Vec::new();
+        if self.new_device { reasons.push("new device"); }
+        if self.new_ip_range { reasons.push("new IP range"); }
+        if self.after_failures { reasons.push("after failed attempts"); }
+        reasons.join(", ")
+    }
+}
+
+
+/**
+ * How unusual is this login, compared to the account's history?
+ * DB errors are logged and count as "not unusual": a broken check
+ * must never stop someone logging in.
+ */
+pub async fn assess_login(
+    pool: &MySqlPool,
+    user_id: i32,
+    device: &db::SessionDevice
+) -> LoginRisk {
+    let mut risk: LoginRisk = LoginRisk::default();
+
+    let since: OffsetDateTime =
+        OffsetDateTime::now_utc() - Duration::minutes(config::failure_burst_minutes());
+
+    match db::count_audit_events_since(
+        pool, user_id, AuditEvent::LoginFailure.as_str(), since).await {
+        Ok(failures) => risk.after_failures = failures >= config::failure_burst_count(),
+        Err(e) => eprintln!("Could not count failed logins: {e}")
+    }
+
+    let known_ips: Vec<String> = match db::get_known_ip_addresses(pool, user_id).await {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            eprintln!("Could not get known IP addresses: {e}");
+            return risk;
+        }
+    };
+
+    let user_agent_known: bool =
+        match db::user_agent_known(pool, user_id, &device.user_agent).await {
+            Ok(known) => known,
+            Err(e) => {
+                eprintln!("Could not check known devices: {e}");
+                return risk;
+            }
+        };
+
+    // No history at all: nothing to compare against
+    if known_ips.is_empty() && !user_agent_known {
+        return risk;
+    }
+
+    let this_range: String = utils::ip_range(&device.ip_address);
+    risk.new_device = !user_agent_known;
+    risk.new_ip_range = !known_ips.iter().any(|ip| utils::ip_range(ip) == this_range);
+
+    risk
+}
+
+
+/**
+ * Email the user about an unusual login, with a "this wasn't me" link.
+ * Failures are logged. The login goes ahead regardless.
+ */
+pub async fn send_login_alert(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    user: &db::User,
+    client_id: &String,
+    risk: &LoginRisk
+) {
+    let lang: utils::SupportedLangs = auth::get_user_req_data(req).clone_lang();
+    let device: db::SessionDevice = routes_utils::session_device(req);
+
+    let client_name: String = match db::get_client_by_client_id(pool, client_id).await {
+        Ok(Some(client)) => client.name,
+        _ => client_id.to_owned()
+    };
+
+    let token: String = auth::generate_email_token();
+    if let Err(e) = db::add_account_token(
+        pool,
+        user.get_id(),
+        &token,
+        db::TOKEN_NOT_ME,
+        Duration::days(config::not_me_link_days())
+    ).await {
+        eprintln!("Could not send login alert: {e}");
+        return;
+    }
+
+    let not_me_url: String = format!("{}/auth/not_me/{}", config::auth_domain(), token);
+
+    let reasons: String = risk.reason_keys().iter()
+        .map(|key| get_translation(key, &lang, None))
+        .collect::<Vec<String>>()
+        .join("\n- ");
+
+    let time: String = utils::format_utc(&OffsetDateTime::now_utc());
+    let location: String = approximate_location(req, &device.ip_address);
+
+    mailer::queue_mail(mailer::OutgoingMail {
+        to: user.get_email().to_owned(),
+        subject: get_translation("mail.login_alert.subject", &lang, None),
+        body: get_translation(
+            "mail.login_alert.body",
+            &lang,
+            Some(&[
+                user.get_username(),
+                &reasons,
+                &time,
+                &location,
+                &client_name,
+                &device.device_label,
+                &not_me_url
+            ])),
+    });
+
+    audit::record(
+        pool, req, AuditEvent::LoginAlert,
+        Some(user.get_id()), Some(client_id), &risk.summary()).await;
+}
+
+
+/**
+ * Roughly where the login came from.
+ * We have no GeoIP database, so this is whatever our proxy/CDN tells us
+ * in LOCATION_HEADER (ie: a country code), plus the IP range.
+ */
+fn approximate_location(req: &HttpRequest, ip_address: &String) -> String {
+    let ip_range: String = utils::ip_range(ip_address);
+    let header_name: String = config::location_header();
+
+    if header_name.is_empty() {
+        return ip_range;
+    }
+
+    match req.headers().get(header_name.as_str()).and_then(|value| value.to_str().ok()) {
+        // Cloudflare uses XX for "unknown"
+        Some(location) if !location.is_empty() && location != "XX" => {
+            format!("{location} ({ip_range})")
+        },
+        _ => ip_range
+    }
+}
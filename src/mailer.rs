@@ -0,0 +1,238 @@
+/*
+ *
+ *
+ *
+ *
+ * ====================
+ * ====================
+ * =====          =====
+ * =====  MAILER  =====
+ * =====          =====
+ * ====================
+ * ====================
+ *
+ *
+ * Outgoing email.
+ * Where mail actually goes depends on the MAIL_TRANSPORT env variable:
+ * -- "file" (default): every message is written to a local "sink"
+ * --                   directory (one file per message) so we can read them while testing
+ * -- "mailjet":        sent through the Mailjet HTTP API
+ *
+ * To plug in another service, implement the Mailer trait
+ * and add it to configured_mailer().
+ *
+ *
+*/
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::{ auth, config };
+
+
+pub struct OutgoingMail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+
+// Not Send: the HTTP client lives on the actix (single thread) runtime
+pub type SendResult<'a> = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + 'a>>;
+
+
+/**
+ * Anything that can deliver an email.
+ */
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, mail: &'a OutgoingMail) -> SendResult<'a>;
+}
+
+
+static MAILER: OnceLock<Box<dyn Mailer>> = OnceLock::new();
+
+
+// The address our messages come from
+pub fn mail_from() -> String {
+    config::env_or("MAIL_FROM", String::from("no-reply@crankade.com"))
+}
+
+
+/**
+ * Pick the mailer from MAIL_TRANSPORT. Falls back to the file sink.
+ */
+fn configured_mailer() -> Box<dyn Mailer> {
+    let transport: String = config::env_or("MAIL_TRANSPORT", String::from("file"));
+
+    if transport == "mailjet" {
+        match (std::env::var("MAILJET_API_KEY"), std::env::var("MAILJET_SECRET_KEY")) {
+            (Ok(api_key), Ok(secret_key)) => {
+                return Box::new(MailjetMailer { api_key, secret_key });
+            },
+            _ => eprintln!("MAILJET_API_KEY or MAILJET_SECRET_KEY missing. Using the file sink.")
+        }
+    }
+
+    Box::new(FileSinkMailer {
+        dir: config::env_or("MAIL_SINK_DIR", String::from("./mail_sink"))
+    })
+}
+
+
+fn mailer() -> &'static dyn Mailer {
+    MAILER.get_or_init(configured_mailer).as_ref()
+}
+
+
+/**
+ * Send an email in the background so the request doesn't wait for it.
+ * Failures are logged, never returned.
+ */
+pub fn queue_mail(mail: OutgoingMail) {
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_mail(mail).await {
+            eprintln!("Failed to send queued email: {e}");
+        }
+    });
+}
+
+
+/**
+ * Send one email with whichever mailer is configured.
+ * Callers usually don't want a failed email to fail the whole request,
+ * so they can log the error and carry on.
+ */
+pub async fn send_mail(mail: OutgoingMail) -> Result<(), anyhow::Error> {
+    mailer().send(&mail).await
+}
+
+
+
+/*
+ *
+ *
+ * =======================
+ * =======================
+ * =====             =====
+ * =====  FILE SINK  =====
+ * =====             =====
+ * =======================
+ * =======================
+ *
+ *
+ * For testing. Nothing leaves the machine.
+ *
+*/
+
+pub struct FileSinkMailer {
+    dir: String,
+}
+
+impl Mailer for FileSinkMailer {
+    fn send<'a>(&'a self, mail: &'a OutgoingMail) -> SendResult<'a> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await
+                .map_err(|e| anyhow!("Could not create mail sink directory: {e}"))?;
+
+            // Timestamp first so the files sort in the order they were sent
+            let file_name: String = format!(
+                "{}/{}_{}.eml",
+                self.dir,
+                OffsetDateTime::now_utc().unix_timestamp_nanos(),
+                &auth::generate_auth_code()[..8]
+            );
+
+            let contents: String = format!(
+                "From: {}\nTo: {}\nSubject: {}\nDate: {}\n\n{}\n",
+                mail_from(),
+                mail.to,
+                mail.subject,
+                OffsetDateTime::now_utc(),
+                mail.body
+            );
+
+            tokio::fs::write(&file_name, contents).await
+                .map_err(|e| anyhow!("Could not write mail to sink: {e}"))?;
+
+            Ok(())
+        })
+    }
+}
+
+
+
+/*
+ *
+ *
+ * =====================
+ * =====================
+ * =====           =====
+ * =====  MAILJET  =====
+ * =====           =====
+ * =====================
+ * =====================
+ *
+ *
+ * Mailjet's Send API v3.1, with basic auth (API key : secret key).
+ *
+*/
+
+const MAILJET_SEND_URL: &str = "https://api.mailjet.com/v3.1/send";
+
+pub struct MailjetMailer {
+    api_key: String,
+    secret_key: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MailjetAddress {
+    email: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MailjetMessage {
+    from: MailjetAddress,
+    to: Vec<MailjetAddress>,
+    subject: String,
+    text_part: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MailjetRequest {
+    messages: Vec<MailjetMessage>,
+}
+
+impl Mailer for MailjetMailer {
+    fn send<'a>(&'a self, mail: &'a OutgoingMail) -> SendResult<'a> {
+        Box::pin(async move {
+            let request: MailjetRequest = MailjetRequest {
+                messages: vec![MailjetMessage {
+                    from: MailjetAddress { email: mail_from() },
+                    to: vec![MailjetAddress { email: mail.to.to_owned() }],
+                    subject: mail.subject.to_owned(),
+                    text_part: mail.body.to_owned(),
+                }]
+            };
+
+            let response = awc::Client::default()
+                .post(MAILJET_SEND_URL)
+                .basic_auth(&self.api_key, &self.secret_key)
+                .send_json(&request)
+                .await
+                .map_err(|e| anyhow!("Could not reach Mailjet: {e}"))?;
+
+            if !response.status().is_success() {
+                return Err(anyhow!("Mailjet refused the message: {}", response.status()));
+            }
+
+            Ok(())
+        })
+    }
+}
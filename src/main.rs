@@ -2,7 +2,6 @@
 
 use actix_web::{ App, HttpServer, middleware::{from_fn}, web };
 use actix_files::Files;
-use dotenvy;
 use sqlx::{ MySqlPool };
 use std::io;
 
@@ -16,6 +15,16 @@ mod resources;
 mod resource_mgr;
 mod auth_code_shared;
 mod routes_utils;
+mod config;
+mod mailer;
+mod password_policy;
+mod hash_pool;
+mod legacy_hashes;
+mod import_users;
+mod audit;
+mod login_alerts;
+mod pow;
+mod rbac;
 
 
 /**
@@ -41,7 +50,22 @@ async fn main() -> std::io::Result<()> {
         Err(_e) => return database_pool_err().await
     };
 
+    // Command line tools run instead of the server
+    // ie: pattmayne_auth import-users players.jsonl
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("import-users") {
+        return import_users::run_import(&pool, args.get(2)).await;
+    }
+
     db_first_entries(&pool).await;
+    check_permissions(&pool).await;
+
+    let breached_count: usize = password_policy::load_breached_passwords();
+    println!("Loaded {breached_count} breached passwords.");
+
+    // Argon2 runs on its own threads, never on the actix workers
+    let hash_threads: usize = hash_pool::start();
+    println!("Started {hash_threads} password hashing threads.");
 
     HttpServer::new(move || {
         App::new()
@@ -53,6 +77,7 @@ async fn main() -> std::io::Result<()> {
             .service(routes::error_root)
             .service(routes::error_root_2)
             .service(routes::error_page)
+            .service(routes::error_reason_page)
             .service(
                 web::scope("/auth")
                     .route("/login", web::get().to(routes::login_page))
@@ -61,9 +86,21 @@ async fn main() -> std::io::Result<()> {
                     .route("", web::get().to(routes::auth_home))
                     .service(routes::login_post)
                     .service(routes::register_post)
+                    .service(routes::challenge)
+                    .service(routes::confirm_registration)
                     .service(routes::logout_post)
                     .service(routes::update_names)
                     .service(routes::update_password)
+                    .service(routes::list_sessions)
+                    .service(routes::revoke_session)
+                    .service(routes::revoke_other_sessions)
+                    .service(routes::stop_impersonating)
+                    .service(routes::step_up_post)
+                    .service(routes::not_me_page)
+                    .service(routes::not_me_post)
+                    .service(routes::reset_password_page)
+                    .service(routes::reset_password_post)
+                    .service(routes::suspended_page)
             )
             .service(
                 web::scope("/admin")
@@ -75,14 +112,64 @@ async fn main() -> std::io::Result<()> {
                     .service(routes::update_client_post)
                     .service(routes::edit_client_site_form_page)
                     .service(routes::req_secret_post)
+                    .service(routes::archive_client_post)
+                    .service(routes::restore_client_post)
+                    .service(routes::delete_client_post)
+                    .service(routes::hash_pool_stats)
+                    .service(routes::audit_log_page)
+                    .service(routes::suspensions_page)
+                    .service(routes::suspend_post)
+                    .service(routes::lift_suspension_post)
+                    .service(routes::admin_restrict_user_post)
+                    .service(routes::admin_unrestrict_user_post)
+                    .service(routes::add_client_role_post)
+                    .service(routes::remove_client_role_post)
+                    .service(routes::admin_users_page)
+                    .service(routes::admin_user_page)
+                    .service(routes::admin_update_names_post)
+                    .service(routes::admin_update_role_post)
+                    .service(routes::admin_add_role_post)
+                    .service(routes::admin_remove_role_post)
+                    .service(routes::admin_verify_email_post)
+                    .service(routes::admin_force_reset_post)
+                    .service(routes::admin_revoke_session_post)
+                    .service(routes::admin_revoke_sessions_post)
+                    .service(routes::admin_impersonate_post)
+                    .service(routes::client_reviews_page)
+                    .service(routes::approve_client_post)
+                    .service(routes::reject_client_post)
+                    .service(routes::registration_tokens_page)
+                    .service(routes::issue_registration_token_post)
+                    .service(routes::revoke_registration_token_post)
+            )
+            .service(
+                web::scope("/developer")
+                    .route("/", web::get().to(routes::developer_page))
+                    .route("", web::get().to(routes::developer_page))
+                    .service(routes::developer_add_client_post)
+                    .service(routes::developer_update_client_post)
+                    .service(routes::developer_secret_post)
             )
             .service(
                 web::scope("/ext_auth")
                     .service(routes::verify_auth_code)
                     .service(routes::check_refresh)
+                    .service(routes::restrict_user)
+                    .service(routes::unrestrict_user)
+                    .service(routes::add_user_role)
+                    .service(routes::remove_user_role)
+            )
+            .service(
+                web::scope("/oauth")
+                    .service(routes::register_client_post)
+                    .service(routes::read_registration)
+                    .service(routes::update_registration)
+                    .service(routes::delete_registration)
             )
             .default_service(web::get().to(routes::not_found)) // <- catch-all
             .wrap(from_fn(middleware::jwt_cookie_middleware))
+            .wrap(from_fn(middleware::csrf_middleware))
+            .wrap(from_fn(middleware::security_headers_middleware))
     })
     .bind(("127.0.0.1", 3000))?
     .run()
@@ -124,12 +211,29 @@ async fn db_first_entries(pool: &MySqlPool) {
 }
 
 
+/**
+ * Every permission the code checks for must be in the permissions table,
+ * or no role can ever have it. Say so at startup (a missing migration).
+ */
+async fn check_permissions(pool: &MySqlPool) {
+    match db::get_permission_names(pool).await {
+        Ok(names) => {
+            for permission in rbac::Permission::all() {
+                if !names.iter().any(|name| name == permission.as_str()) {
+                    eprintln!("WARNING: permission {} is not in the DB.", permission.as_str());
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("DB Error: {e}");
+        }
+    };
+}
+
+
 async fn database_pool_err() -> std::io::Result<()> {
     eprintln!("ERROR: NO HASH ID SECRET.");
-    return Err(
-        io::Error::new(
-            io::ErrorKind::Other, "HASHID_SECRET not set")
-    );
+    Err(io::Error::other("HASHID_SECRET not set"))
 }
 
 
@@ -15,16 +15,29 @@
  * That post-processing happens AFTER all the later calls
  */
 
-use std::any::Any;
 
 use actix_web::{
-    web, error, Error, HttpMessage,
-    body::MessageBody, dev::{ServiceRequest, ServiceResponse},
+    web, error, Error, HttpMessage, HttpRequest, HttpResponse,
+    body::{ EitherBody, MessageBody }, dev::{ServiceRequest, ServiceResponse},
+    http::{ Method, header::{ self, HeaderMap, HeaderName, HeaderValue } },
     middleware::{ Next }
 };
+use rand::{ distr::Alphanumeric, Rng };
 use sqlx::{MySqlPool };
 
-use crate::{ auth, db, utils };
+use crate::{ auth, config, db, utils, routes_utils::ErrorResponse };
+
+
+// This browser's CSRF token, for login_status_middleware to hand to the templates
+pub struct CsrfToken {
+    token: String
+}
+
+
+// This response's CSP nonce, for login_status_middleware to hand to the templates
+pub struct CspNonce {
+    nonce: String
+}
 
 
 pub struct NewJwtObj {
@@ -80,13 +93,40 @@ pub async fn login_status_middleware(
 
     let guest_data: auth::UserReqData = auth::UserReqData::new(None);
     let user_req_data_opt: Option<actix_web::cookie::Cookie<'_>> = req.cookie("jwt");
-    let user_req_data: auth::UserReqData = get_user_req_data_from_opt(
-        &pool,
+    let mut user_req_data: auth::UserReqData = get_user_req_data_from_opt(
+        pool,
         user_req_data_opt,
         &req,
         guest_data
     ).await?;
 
+    // A suspended user is a guest, whatever their JWT says
+    if let Some(id) = user_req_data.id {
+        match db::get_active_suspension(pool, id).await {
+            Ok(Some(_suspension)) => user_req_data = auth::UserReqData::new(None),
+            Ok(None) => {},
+            Err(e) => return Err(error::ErrorInternalServerError(e.to_string()))
+        }
+    }
+
+    // Permissions come from the DB every time, so a revoked role takes effect at once
+    if let Some(id) = user_req_data.id {
+        match db::get_user_permissions(pool, id).await {
+            Ok(permissions) => user_req_data.permissions = permissions,
+            Err(e) => return Err(error::ErrorInternalServerError(e.to_string()))
+        }
+    }
+
+    // csrf_middleware already ran, so the token is here for the templates
+    if let Some(csrf) = req.extensions().get::<CsrfToken>() {
+        user_req_data.csrf_token = csrf.token.to_owned();
+    }
+
+    // Same for the CSP nonce from security_headers_middleware
+    if let Some(csp) = req.extensions().get::<CspNonce>() {
+        user_req_data.csp_nonce = csp.nonce.to_owned();
+    }
+
     // Put UserReqData into the request object to identify user to all routes.
     req.extensions_mut().insert(user_req_data);
     next.call(req).await
@@ -133,12 +173,11 @@ async fn get_user_req_data_from_opt(
             if r_token_optn.is_none() { return Ok(guest_data); }
             let r_tkn_ckie: actix_web::cookie::Cookie<'_> = r_token_optn.unwrap();
 
-            // check DB for refresh_token to compare
+            // check DB for the session this refresh_token belongs to
             let r_db_token_result: Result<Option<db::RefreshToken>, anyhow::Error> =
                 db::get_refresh_token(
-                    &pool,
-                    claims.get_sub(),
-                    utils::auth_client_id()
+                    pool,
+                    &r_tkn_ckie.value().to_string()
                 ).await;
 
             if let Err(e) = r_db_token_result {
@@ -149,26 +188,60 @@ async fn get_user_req_data_from_opt(
             if r_db_token_option.is_none() { return Ok(guest_data); }
             let r_db_token: db::RefreshToken = r_db_token_option.unwrap();
 
-            let r_tkn_valid: bool = 
-                r_tkn_ckie.value() == r_db_token.get_token() &&
+            // An expired impersonation: the session is the admin's, and they get themself back
+            // (their own auth_time is lost, so sensitive actions ask for the password again)
+            let (user_id, username, auth_time): (i32, String, usize) = match claims.get_act() {
+                Some(actor) => (actor.sub, actor.username.to_owned(), 0),
+                None => (claims.get_sub(), claims.get_username().to_owned(), claims.get_auth_time())
+            };
+
+            // The session must belong to this user, on this (auth) site
+            let r_tkn_valid: bool =
+                r_db_token.get_user_id() == user_id &&
+                r_db_token.get_client_id() == &utils::auth_client_id() &&
                 !r_db_token.is_expired();
 
             if r_tkn_valid {
-                // CREATE and GIVE NEW JWT
-                let new_jwt_rslt: Result<String, auth::AuthError> =
-                    auth::generate_jwt(
-                        claims.get_sub(),
-                        claims.get_username().to_owned(),
-                        claims.get_role().to_owned()
-                    );
-
-                if let Err(e) = new_jwt_rslt {
-                    return Err(error::ErrorInternalServerError(e.to_string()));
+                let ip_address: String = utils::client_ip(req.peer_addr(), req.headers());
+                if let Err(e) = db::touch_refresh_token(
+                    pool,
+                    r_db_token.get_id(),
+                    &ip_address
+                ).await {
+                    eprintln!("Database error: {e}");
                 }
 
-                let new_jwt = new_jwt_rslt.unwrap();
+                // Role and roles may have changed since the last JWT
+                let role: String = match db::get_username_and_role_by_id(pool, user_id).await {
+                    Ok(Some(username_and_role)) => username_and_role.role,
+                    Ok(None) => return Ok(guest_data),
+                    Err(e) => return Err(error::ErrorInternalServerError(e.to_string()))
+                };
+
+                let client_roles: Vec<String> = match db::get_client_user_roles(
+                    pool, &utils::auth_client_id(), user_id).await {
+                    Ok(roles) => roles,
+                    Err(e) => return Err(error::ErrorInternalServerError(e.to_string()))
+                };
+
+                // CREATE and GIVE NEW JWT
+                let new_claims: auth::Claims = auth::Claims::new(
+                    user_id,
+                    username,
+                    role,
+                    client_roles,
+                    None,
+                    auth_time,
+                    time::Duration::minutes(auth::JWT_MINUTES)
+                );
+
+                let new_jwt: String = match auth::encode_jwt(&new_claims) {
+                    Ok(jwt) => jwt,
+                    Err(e) => return Err(error::ErrorInternalServerError(e.to_string()))
+                };
+
                 req.extensions_mut().insert(NewJwtObj::new(new_jwt));
-                return Ok(auth::UserReqData::new(Some(claims)));
+                Ok(auth::UserReqData::new(Some(new_claims)))
             } else {
                 Ok(guest_data)
             }                   
@@ -224,4 +297,366 @@ pub async fn jwt_cookie_middleware<B>(
         res.response_mut().add_cookie(&cookie).ok();
     }
     Ok(res)
-}
\ No newline at end of file
+}
+
+
+
+/* 
+ * 
+ * 
+ * 
+ * 
+ * ==================
+ * ==================
+ * =====        =====
+ * =====  CSRF  =====
+ * =====        =====
+ * ==================
+ * ==================
+ * 
+ * 
+ * Our own pages authenticate with cookies, which the browser sends along
+ * even when another site makes the request. So every state-changing
+ * request must:
+ * -- come from our own origin (Origin header, or Referer if there's no Origin)
+ * -- carry the CSRF token in the X-CSRF-Token header, matching the csrf_token cookie
+ * 
+ * The templates put the token in a meta tag and the fetch helpers in
+ * static/js send it automatically.
+ * 
+ * /ext_auth is skipped: client sites call it server to server with their
+ * client secret, not with a browser's cookies.
+ * So is /oauth (client registration): a bearer token, no cookies either.
+ * 
+ * MUST wrap OUTSIDE login_status_middleware (ie: registered after it)
+ * so the token is ready when UserReqData is built.
+ * 
+ * 
+*/
+
+
+/**
+ * Check Origin and the CSRF token on state-changing requests.
+ * Give every browser a CSRF token cookie if it doesn't have one.
+ */
+pub async fn csrf_middleware<B>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> where B: MessageBody, {
+    let cookie_token: Option<String> = req
+        .cookie(auth::CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty());
+
+    if needs_csrf_check(&req) {
+        let header_token: Option<&str> = req
+            .headers()
+            .get(auth::CSRF_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        let token_valid: bool = match (&cookie_token, header_token) {
+            (Some(cookie), Some(header)) => {
+                utils::constant_time_eq(cookie.as_bytes(), header.as_bytes())
+            },
+            _ => false
+        };
+
+        if !origin_allowed(&req) || !token_valid {
+            let response: HttpResponse = HttpResponse::Forbidden().json(ErrorResponse {
+                error: String::from("Request could not be verified. Reload the page and try again."),
+                code: 403
+            });
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    let is_new: bool = cookie_token.is_none();
+    let token: String = cookie_token.unwrap_or_else(auth::generate_csrf_token);
+    req.extensions_mut().insert(CsrfToken { token: token.to_owned() });
+
+    let mut res: ServiceResponse<B> = next.call(req).await?;
+
+    if is_new {
+        res.response_mut().add_cookie(&auth::build_csrf_cookie(token)).ok();
+    }
+
+    Ok(res.map_into_left_body())
+}
+
+
+// Reading never changes anything. Client sites don't use cookies.
+fn needs_csrf_check(req: &ServiceRequest) -> bool {
+    let safe_method: bool = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    !safe_method && !req.path().starts_with("/ext_auth") && !req.path().starts_with("/oauth")
+}
+
+
+/**
+ * Did the request come from a page on AUTH_DOMAIN?
+ * Browsers send Origin on POSTs. Fall back to Referer if some don't.
+ * Neither header: refuse (we can't tell where it came from).
+ */
+fn origin_allowed(req: &ServiceRequest) -> bool {
+    let auth_domain: String = config::auth_domain();
+
+    if let Some(origin) = req.headers().get(header::ORIGIN) {
+        return origin.to_str().map(|origin| origin == auth_domain).unwrap_or(false);
+    }
+
+    match req.headers().get(header::REFERER).and_then(|value| value.to_str().ok()) {
+        Some(referer) => referer == auth_domain || referer.starts_with(&format!("{auth_domain}/")),
+        None => false
+    }
+}
+
+
+
+/* 
+ * 
+ * 
+ * 
+ * 
+ * ==============================
+ * ==============================
+ * =====                    =====
+ * =====  SECURITY HEADERS  =====
+ * =====                    =====
+ * ==============================
+ * ==============================
+ * 
+ * 
+ * Every response gets CSP, HSTS, X-Frame-Options, X-Content-Type-Options,
+ * Referrer-Policy and Permissions-Policy. Defaults come from config.rs.
+ * 
+ * The CSP only allows scripts from our own origin, plus inline scripts
+ * carrying this response's nonce: <script nonce="{{ user.csp_nonce }}">
+ * No inline event handlers (onclick etc). Use addEventListener.
+ * 
+ * A route can change the policy for its own response with
+ * override_security_policy() (ie: a login widget other sites may frame).
+ * Any header the route sets itself is left alone.
+ * 
+ * MUST wrap OUTSIDE login_status_middleware (registered after it)
+ * so the nonce is ready when UserReqData is built.
+ * 
+ * 
+*/
+
+
+/**
+ * The parts of the security headers a route may change.
+ */
+#[derive(Clone)]
+pub struct SecurityPolicy {
+    pub frame_ancestors: String, // 'none', 'self', or a list of origins
+    pub extra_script_src: String, // more places scripts may come from
+    pub extra_connect_src: String, // more places fetch() may talk to
+}
+
+
+impl SecurityPolicy {
+    pub fn from_config() -> Self {
+        SecurityPolicy {
+            frame_ancestors: config::frame_ancestors(),
+            extra_script_src: String::new(),
+            extra_connect_src: String::new(),
+        }
+    }
+
+    // The default policy, but these origins may frame the page
+    pub fn framed_by(origins: &str) -> Self {
+        SecurityPolicy {
+            frame_ancestors: origins.to_string(),
+            ..SecurityPolicy::from_config()
+        }
+    }
+
+    /**
+     * Build the Content-Security-Policy header value.
+     * Styles allow 'unsafe-inline' for the style="" attributes in our
+     * templates and the ones Foundation sets.
+     */
+    fn content_security_policy(&self, nonce: &str) -> String {
+        format!(
+            "default-src 'self'; \
+            script-src 'self' 'nonce-{nonce}' {}; \
+            style-src 'self' 'unsafe-inline'; \
+            img-src 'self' data: https:; \
+            connect-src 'self' {}; \
+            object-src 'none'; \
+            base-uri 'self'; \
+            form-action 'self'; \
+            frame-ancestors {}",
+            self.extra_script_src,
+            self.extra_connect_src,
+            self.frame_ancestors
+        )
+    }
+
+    // Old browsers only understand X-Frame-Options, which can't list origins
+    fn x_frame_options(&self) -> Option<&'static str> {
+        match self.frame_ancestors.trim() {
+            "'none'" => Some("DENY"),
+            "'self'" => Some("SAMEORIGIN"),
+            _ => None
+        }
+    }
+}
+
+
+/**
+ * Call from a route to use a different policy for its response.
+ */
+pub fn override_security_policy(req: &HttpRequest, policy: SecurityPolicy) {
+    req.extensions_mut().insert(policy);
+}
+
+
+/**
+ * Make a nonce for this response's CSP, then add the security headers
+ * once the route is done.
+ */
+pub async fn security_headers_middleware<B>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, Error> where B: MessageBody, {
+    let nonce: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect();
+
+    req.extensions_mut().insert(CspNonce { nonce: nonce.to_owned() });
+
+    let mut res: ServiceResponse<B> = next.call(req).await?;
+
+    let policy: SecurityPolicy = res
+        .request()
+        .extensions()
+        .get::<SecurityPolicy>()
+        .cloned()
+        .unwrap_or_else(SecurityPolicy::from_config);
+
+    let headers: &mut HeaderMap = res.headers_mut();
+
+    set_header_if_missing(
+        headers, header::CONTENT_SECURITY_POLICY, &policy.content_security_policy(&nonce));
+
+    if let Some(x_frame_options) = policy.x_frame_options() {
+        set_header_if_missing(headers, header::X_FRAME_OPTIONS, x_frame_options);
+    }
+
+    set_header_if_missing(headers, header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    set_header_if_missing(headers, header::REFERRER_POLICY, &config::referrer_policy());
+    set_header_if_missing(
+        headers, HeaderName::from_static("permissions-policy"), &config::permissions_policy());
+
+    // Browsers ignore HSTS over plain http, so this is harmless locally
+    if config::hsts_max_age() > 0 {
+        set_header_if_missing(
+            headers,
+            header::STRICT_TRANSPORT_SECURITY,
+            &format!("max-age={}; includeSubDomains", config::hsts_max_age()));
+    }
+
+    Ok(res)
+}
+
+
+// The route's own header wins. A value that isn't a valid header is logged and skipped.
+fn set_header_if_missing(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if headers.contains_key(&name) {
+        return;
+    }
+
+    match HeaderValue::from_str(value) {
+        Ok(header_value) => { headers.insert(name, header_value); },
+        Err(e) => eprintln!("Invalid {name} header value: {e}")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{ App, HttpResponse, Responder, get, test, middleware::from_fn };
+
+    // Stands in for a page template: shows the nonce it was handed
+    #[get("/page")]
+    async fn page(req: HttpRequest) -> impl Responder {
+        let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+        HttpResponse::Ok().body(user_req_data.csp_nonce)
+    }
+
+    #[get("/framed")]
+    async fn framed(req: HttpRequest) -> impl Responder {
+        override_security_policy(&req, SecurityPolicy::framed_by("https://game.example.com"));
+        HttpResponse::Ok().finish()
+    }
+
+    // Wrapped as in main.rs. Guests never touch the DB, so a lazy pool will do.
+    fn app() -> App<impl actix_web::dev::ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = ()
+    >> {
+        let pool: MySqlPool = MySqlPool::connect_lazy("mysql://root@127.0.0.1:1/none").unwrap();
+        App::new()
+            .app_data(web::Data::new(pool))
+            .wrap(from_fn(login_status_middleware))
+            .service(page)
+            .service(framed)
+            .wrap(from_fn(security_headers_middleware))
+    }
+
+    fn header_value(res: &ServiceResponse<impl MessageBody>, name: HeaderName) -> Option<String> {
+        res.headers().get(name).map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[actix_web::test]
+    async fn templates_get_the_nonce_the_csp_allows() {
+        let app = test::init_service(app()).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/page").to_request()).await;
+        let csp: String = header_value(&res, header::CONTENT_SECURITY_POLICY).unwrap();
+        let nonce: String = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+        assert_eq!(nonce.len(), 24);
+        assert!(csp.contains(&format!("script-src 'self' 'nonce-{nonce}'")));
+    }
+
+    #[actix_web::test]
+    async fn every_response_gets_its_own_nonce() {
+        let app = test::init_service(app()).await;
+
+        let first = test::call_and_read_body(&app, test::TestRequest::get().uri("/page").to_request()).await;
+        let second = test::call_and_read_body(&app, test::TestRequest::get().uri("/page").to_request()).await;
+
+        assert_ne!(first, second);
+    }
+
+    #[actix_web::test]
+    async fn default_policy_forbids_framing() {
+        let app = test::init_service(app()).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/page").to_request()).await;
+
+        assert!(header_value(&res, header::CONTENT_SECURITY_POLICY).unwrap()
+            .ends_with(&format!("frame-ancestors {}", config::frame_ancestors())));
+        assert!(header_value(&res, header::X_FRAME_OPTIONS).is_some());
+    }
+
+    #[actix_web::test]
+    async fn route_override_lets_its_origin_frame_the_page() {
+        let app = test::init_service(app()).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/framed").to_request()).await;
+
+        assert!(header_value(&res, header::CONTENT_SECURITY_POLICY).unwrap()
+            .ends_with("frame-ancestors https://game.example.com"));
+        // X-Frame-Options can't name an origin, so it would block the frame
+        assert_eq!(header_value(&res, header::X_FRAME_OPTIONS), None);
+    }
+}
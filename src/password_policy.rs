@@ -0,0 +1,267 @@
+/*
+ *
+ *
+ *
+ *
+ * =============================
+ * =============================
+ * =====                   =====
+ * =====  PASSWORD POLICY  =====
+ * =====                   =====
+ * =============================
+ * =============================
+ *
+ *
+ * Rules for NEW passwords (registration and password changes).
+ * Logging in never checks the policy, so old passwords keep working.
+ *
+ * -- any Unicode is allowed, so passphrases and password-manager
+ * -- output are fine. Passwords are NFKC-normalised before they are
+ * -- hashed or verified, so the same passphrase typed on two different
+ * -- keyboards gives the same hash.
+ * -- length is counted in characters (not bytes) after normalising.
+ * -- strength is estimated with zxcvbn (score 0 to 4).
+ * -- a local list of breached passwords (one per line) is checked.
+ *
+ *
+*/
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+use zxcvbn::zxcvbn;
+
+use crate::{
+    config,
+    resources::get_translation,
+    utils::SupportedLangs
+};
+
+
+/**
+ * Everything that can be wrong with a new password.
+ * Each one becomes a translated, field-level message for the forms.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordProblem {
+    TooShort(usize),
+    TooLong(usize),
+    ControlCharacters,
+    Breached,
+    TooWeak,
+}
+
+
+impl PasswordProblem {
+    pub fn message(&self, lang: &SupportedLangs) -> String {
+        match self {
+            PasswordProblem::TooShort(min) => {
+                get_translation("pw.too_short", lang, Some(&[&min.to_string()]))
+            },
+            PasswordProblem::TooLong(max) => {
+                get_translation("pw.too_long", lang, Some(&[&max.to_string()]))
+            },
+            PasswordProblem::ControlCharacters => get_translation("pw.control_chars", lang, None),
+            PasswordProblem::Breached => get_translation("pw.breached", lang, None),
+            PasswordProblem::TooWeak => get_translation("pw.too_weak", lang, None),
+        }
+    }
+}
+
+
+/*
+ * POLICY SETTINGS (from env variables)
+ */
+
+pub fn min_length() -> usize {
+    config::env_or("PASSWORD_MIN_LENGTH", 8)
+}
+
+pub fn max_length() -> usize {
+    config::env_or("PASSWORD_MAX_LENGTH", 256)
+}
+
+// zxcvbn score from 0 (terrible) to 4 (great)
+pub fn min_strength() -> u8 {
+    config::env_or("PASSWORD_MIN_STRENGTH", 2)
+}
+
+
+/*
+ * BREACHED PASSWORDS LIST
+ */
+
+static BREACHED_PASSWORDS: OnceLock<HashSet<String>> = OnceLock::new();
+
+/**
+ * Load the breached password list (if there is one) into memory.
+ * Called once when the server starts. The file is plain text with one
+ * password per line. Entries are normalised and lower-cased, so the
+ * check also catches simple case changes.
+ * @return number of passwords loaded
+ */
+pub fn load_breached_passwords() -> usize {
+    let passwords: HashSet<String> = match std::env::var("BREACHED_PASSWORDS_FILE") {
+        Ok(path) => {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => {
+                    contents.lines()
+                        .map(|line| normalize(line.trim_end_matches('\r')).to_lowercase())
+                        .filter(|line| !line.is_empty())
+                        .collect()
+                },
+                Err(e) => {
+                    eprintln!("Could not read breached passwords file {path}: {e}");
+                    HashSet::new()
+                }
+            }
+        },
+        Err(_e) => HashSet::new()
+    };
+
+    let count: usize = passwords.len();
+    let _ = BREACHED_PASSWORDS.set(passwords);
+    count
+}
+
+fn is_breached(normalized_password: &str) -> bool {
+    match BREACHED_PASSWORDS.get() {
+        Some(passwords) => passwords.contains(&normalized_password.to_lowercase()),
+        None => false
+    }
+}
+
+
+/*
+ * CHECKS
+ */
+
+/**
+ * NFKC normalisation. Use this on every password before hashing
+ * or verifying it.
+ */
+pub fn normalize(password: &str) -> String {
+    password.nfkc().collect()
+}
+
+
+/**
+ * Check a NEW password against the policy.
+ * user_inputs are things like the username and email, which make a
+ * password weak if it contains them.
+ * An empty list means the password is acceptable.
+ */
+pub fn check_password(password: &str, user_inputs: &[&str]) -> Vec<PasswordProblem> {
+    let mut problems: Vec<PasswordProblem> = Vec::new();
+    let normalized: String = normalize(password);
+    let length: usize = normalized.chars().count();
+
+    if length < min_length() {
+        problems.push(PasswordProblem::TooShort(min_length()));
+    }
+
+    if length > max_length() {
+        // Don't bother estimating the strength of a novel
+        problems.push(PasswordProblem::TooLong(max_length()));
+        return problems;
+    }
+
+    if normalized.chars().any(|c| c.is_control()) {
+        problems.push(PasswordProblem::ControlCharacters);
+    }
+
+    if is_breached(&normalized) {
+        problems.push(PasswordProblem::Breached);
+    } else if !normalized.is_empty() {
+        let strength: u8 = zxcvbn(&normalized, user_inputs).score() as u8;
+        if strength < min_strength() {
+            problems.push(PasswordProblem::TooWeak);
+        }
+    }
+
+    problems
+}
+
+
+/**
+ * Translate a list of problems for the front end.
+ */
+pub fn problem_messages(problems: &[PasswordProblem], lang: &SupportedLangs) -> Vec<String> {
+    problems.iter()
+        .map(|problem| problem.message(lang))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A breached entry that zxcvbn would otherwise be happy with
+    fn with_breached_list() {
+        BREACHED_PASSWORDS.get_or_init(|| {
+            HashSet::from([normalize("Vq7#mZt2!pLw9xR").to_lowercase()])
+        });
+    }
+
+    #[test]
+    fn strong_password_passes() {
+        assert!(check_password("blue Tractor sings 42 lullabies", &[]).is_empty());
+    }
+
+    #[test]
+    fn short_password_is_too_short() {
+        assert!(check_password("x9!Qz", &[]).contains(&PasswordProblem::TooShort(8)));
+    }
+
+    #[test]
+    fn length_counts_characters_not_bytes() {
+        // 7 characters, 14 bytes
+        assert!(check_password("ééééééé", &[]).contains(&PasswordProblem::TooShort(8)));
+        assert!(!check_password("日本語のとても長い合言葉です", &[]).contains(&PasswordProblem::TooShort(8)));
+    }
+
+    #[test]
+    fn long_password_stops_at_too_long() {
+        let novel: String = "a".repeat(300);
+        assert_eq!(check_password(&novel, &[]), vec![PasswordProblem::TooLong(256)]);
+    }
+
+    #[test]
+    fn control_characters_are_refused() {
+        let problems: Vec<PasswordProblem> = check_password("blue Tractor\u{7}sings 42 lullabies", &[]);
+        assert!(problems.contains(&PasswordProblem::ControlCharacters));
+    }
+
+    #[test]
+    fn common_password_is_too_weak() {
+        assert!(check_password("password1", &[]).contains(&PasswordProblem::TooWeak));
+    }
+
+    #[test]
+    fn user_inputs_make_a_password_weaker() {
+        assert!(check_password("pattmayne", &["pattmayne"]).contains(&PasswordProblem::TooWeak));
+    }
+
+    #[test]
+    fn breached_password_is_refused_in_any_case() {
+        with_breached_list();
+        assert_eq!(check_password("vQ7#Mzt2!PlW9Xr", &[]), vec![PasswordProblem::Breached]);
+    }
+
+    #[test]
+    fn normalize_uses_nfkc() {
+        assert_eq!(normalize("ｐａｓｓ"), "pass");
+        assert_eq!(normalize("ﬁsh"), "fish");
+        assert_eq!(normalize("e\u{301}"), "é");
+    }
+
+    #[test]
+    fn problems_are_translated() {
+        let messages: Vec<String> = problem_messages(
+            &[PasswordProblem::TooShort(8), PasswordProblem::TooWeak],
+            &SupportedLangs::English);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains('8'));
+    }
+}
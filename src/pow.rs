@@ -0,0 +1,416 @@
+/*
+ *
+ *
+ *
+ *
+ * ===========================
+ * ===========================
+ * =====                 =====
+ * =====  PROOF OF WORK  =====
+ * =====                 =====
+ * ===========================
+ * ===========================
+ *
+ *
+ * A hashcash-style puzzle to slow down bots, with no third-party service.
+ *
+ * The browser gets a challenge token from GET /auth/challenge, then looks for
+ * a nonce where SHA-256("{token}:{nonce}") starts with at least
+ * `difficulty` zero bits, and sends both back with the form.
+ *
+ * The token is signed (HMAC-SHA256) and carries its own expiry, difficulty
+ * and the IP address it was issued to, so nothing is stored when it's handed out.
+ * Once it's used, its signature goes in used_challenges until it expires:
+ * each solved challenge only works once.
+ *
+ * Required on every registration, and on logins after a burst of failures
+ * (see login_needs_challenge).
+ *
+ *
+*/
+
+use hkdf::Hkdf;
+use hmac::{ Hmac, Mac };
+use serde::Serialize;
+use sha2::{ Digest, Sha256 };
+use sqlx::MySqlPool;
+use time::{ Duration, OffsetDateTime };
+use anyhow::anyhow;
+
+use crate::{ auth, config, db, utils, audit::AuditEvent };
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+
+// What GET /auth/challenge sends to the browser
+#[derive(Serialize)]
+pub struct Challenge {
+    pub token: String,
+    pub difficulty: u32,
+}
+
+
+// A solved challenge that checked out, before it's marked as used
+struct SolvedChallenge {
+    id: String, // the signature: unique to this challenge and IP
+    expires: OffsetDateTime,
+}
+
+
+/**
+ * Challenges are signed with POW_SECRET if it's set. Otherwise with a key
+ * derived (HKDF-SHA256) from the JWT secret, so a challenge signature
+ * is never something that could pass as a JWT signature.
+ */
+fn challenge_key() -> Result<Vec<u8>, anyhow::Error> {
+    if let Ok(secret) = std::env::var("POW_SECRET") {
+        return Ok(secret.into_bytes());
+    }
+
+    let jwt_secret: String = auth::get_jwt_secret()
+        .map_err(|_e| anyhow!("No POW_SECRET or JWT_SECRET to sign challenges with"))?;
+
+    let mut key: Vec<u8> = vec![0u8; 32];
+    Hkdf::<Sha256>::new(None, jwt_secret.as_bytes())
+        .expand(b"proof-of-work challenges", &mut key)
+        .map_err(|e| anyhow!("Could not derive the challenge key: {e}"))?;
+
+    Ok(key)
+}
+
+
+fn sign(key: &[u8], payload: &str) -> Result<String, anyhow::Error> {
+    let mut mac: HmacSha256 = HmacSha256::new_from_slice(key)
+        .map_err(|e| anyhow!("Could not start HMAC: {e}"))?;
+    mac.update(payload.as_bytes());
+
+    Ok(to_hex(&mac.finalize().into_bytes()))
+}
+
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+
+/**
+ * A new challenge for this IP address, at this deployment's difficulty.
+ * Token: "{expires}.{difficulty}.{salt}.{signature}"
+ * The IP is signed but not included, so the token only works from there.
+ */
+pub fn issue_challenge(ip_address: &String) -> Result<Challenge, anyhow::Error> {
+    let expires: OffsetDateTime =
+        OffsetDateTime::now_utc() + Duration::minutes(config::pow_challenge_minutes());
+
+    make_challenge(&challenge_key()?, ip_address, config::pow_difficulty(), expires)
+}
+
+
+fn make_challenge(
+    key: &[u8],
+    ip_address: &String,
+    difficulty: u32,
+    expires: OffsetDateTime
+) -> Result<Challenge, anyhow::Error> {
+    let expires: i64 = expires.unix_timestamp();
+    let salt: String = auth::generate_auth_code();
+
+    let unsigned: String = format!("{expires}.{difficulty}.{salt}");
+    let signature: String = sign(key, &format!("{unsigned}.{ip_address}"))?;
+
+    Ok(Challenge {
+        token: format!("{unsigned}.{signature}"),
+        difficulty,
+    })
+}
+
+
+/**
+ * Is this a real challenge (ours, unexpired, issued to this IP, hard enough),
+ * does the nonce solve it, and has it not been used before?
+ * A good one is used up by this check.
+ */
+pub async fn verify_solution(
+    pool: &MySqlPool,
+    token: &Option<String>,
+    nonce: &Option<String>,
+    ip_address: &String
+) -> bool {
+    let key: Vec<u8> = match challenge_key() {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Could not verify challenge: {e}");
+            return false;
+        }
+    };
+
+    let solved: SolvedChallenge = match check_solution(
+        &key, token, nonce, ip_address, config::pow_difficulty()) {
+        Some(solved) => solved,
+        None => return false
+    };
+
+    match db::use_challenge(pool, &solved.id, solved.expires).await {
+        Ok(first_use) => first_use,
+        Err(e) => {
+            eprintln!("Could not record used challenge: {e}");
+            false
+        }
+    }
+}
+
+
+fn check_solution(
+    key: &[u8],
+    token: &Option<String>,
+    nonce: &Option<String>,
+    ip_address: &String,
+    min_difficulty: u32
+) -> Option<SolvedChallenge> {
+    let (token, nonce) = match (token, nonce) {
+        (Some(token), Some(nonce)) if nonce.len() <= 32 => (token, nonce),
+        _ => return None
+    };
+
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 4 {
+        return None;
+    }
+
+    let expires: OffsetDateTime = parts[0].parse::<i64>().ok()
+        .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())?;
+    let difficulty: u32 = parts[1].parse::<u32>().ok()?;
+
+    // Raising POW_DIFFICULTY retires the easier challenges already handed out
+    if expires < OffsetDateTime::now_utc() || difficulty < min_difficulty {
+        return None;
+    }
+
+    let expected_signature: String = match sign(
+        key, &format!("{}.{}.{}.{}", parts[0], parts[1], parts[2], ip_address)) {
+        Ok(signature) => signature,
+        Err(e) => {
+            eprintln!("Could not verify challenge: {e}");
+            return None;
+        }
+    };
+
+    if !utils::constant_time_eq(expected_signature.as_bytes(), parts[3].as_bytes()) {
+        return None;
+    }
+
+    let hash = Sha256::digest(format!("{token}:{nonce}").as_bytes());
+    if leading_zero_bits(&hash) < difficulty {
+        return None;
+    }
+
+    Some(SolvedChallenge { id: expected_signature, expires })
+}
+
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits: u32 = 0;
+
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+
+    bits
+}
+
+
+/**
+ * After POW_LOGIN_FAILURES failed logins (for this account, or from this IP)
+ * within POW_FAILURE_MINUTES, every login attempt needs a solved challenge.
+ * With enumeration resistance on, only the IP counts: failures against an
+ * account can only be counted when it exists, so a challenge for one name
+ * and none for another would tell them apart.
+ * DB errors count as "needs a challenge": it's only a short delay for a human.
+ */
+pub async fn login_needs_challenge(
+    pool: &MySqlPool,
+    user_id: Option<i32>,
+    ip_address: &String
+) -> bool {
+    let since: OffsetDateTime =
+        OffsetDateTime::now_utc() - Duration::minutes(config::pow_failure_minutes());
+    let limit: i64 = config::pow_login_failures();
+    let event_type: &str = AuditEvent::LoginFailure.as_str();
+
+    match db::count_audit_events_from_ip_since(pool, ip_address, event_type, since).await {
+        Ok(failures) if failures >= limit => return true,
+        Ok(_) => {},
+        Err(e) => {
+            eprintln!("Could not count failed logins: {e}");
+            return true;
+        }
+    }
+
+    if let Some(id) = user_id && !config::enumeration_resistance() {
+        return match db::count_audit_events_since(pool, id, event_type, since).await {
+            Ok(failures) => failures >= limit,
+            Err(e) => {
+                eprintln!("Could not count failed logins: {e}");
+                true
+            }
+        };
+    }
+
+    false
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test challenge key";
+    const DIFFICULTY: u32 = 8;
+
+    fn ip() -> String {
+        String::from("203.0.113.7")
+    }
+
+    fn challenge_expiring_in(minutes: i64) -> Challenge {
+        let expires: OffsetDateTime = OffsetDateTime::now_utc() + Duration::minutes(minutes);
+        make_challenge(KEY, &ip(), DIFFICULTY, expires).unwrap()
+    }
+
+    // What the browser does (see solve_challenge in utils.js)
+    fn solve(challenge: &Challenge) -> String {
+        (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}:{nonce}", challenge.token).as_bytes());
+                leading_zero_bits(&hash) >= challenge.difficulty
+            })
+            .unwrap()
+    }
+
+    fn check(token: &str, nonce: &str, ip_address: &String) -> bool {
+        check_solution(
+            KEY,
+            &Some(token.to_string()),
+            &Some(nonce.to_string()),
+            ip_address,
+            DIFFICULTY
+        ).is_some()
+    }
+
+    #[test]
+    fn solved_challenge_verifies() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        assert_eq!(challenge.difficulty, DIFFICULTY);
+        assert!(check(&challenge.token, &solve(&challenge), &ip()));
+    }
+
+    #[test]
+    fn wrong_nonce_fails() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let wrong_nonce: String = (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}:{nonce}", challenge.token).as_bytes());
+                leading_zero_bits(&hash) < DIFFICULTY
+            })
+            .unwrap();
+
+        assert!(!check(&challenge.token, &wrong_nonce, &ip()));
+    }
+
+    #[test]
+    fn missing_or_huge_nonce_fails() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let token: Option<String> = Some(challenge.token);
+
+        assert!(check_solution(KEY, &token, &None, &ip(), DIFFICULTY).is_none());
+        assert!(check_solution(KEY, &None, &Some(String::from("1")), &ip(), DIFFICULTY).is_none());
+        assert!(check_solution(KEY, &token, &Some("1".repeat(33)), &ip(), DIFFICULTY).is_none());
+    }
+
+    #[test]
+    fn expired_challenge_fails() {
+        let challenge: Challenge = challenge_expiring_in(-1);
+        assert!(!check(&challenge.token, &solve(&challenge), &ip()));
+    }
+
+    #[test]
+    fn challenge_only_works_from_its_ip() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        assert!(!check(&challenge.token, &solve(&challenge), &String::from("198.51.100.1")));
+    }
+
+    #[test]
+    fn tampered_signature_fails() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let nonce: String = solve(&challenge);
+
+        let mut tampered: String = challenge.token.to_owned();
+        let last: char = tampered.pop().unwrap();
+        tampered.push(if last == '0' { '1' } else { '0' });
+
+        assert!(!check(&tampered, &nonce, &ip()));
+    }
+
+    #[test]
+    fn lowered_difficulty_fails() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let parts: Vec<&str> = challenge.token.split('.').collect();
+        let easier: String = format!("{}.0.{}.{}", parts[0], parts[2], parts[3]);
+
+        assert!(!check(&easier, "0", &ip()));
+    }
+
+    #[test]
+    fn other_key_fails() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let solved: Option<SolvedChallenge> = check_solution(
+            b"some other key",
+            &Some(challenge.token.to_owned()),
+            &Some(solve(&challenge)),
+            &ip(),
+            DIFFICULTY);
+
+        assert!(solved.is_none());
+    }
+
+    #[test]
+    fn raised_difficulty_retires_old_challenges() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let solved: Option<SolvedChallenge> = check_solution(
+            KEY,
+            &Some(challenge.token.to_owned()),
+            &Some(solve(&challenge)),
+            &ip(),
+            DIFFICULTY + 1);
+
+        assert!(solved.is_none());
+    }
+
+    #[test]
+    fn solved_challenge_is_identified_by_its_signature() {
+        let challenge: Challenge = challenge_expiring_in(5);
+        let solved: SolvedChallenge = check_solution(
+            KEY,
+            &Some(challenge.token.to_owned()),
+            &Some(solve(&challenge)),
+            &ip(),
+            DIFFICULTY).unwrap();
+
+        assert!(challenge.token.ends_with(&format!(".{}", solved.id)));
+        assert_eq!(solved.id.len(), 64);
+    }
+
+    #[test]
+    fn counts_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x01]), 15);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x80]), 16);
+    }
+}
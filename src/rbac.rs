@@ -0,0 +1,80 @@
+/*
+ *
+ *
+ *
+ *
+ * ==================
+ * ==================
+ * =====        =====
+ * =====  RBAC  =====
+ * =====        =====
+ * ==================
+ * ==================
+ *
+ *
+ * Role-based access control.
+ * Roles and what they're allowed to do live in the DB
+ * (roles, permissions, role_permissions, user_roles).
+ * A user's permissions come from their base role (users.role)
+ * plus any extra roles in user_roles.
+ *
+ * login_status_middleware looks them up on every request (not in the JWT),
+ * so taking a role away works at once.
+ * Routes check them with routes_utils::require_permission.
+ *
+ * Every name here must exist in the permissions table (see the migrations).
+ *
+ *
+*/
+
+
+#[derive(Clone, Copy)]
+pub enum Permission {
+    AdminAccess,
+    ClientsRead,
+    ClientsWrite,
+    ClientsReview,
+    UsersRead,
+    UsersWrite,
+    UsersSuspend,
+    UsersImpersonate,
+    AuditRead,
+    SystemRead,
+    DeveloperAccess,
+}
+
+
+impl Permission {
+    // What's stored in permissions.name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::AdminAccess => "admin.access",
+            Permission::ClientsRead => "clients.read",
+            Permission::ClientsWrite => "clients.write",
+            Permission::ClientsReview => "clients.review",
+            Permission::UsersRead => "users.read",
+            Permission::UsersWrite => "users.write",
+            Permission::UsersSuspend => "users.suspend",
+            Permission::UsersImpersonate => "users.impersonate",
+            Permission::AuditRead => "audit.read",
+            Permission::SystemRead => "system.read",
+            Permission::DeveloperAccess => "developer.access",
+        }
+    }
+
+    pub fn all() -> Vec<Permission> {
+        vec![
+            Permission::AdminAccess,
+            Permission::ClientsRead,
+            Permission::ClientsWrite,
+            Permission::ClientsReview,
+            Permission::UsersRead,
+            Permission::UsersWrite,
+            Permission::UsersSuspend,
+            Permission::UsersImpersonate,
+            Permission::AuditRead,
+            Permission::SystemRead,
+            Permission::DeveloperAccess,
+        ]
+    }
+}
@@ -24,6 +24,7 @@
 
 
 use crate::{
+    config,
     auth::UserReqData,
     resources::{ get_translation, raw_trans_or_missing, TRANSLATIONS },
     utils::SupportedLangs
@@ -69,7 +70,7 @@ impl HomeTexts {
         let message: String = get_translation(
             "home.greeting",
             lang,
-            Some(&[&user_req_data.get_role()]));
+            Some(&[user_req_data.get_role()]));
         let nav = NavTexts::new(lang);
 
         HomeTexts {
@@ -166,9 +167,27 @@ pub struct AdminTexts {
     pub actions_label: String,
     pub new_client_btn: String,
     pub edit_clients_label: String,
+    pub audit_btn: String,
+    pub suspensions_btn: String,
+    pub users_btn: String,
+    pub reviews_btn: String,
+    pub reg_tokens_btn: String,
+    pub edit_client_btn: String,
+    pub archive_btn: String,
+    pub restore_btn: String,
+    pub delete_btn: String,
+    pub archived_label: String,
+    pub confirm_archive: String,
+    pub confirm_restore: String,
+    pub confirm_delete: String,
+    pub confirm_btn: String,
+    pub cancel_btn: String,
+    pub users: AdminUsersTexts,
+    pub step_up: StepUpTexts,
     pub nav: NavTexts
 }
 
+
 impl AdminTexts {
     pub fn new(user_req_data: &UserReqData) -> AdminTexts {
         let lang: &SupportedLangs = &user_req_data.lang;
@@ -177,6 +196,23 @@ impl AdminTexts {
         let actions_label: String = get_translation("admin.actions.label", lang,None);
         let new_client_btn: String = get_translation("admin.newclient.btn", lang,None);
         let edit_clients_label: String = get_translation("admin.editclients.label", lang,None);
+        let audit_btn: String = get_translation("admin.audit.btn", lang, None);
+        let suspensions_btn: String = get_translation("admin.suspensions.btn", lang, None);
+        let users_btn: String = get_translation("admin.users.btn", lang, None);
+        let reviews_btn: String = get_translation("admin.reviews.btn", lang, None);
+        let reg_tokens_btn: String = get_translation("admin.reg_tokens.btn", lang, None);
+        let edit_client_btn: String = get_translation("admin.clients.edit", lang, None);
+        let archive_btn: String = get_translation("admin.clients.archive", lang, None);
+        let restore_btn: String = get_translation("admin.clients.restore", lang, None);
+        let delete_btn: String = get_translation("admin.clients.delete", lang, None);
+        let archived_label: String = get_translation("admin.clients.archived", lang, None);
+        let confirm_archive: String = get_translation("admin.clients.confirm_archive", lang, None);
+        let confirm_restore: String = get_translation("admin.clients.confirm_restore", lang, None);
+        let confirm_delete: String = get_translation("admin.clients.confirm_delete", lang, None);
+        let confirm_btn: String = get_translation("admin.clients.confirm", lang, None);
+        let cancel_btn: String = get_translation("admin.clients.cancel", lang, None);
+        let users: AdminUsersTexts = AdminUsersTexts::new(lang);
+        let step_up: StepUpTexts = StepUpTexts::new(lang);
         let nav = NavTexts::new(lang);
 
         AdminTexts {
@@ -186,6 +222,481 @@ impl AdminTexts {
             actions_label,
             new_client_btn,
             edit_clients_label,
+            audit_btn,
+            suspensions_btn,
+            users_btn,
+            reviews_btn,
+            reg_tokens_btn,
+            edit_client_btn,
+            archive_btn,
+            restore_btn,
+            delete_btn,
+            archived_label,
+            confirm_archive,
+            confirm_restore,
+            confirm_delete,
+            confirm_btn,
+            cancel_btn,
+            users,
+            step_up,
+        }
+    }
+}
+
+
+/**
+ * The "confirm your password" modal (templates/step_up_modal.html)
+ * on pages with sensitive admin actions.
+ * Part of AdminTexts, NewClientTexts, EditClientTexts,
+ * DeveloperTexts, ClientReviewTexts and RegistrationTokenTexts.
+ */
+pub struct StepUpTexts {
+    pub title: String,
+    pub message: String,
+    pub password: String,
+    pub confirm_btn: String,
+    pub cancel_btn: String,
+}
+
+impl StepUpTexts {
+    pub fn new(lang: &SupportedLangs) -> StepUpTexts {
+        StepUpTexts {
+            title: get_translation("step_up.title", lang, None),
+            message: get_translation("step_up.message", lang, None),
+            password: get_translation("step_up.password", lang, None),
+            confirm_btn: get_translation("step_up.confirm", lang, None),
+            cancel_btn: get_translation("step_up.cancel", lang, None),
+        }
+    }
+}
+
+
+/**
+ * The users console: "/admin/users" and "/admin/users/{user_id}".
+ * Part of AdminTexts.
+ */
+pub struct AdminUsersTexts {
+    pub title: String,
+    pub message: String,
+    pub search_label: String,
+    pub search_btn: String,
+    pub username_col: String,
+    pub email_col: String,
+    pub role_col: String,
+    pub verified_col: String,
+    pub created_col: String,
+    pub no_users_msg: String,
+    pub yes: String,
+    pub no: String,
+    pub prev_btn: String,
+    pub next_btn: String,
+    pub profile_title: String,
+    pub first_name_label: String,
+    pub last_name_label: String,
+    pub save_names_btn: String,
+    pub save_role_btn: String,
+    pub extra_roles_label: String,
+    pub add_role_btn: String,
+    pub remove_role_btn: String,
+    pub verify_email_btn: String,
+    pub force_reset_btn: String,
+    pub force_reset_done: String,
+    pub impersonate_btn: String,
+    pub impersonate_note: String,
+    pub sessions_title: String,
+    pub revoke_btn: String,
+    pub revoke_all_btn: String,
+    pub no_sessions_msg: String,
+    pub history_title: String,
+}
+
+impl AdminUsersTexts {
+    pub fn new(lang: &SupportedLangs) -> AdminUsersTexts {
+        AdminUsersTexts {
+            title: get_translation("admin.users.title", lang, None),
+            message: get_translation("admin.users.message", lang, None),
+            search_label: get_translation("admin.users.search", lang, None),
+            search_btn: get_translation("admin.users.search.btn", lang, None),
+            username_col: get_translation("admin.users.col.username", lang, None),
+            email_col: get_translation("admin.users.col.email", lang, None),
+            role_col: get_translation("admin.users.col.role", lang, None),
+            verified_col: get_translation("admin.users.col.verified", lang, None),
+            created_col: get_translation("admin.users.col.created", lang, None),
+            no_users_msg: get_translation("admin.users.none", lang, None),
+            yes: get_translation("admin.users.yes", lang, None),
+            no: get_translation("admin.users.no", lang, None),
+            prev_btn: get_translation("admin.users.prev", lang, None),
+            next_btn: get_translation("admin.users.next", lang, None),
+            profile_title: get_translation("admin.user.profile", lang, None),
+            first_name_label: get_translation("admin.user.first_name", lang, None),
+            last_name_label: get_translation("admin.user.last_name", lang, None),
+            save_names_btn: get_translation("admin.user.save_names", lang, None),
+            save_role_btn: get_translation("admin.user.save_role", lang, None),
+            extra_roles_label: get_translation("admin.user.extra_roles", lang, None),
+            add_role_btn: get_translation("admin.user.add_role", lang, None),
+            remove_role_btn: get_translation("admin.user.remove_role", lang, None),
+            verify_email_btn: get_translation("admin.user.verify_email", lang, None),
+            force_reset_btn: get_translation("admin.user.force_reset", lang, None),
+            force_reset_done: get_translation("admin.user.force_reset.done", lang, None),
+            impersonate_btn: get_translation("admin.user.impersonate", lang, None),
+            impersonate_note: get_translation(
+                "admin.user.impersonate.note",
+                lang,
+                Some(&[&config::impersonation_minutes().to_string()])),
+            sessions_title: get_translation("admin.user.sessions", lang, None),
+            revoke_btn: get_translation("admin.user.revoke", lang, None),
+            revoke_all_btn: get_translation("admin.user.revoke_all", lang, None),
+            no_sessions_msg: get_translation("admin.user.no_sessions", lang, None),
+            history_title: get_translation("admin.user.history", lang, None),
+        }
+    }
+}
+
+
+/**
+ * route: get "/admin/audit"
+ */
+pub struct AuditTexts {
+    pub title: String,
+    pub message: String,
+    pub event_filter_label: String,
+    pub any_event: String,
+    pub username_filter_label: String,
+    pub client_filter_label: String,
+    pub ip_filter_label: String,
+    pub filter_btn: String,
+    pub time_col: String,
+    pub event_col: String,
+    pub user_col: String,
+    pub actor_col: String,
+    pub client_col: String,
+    pub ip_col: String,
+    pub device_col: String,
+    pub details_col: String,
+    pub no_events_msg: String,
+    pub newer_btn: String,
+    pub older_btn: String,
+    pub nav: NavTexts
+}
+
+impl AuditTexts {
+    pub fn new(user_req_data: &UserReqData) -> AuditTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        AuditTexts {
+            title: get_translation("audit.title", lang, None),
+            message: get_translation("audit.message", lang, None),
+            event_filter_label: get_translation("audit.filter.event", lang, None),
+            any_event: get_translation("audit.filter.any", lang, None),
+            username_filter_label: get_translation("audit.filter.username", lang, None),
+            client_filter_label: get_translation("audit.filter.client", lang, None),
+            ip_filter_label: get_translation("audit.filter.ip", lang, None),
+            filter_btn: get_translation("audit.filter.btn", lang, None),
+            time_col: get_translation("audit.col.time", lang, None),
+            event_col: get_translation("audit.col.event", lang, None),
+            user_col: get_translation("audit.col.user", lang, None),
+            actor_col: get_translation("audit.col.actor", lang, None),
+            client_col: get_translation("audit.col.client", lang, None),
+            ip_col: get_translation("audit.col.ip", lang, None),
+            device_col: get_translation("audit.col.device", lang, None),
+            details_col: get_translation("audit.col.details", lang, None),
+            no_events_msg: get_translation("audit.none", lang, None),
+            newer_btn: get_translation("audit.prev", lang, None),
+            older_btn: get_translation("audit.next", lang, None),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * route: get "/admin/client_reviews"
+ */
+pub struct ClientReviewTexts {
+    pub title: String,
+    pub message: String,
+    pub name_col: String,
+    pub owner_col: String,
+    pub domain_col: String,
+    pub redirect_col: String,
+    pub type_col: String,
+    pub submitted_col: String,
+    pub approve_btn: String,
+    pub reject_btn: String,
+    pub none_msg: String,
+    pub step_up: StepUpTexts,
+    pub nav: NavTexts
+}
+
+impl ClientReviewTexts {
+    pub fn new(user_req_data: &UserReqData) -> ClientReviewTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        ClientReviewTexts {
+            title: get_translation("reviews.title", lang, None),
+            message: get_translation("reviews.message", lang, None),
+            name_col: get_translation("reviews.col.name", lang, None),
+            owner_col: get_translation("reviews.col.owner", lang, None),
+            domain_col: get_translation("reviews.col.domain", lang, None),
+            redirect_col: get_translation("reviews.col.redirect", lang, None),
+            type_col: get_translation("reviews.col.type", lang, None),
+            submitted_col: get_translation("reviews.col.submitted", lang, None),
+            approve_btn: get_translation("reviews.approve", lang, None),
+            reject_btn: get_translation("reviews.reject", lang, None),
+            none_msg: get_translation("reviews.none", lang, None),
+            step_up: StepUpTexts::new(lang),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * route: get "/admin/registration_tokens"
+ */
+pub struct RegistrationTokenTexts {
+    pub title: String,
+    pub message: String,
+    pub label_label: String,
+    pub max_uses_label: String,
+    pub validity: String,
+    pub issue_btn: String,
+    pub issued_msg: String,
+    pub usable_title: String,
+    pub label_col: String,
+    pub issued_by_col: String,
+    pub uses_col: String,
+    pub created_col: String,
+    pub expires_col: String,
+    pub revoke_btn: String,
+    pub none_msg: String,
+    pub step_up: StepUpTexts,
+    pub nav: NavTexts
+}
+
+impl RegistrationTokenTexts {
+    pub fn new(user_req_data: &UserReqData) -> RegistrationTokenTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        RegistrationTokenTexts {
+            title: get_translation("reg_tokens.title", lang, None),
+            message: get_translation("reg_tokens.message", lang, None),
+            label_label: get_translation("reg_tokens.label", lang, None),
+            max_uses_label: get_translation("reg_tokens.max_uses", lang, None),
+            validity: get_translation(
+                "reg_tokens.validity",
+                lang,
+                Some(&[&config::initial_access_token_days().to_string()])),
+            issue_btn: get_translation("reg_tokens.issue", lang, None),
+            issued_msg: get_translation("reg_tokens.issued", lang, None),
+            usable_title: get_translation("reg_tokens.usable", lang, None),
+            label_col: get_translation("reg_tokens.col.label", lang, None),
+            issued_by_col: get_translation("reg_tokens.col.issued_by", lang, None),
+            uses_col: get_translation("reg_tokens.col.uses", lang, None),
+            created_col: get_translation("reg_tokens.col.created", lang, None),
+            expires_col: get_translation("reg_tokens.col.expires", lang, None),
+            revoke_btn: get_translation("reg_tokens.revoke", lang, None),
+            none_msg: get_translation("reg_tokens.none", lang, None),
+            step_up: StepUpTexts::new(lang),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * route: get "/developer"
+ * The client form labels are the admin's (clientform.*).
+ */
+pub struct DeveloperTexts {
+    pub title: String,
+    pub message: String,
+    pub register_title: String,
+    pub register_note: String,
+    pub mine_title: String,
+    pub none_msg: String,
+    pub domain: String,
+    pub name: String,
+    pub id: String,
+    pub red_uri: String,
+    pub logo_url: String,
+    pub cli_type: String,
+    pub cat: String,
+    pub desc: String,
+    pub submit_btn: String,
+    pub save_btn: String,
+    pub gen_secret_btn: String,
+    pub status_label: String,
+    pub status_pending: String,
+    pub status_rejected: String,
+    pub status_live: String,
+    pub status_inactive: String,
+    pub created_label: String,
+    pub sessions_label: String,
+    pub logins_label: String,
+    pub users_label: String,
+    pub review_note: String,
+    pub secret_msg: String,
+    pub step_up: StepUpTexts,
+    pub nav: NavTexts
+}
+
+impl DeveloperTexts {
+    pub fn new(user_req_data: &UserReqData) -> DeveloperTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+        let usage_days: String = config::developer_usage_days().to_string();
+
+        DeveloperTexts {
+            title: get_translation("developer.title", lang, None),
+            message: get_translation("developer.message", lang, None),
+            register_title: get_translation("developer.register", lang, None),
+            register_note: get_translation("developer.register.note", lang, None),
+            mine_title: get_translation("developer.mine", lang, None),
+            none_msg: get_translation("developer.none", lang, None),
+            domain: get_translation("clientform.domain", lang, None),
+            name: get_translation("clientform.name", lang, None),
+            id: get_translation("clientform.id", lang, None),
+            red_uri: get_translation("clientform.red_uri", lang, None),
+            logo_url: get_translation("clientform.logo_url", lang, None),
+            cli_type: get_translation("clientform.type", lang, None),
+            cat: get_translation("clientform.cat", lang, None),
+            desc: get_translation("clientform.desc", lang, None),
+            submit_btn: get_translation("clientform.submit", lang, None),
+            save_btn: get_translation("clientform.save_changes", lang, None),
+            gen_secret_btn: get_translation("clientform.gen_secret", lang, None),
+            status_label: get_translation("developer.status", lang, None),
+            status_pending: get_translation("developer.status.pending", lang, None),
+            status_rejected: get_translation("developer.status.rejected", lang, None),
+            status_live: get_translation("developer.status.live", lang, None),
+            status_inactive: get_translation("developer.status.inactive", lang, None),
+            created_label: get_translation("developer.created", lang, None),
+            sessions_label: get_translation("developer.sessions", lang, None),
+            logins_label: get_translation("developer.logins", lang, Some(&[&usage_days])),
+            users_label: get_translation("developer.users", lang, Some(&[&usage_days])),
+            review_note: get_translation("developer.review_note", lang, None),
+            secret_msg: get_translation("developer.secret", lang, None),
+            step_up: StepUpTexts::new(lang),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * route: get "/admin/suspensions"
+ */
+pub struct SuspensionTexts {
+    pub title: String,
+    pub message: String,
+    pub username_label: String,
+    pub reason_label: String,
+    pub days_label: String,
+    pub suspend_btn: String,
+    pub active_title: String,
+    pub user_col: String,
+    pub reason_col: String,
+    pub issued_by_col: String,
+    pub start_col: String,
+    pub end_col: String,
+    pub indefinite: String,
+    pub lift_btn: String,
+    pub none_msg: String,
+    pub nav: NavTexts
+}
+
+impl SuspensionTexts {
+    pub fn new(user_req_data: &UserReqData) -> SuspensionTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        SuspensionTexts {
+            title: get_translation("suspend.title", lang, None),
+            message: get_translation("suspend.message", lang, None),
+            username_label: get_translation("suspend.username", lang, None),
+            reason_label: get_translation("suspend.reason", lang, None),
+            days_label: get_translation("suspend.days", lang, None),
+            suspend_btn: get_translation("suspend.btn", lang, None),
+            active_title: get_translation("suspend.active", lang, None),
+            user_col: get_translation("suspend.col.user", lang, None),
+            reason_col: get_translation("suspend.col.reason", lang, None),
+            issued_by_col: get_translation("suspend.col.issued_by", lang, None),
+            start_col: get_translation("suspend.col.start", lang, None),
+            end_col: get_translation("suspend.col.end", lang, None),
+            indefinite: get_translation("suspend.indefinite", lang, None),
+            lift_btn: get_translation("suspend.lift", lang, None),
+            none_msg: get_translation("suspend.none", lang, None),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * route: get "/auth/suspended/{token}"
+ */
+pub struct SuspendedTexts {
+    pub title: String,
+    pub message: String,
+    pub reason_label: String,
+    pub since_label: String,
+    pub until_label: String,
+    pub indefinite: String,
+    pub contact_msg: String,
+    pub nav: NavTexts
+}
+
+impl SuspendedTexts {
+    pub fn new(user_req_data: &UserReqData) -> SuspendedTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        SuspendedTexts {
+            title: get_translation("suspended.title", lang, None),
+            message: get_translation("suspended.message", lang, None),
+            reason_label: get_translation("suspended.reason", lang, None),
+            since_label: get_translation("suspended.since", lang, None),
+            until_label: get_translation("suspended.until", lang, None),
+            indefinite: get_translation("suspended.indefinite", lang, None),
+            contact_msg: get_translation("suspended.contact", lang, None),
+            nav: NavTexts::new(lang),
+        }
+    }
+}
+
+
+/**
+ * routes: get "/auth/not_me/{token}" and get "/auth/reset_password/{token}"
+ */
+pub struct RecoveryTexts {
+    pub not_me_title: String,
+    pub not_me_message: String,
+    pub not_me_btn: String,
+    pub reset_title: String,
+    pub reset_message: String,
+    pub reset_btn: String,
+    pub reset_done: String,
+    pub mismatch: String,
+    pub password_label: String,
+    pub confirm_password_label: String,
+    pub login_btn: String,
+    pub nav: NavTexts
+}
+
+impl RecoveryTexts {
+    pub fn new(user_req_data: &UserReqData) -> RecoveryTexts {
+        let lang: &SupportedLangs = &user_req_data.lang;
+
+        RecoveryTexts {
+            not_me_title: get_translation("recover.not_me.title", lang, None),
+            not_me_message: get_translation("recover.not_me.message", lang, None),
+            not_me_btn: get_translation("recover.not_me.btn", lang, None),
+            reset_title: get_translation("recover.reset.title", lang, None),
+            reset_message: get_translation("recover.reset.message", lang, None),
+            reset_btn: get_translation("recover.reset.btn", lang, None),
+            reset_done: get_translation("recover.reset.done", lang, None),
+            mismatch: get_translation("recover.mismatch", lang, None),
+            password_label: get_translation("dash.password1", lang, None),
+            confirm_password_label: get_translation("dash.password2", lang, None),
+            login_btn: get_translation("recover.login_btn", lang, None),
+            nav: NavTexts::new(lang),
         }
     }
 }
@@ -207,6 +718,7 @@ pub struct NewClientTexts {
     pub desc: String,
     pub is_active: String,
     pub submit_btn: String,
+    pub step_up: StepUpTexts,
     pub nav: NavTexts,
 }
 
@@ -225,6 +737,7 @@ impl NewClientTexts {
         let desc: String = get_translation("clientform.desc", lang, None);
         let is_active: String = get_translation("clientform.isactive", lang, None);
         let submit_btn: String = get_translation("clientform.submit", lang, None);
+        let step_up: StepUpTexts = StepUpTexts::new(lang);
         let nav = NavTexts::new(lang);
 
         NewClientTexts {
@@ -240,6 +753,7 @@ impl NewClientTexts {
             desc,
             is_active,
             submit_btn,
+            step_up,
             nav
         }
     }
@@ -263,6 +777,29 @@ pub struct EditClientTexts {
     pub is_active: String,
     pub save_btn: String,
     pub new_scret_btn: String,
+    pub restrictions_title: String,
+    pub restrictions_message: String,
+    pub username_label: String,
+    pub reason_label: String,
+    pub days_label: String,
+    pub restrict_btn: String,
+    pub user_col: String,
+    pub reason_col: String,
+    pub issued_by_col: String,
+    pub start_col: String,
+    pub end_col: String,
+    pub indefinite: String,
+    pub by_client: String,
+    pub remove_btn: String,
+    pub no_restrictions_msg: String,
+    pub roles_title: String,
+    pub roles_message: String,
+    pub role_label: String,
+    pub since_col: String,
+    pub add_role_btn: String,
+    pub remove_role_btn: String,
+    pub no_roles_msg: String,
+    pub step_up: StepUpTexts,
     pub nav: NavTexts
 }
 
@@ -282,6 +819,29 @@ impl EditClientTexts {
         let is_active: String = get_translation("clientform.isactive", lang, None);
         let save_btn: String = get_translation("clientform.save_changes", lang, None);
         let new_scret_btn: String = get_translation("clientform.gen_secret", lang, None);
+        let restrictions_title: String = get_translation("restrict.title", lang, None);
+        let restrictions_message: String = get_translation("restrict.message", lang, None);
+        let username_label: String = get_translation("suspend.username", lang, None);
+        let reason_label: String = get_translation("suspend.reason", lang, None);
+        let days_label: String = get_translation("suspend.days", lang, None);
+        let restrict_btn: String = get_translation("restrict.btn", lang, None);
+        let user_col: String = get_translation("suspend.col.user", lang, None);
+        let reason_col: String = get_translation("suspend.col.reason", lang, None);
+        let issued_by_col: String = get_translation("suspend.col.issued_by", lang, None);
+        let start_col: String = get_translation("suspend.col.start", lang, None);
+        let end_col: String = get_translation("suspend.col.end", lang, None);
+        let indefinite: String = get_translation("suspend.indefinite", lang, None);
+        let by_client: String = get_translation("restrict.by_client", lang, None);
+        let remove_btn: String = get_translation("restrict.remove", lang, None);
+        let no_restrictions_msg: String = get_translation("restrict.none", lang, None);
+        let roles_title: String = get_translation("client_roles.title", lang, None);
+        let roles_message: String = get_translation("client_roles.message", lang, None);
+        let role_label: String = get_translation("client_roles.role", lang, None);
+        let since_col: String = get_translation("client_roles.since", lang, None);
+        let add_role_btn: String = get_translation("client_roles.add", lang, None);
+        let remove_role_btn: String = get_translation("client_roles.remove", lang, None);
+        let no_roles_msg: String = get_translation("client_roles.none", lang, None);
+        let step_up: StepUpTexts = StepUpTexts::new(lang);
         let nav = NavTexts::new(lang);
 
 
@@ -299,6 +859,29 @@ impl EditClientTexts {
             is_active,
             save_btn,
             new_scret_btn,
+            restrictions_title,
+            restrictions_message,
+            username_label,
+            reason_label,
+            days_label,
+            restrict_btn,
+            user_col,
+            reason_col,
+            issued_by_col,
+            start_col,
+            end_col,
+            indefinite,
+            by_client,
+            remove_btn,
+            no_restrictions_msg,
+            roles_title,
+            roles_message,
+            role_label,
+            since_col,
+            add_role_btn,
+            remove_role_btn,
+            no_roles_msg,
+            step_up,
             nav
         }
     }
@@ -331,10 +914,31 @@ pub struct DashboardTexts {
     pub message: String,
     pub first_name_label: String,
     pub last_name_label: String,
+    pub current_password_label: String,
     pub password_label: String,
     pub confirm_password_label: String,
     pub update_names_btn: String,
     pub update_password_btn: String,
+    pub sessions_title: String,
+    pub sessions_intro: String,
+    pub session_site_label: String,
+    pub session_device_label: String,
+    pub session_ip_label: String,
+    pub session_created_label: String,
+    pub session_last_used_label: String,
+    pub session_expires_label: String,
+    pub this_device_label: String,
+    pub no_sessions_msg: String,
+    pub sign_out_btn: String,
+    pub sign_out_others_btn: String,
+    pub history_title: String,
+    pub history_intro: String,
+    pub history_time_label: String,
+    pub history_event_label: String,
+    pub history_client_label: String,
+    pub history_ip_label: String,
+    pub history_device_label: String,
+    pub no_history_msg: String,
     pub nav: NavTexts
 }
 
@@ -344,14 +948,35 @@ impl DashboardTexts {
         let lang: &SupportedLangs = &user_req_data.lang;
 
         let message: String = get_translation(
-            "dash.greeting", lang, Some(&[&user_req_data.get_role()]));
+            "dash.greeting", lang, Some(&[user_req_data.get_role()]));
 
         let first_name_label: String = get_translation("dash.firstname", lang,None);
         let last_name_label: String = get_translation("dash.lastname", lang,None);
+        let current_password_label: String = get_translation("dash.current_password", lang, None);
         let password_label: String = get_translation("dash.password1",lang,None);
         let confirm_password_label: String = get_translation("dash.password2", lang, None);
         let update_names_btn: String = get_translation("dash.updatenames.btn", lang, None);
         let update_password_btn: String = get_translation("dash.updatepass.btn", lang, None);
+        let sessions_title: String = get_translation("dash.sessions.title", lang, None);
+        let sessions_intro: String = get_translation("dash.sessions.intro", lang, None);
+        let session_site_label: String = get_translation("dash.sessions.site", lang, None);
+        let session_device_label: String = get_translation("dash.sessions.device", lang, None);
+        let session_ip_label: String = get_translation("dash.sessions.ip", lang, None);
+        let session_created_label: String = get_translation("dash.sessions.created", lang, None);
+        let session_last_used_label: String = get_translation("dash.sessions.last_used", lang, None);
+        let session_expires_label: String = get_translation("dash.sessions.expires", lang, None);
+        let this_device_label: String = get_translation("dash.sessions.this_device", lang, None);
+        let no_sessions_msg: String = get_translation("dash.sessions.none", lang, None);
+        let sign_out_btn: String = get_translation("dash.sessions.signout.btn", lang, None);
+        let sign_out_others_btn: String = get_translation("dash.sessions.signout_others.btn", lang, None);
+        let history_title: String = get_translation("dash.history.title", lang, None);
+        let history_intro: String = get_translation("dash.history.intro", lang, None);
+        let history_time_label: String = get_translation("audit.col.time", lang, None);
+        let history_event_label: String = get_translation("audit.col.event", lang, None);
+        let history_client_label: String = get_translation("audit.col.client", lang, None);
+        let history_ip_label: String = get_translation("audit.col.ip", lang, None);
+        let history_device_label: String = get_translation("audit.col.device", lang, None);
+        let no_history_msg: String = get_translation("audit.none", lang, None);
         let nav: NavTexts = NavTexts::new(lang);
 
         DashboardTexts {
@@ -359,10 +984,31 @@ impl DashboardTexts {
             message,
             first_name_label,
             last_name_label,
+            current_password_label,
             password_label,
             confirm_password_label,
             update_names_btn,
             update_password_btn,
+            sessions_title,
+            sessions_intro,
+            session_site_label,
+            session_device_label,
+            session_ip_label,
+            session_created_label,
+            session_last_used_label,
+            session_expires_label,
+            this_device_label,
+            no_sessions_msg,
+            sign_out_btn,
+            sign_out_others_btn,
+            history_title,
+            history_intro,
+            history_time_label,
+            history_event_label,
+            history_client_label,
+            history_ip_label,
+            history_device_label,
+            no_history_msg,
             nav
         }
     }
@@ -398,6 +1044,9 @@ pub struct NavTexts {
     pub login: &'static str,
     pub register: &'static str,
     pub logout: &'static str,
+    pub impersonating: &'static str,
+    pub stop_impersonating: &'static str,
+    pub developer: &'static str,
 }
 
 
@@ -416,6 +1065,9 @@ impl NavTexts {
         let login_key: String = format!("{}.{}", "nav.login", lang_suffix);
         let register_key: String = format!("{}.{}", "nav.register", lang_suffix);
         let logout_key: String = format!("{}.{}", "nav.logout", lang_suffix);
+        let impersonating_key: String = format!("{}.{}", "nav.impersonating", lang_suffix);
+        let stop_key: String = format!("{}.{}", "nav.stop_impersonating", lang_suffix);
+        let developer_key: String = format!("{}.{}", "nav.developer", lang_suffix);
 
         let home: &'static str = raw_trans_or_missing(home_key.as_str(), lang);
         let admin: &'static str = raw_trans_or_missing(admin_key.as_str(), lang);
@@ -423,6 +1075,9 @@ impl NavTexts {
         let login: &'static str = raw_trans_or_missing(login_key.as_str(), lang);
         let register: &'static str = raw_trans_or_missing(register_key.as_str(), lang);
         let logout: &'static str = raw_trans_or_missing(logout_key.as_str(), lang);
+        let impersonating: &'static str = raw_trans_or_missing(impersonating_key.as_str(), lang);
+        let stop_impersonating: &'static str = raw_trans_or_missing(stop_key.as_str(), lang);
+        let developer: &'static str = raw_trans_or_missing(developer_key.as_str(), lang);
 
         NavTexts {
             home,
@@ -431,6 +1086,9 @@ impl NavTexts {
             login,
             register,
             logout,
+            impersonating,
+            stop_impersonating,
+            developer,
         }
     }
 }
@@ -481,14 +1139,14 @@ impl ErrorData {
             match lang {
                 SupportedLangs::English => {
                     return ErrorData {
-                        code: code,
+                        code,
                         title: "Unknown Error",
                         message: "An unknown error has occurred.",
                     };
                 },
                 SupportedLangs::French => {
                     return ErrorData {
-                        code: code,
+                        code,
                         title: "Erreur inconnue",
                         message: "Une erreur inconnue s'est produite.",
                     };
@@ -498,11 +1156,27 @@ impl ErrorData {
 
         // The error code is known, text is retrieved. Create and return struct.
         ErrorData {
-            code: code,
+            code,
             title: title_option.unwrap(),
             message: body_option.unwrap(),
         }
     }
+
+    /**
+     * Same error code, but a more specific title and message
+     * (ie: 403 because the client site is inactive: "err.client_inactive.title").
+     * Unknown reasons get the generic text for the code.
+     */
+    pub fn with_reason(code: String, reason: &str, lang: &SupportedLangs) -> Self {
+        let lang_suffix: &str = lang.suffix();
+        let title_key: String = format!("{}.{}.{}.{}", "err", reason, "title", lang_suffix);
+        let body_key: String = format!("{}.{}.{}.{}", "err", reason, "body", lang_suffix);
+
+        match (TRANSLATIONS.get(title_key.as_str()), TRANSLATIONS.get(body_key.as_str())) {
+            (Some(title), Some(message)) => ErrorData { code, title, message },
+            _ => ErrorData::new(code, lang)
+        }
+    }
 }
 
 fn missing_error(lang: &SupportedLangs) -> &'static str {
@@ -51,6 +51,8 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "dash.firstname.fr" => "Prénom:",
     "dash.lastname.en" => "Last Name:",
     "dash.lastname.fr" => "Nom:",
+    "dash.current_password.en" => "Current Password:",
+    "dash.current_password.fr" => "Mot de passe actuel :",
     "dash.password1.en" => "New Password:",
     "dash.password1.fr" => "Nouveau mot de passe :",
     "dash.password2.en" => "Confirm Password:",
@@ -60,6 +62,36 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "dash.updatenames.btn.fr" => "MAJ NOMS",
     "dash.updatepass.btn.en" => "UPDATE PASSWORD",
     "dash.updatepass.btn.fr" => "MAJ MOT DE PASSE",
+    // DASHBOARD SECURITY HISTORY
+    "dash.history.title.en" => "Security History",
+    "dash.history.title.fr" => "Historique de sécurité",
+    "dash.history.intro.en" => "Recent sign-ins and changes to your account. If something looks wrong, change your password.",
+    "dash.history.intro.fr" => "Connexions et modifications récentes de votre compte. Si quelque chose vous semble anormal, changez votre mot de passe.",
+    // DASHBOARD SESSIONS
+    "dash.sessions.title.en" => "Signed-in Devices",
+    "dash.sessions.title.fr" => "Appareils connectés",
+    "dash.sessions.intro.en" => "These games and devices are signed in to your account.",
+    "dash.sessions.intro.fr" => "Ces jeux et appareils sont connectés à votre compte.",
+    "dash.sessions.site.en" => "Site",
+    "dash.sessions.site.fr" => "Site",
+    "dash.sessions.device.en" => "Device",
+    "dash.sessions.device.fr" => "Appareil",
+    "dash.sessions.ip.en" => "IP Address",
+    "dash.sessions.ip.fr" => "Adresse IP",
+    "dash.sessions.created.en" => "Signed In",
+    "dash.sessions.created.fr" => "Connecté le",
+    "dash.sessions.last_used.en" => "Last Used",
+    "dash.sessions.last_used.fr" => "Dernière utilisation",
+    "dash.sessions.expires.en" => "Expires",
+    "dash.sessions.expires.fr" => "Expire le",
+    "dash.sessions.this_device.en" => "This device",
+    "dash.sessions.this_device.fr" => "Cet appareil",
+    "dash.sessions.none.en" => "No other sessions.",
+    "dash.sessions.none.fr" => "Aucune autre session.",
+    "dash.sessions.signout.btn.en" => "SIGN OUT",
+    "dash.sessions.signout.btn.fr" => "DÉCONNECTER",
+    "dash.sessions.signout_others.btn.en" => "SIGN OUT EVERYWHERE ELSE",
+    "dash.sessions.signout_others.btn.fr" => "DÉCONNECTER PARTOUT AILLEURS",
 
     // ADMIN DASHBOARD PAGE
     "admin.title.en" => "ADMIN HOME",
@@ -72,6 +104,440 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "admin.editclients.label.fr" => "MODIFIER LES SITES CLIENTS",
     "admin.newclient.btn.en" => "ADD NEW CLIENT",
     "admin.newclient.btn.fr" => "AJOUTEZ SITE CLIENT",
+    "admin.audit.btn.en" => "SECURITY AUDIT LOG",
+    "admin.audit.btn.fr" => "JOURNAL D'AUDIT DE SÉCURITÉ",
+    "admin.suspensions.btn.en" => "SUSPENSIONS",
+    "admin.suspensions.btn.fr" => "SUSPENSIONS",
+    "admin.users.btn.en" => "USERS",
+    "admin.users.btn.fr" => "UTILISATEURS",
+    "admin.reviews.btn.en" => "CLIENT REVIEWS",
+    "admin.reviews.btn.fr" => "VALIDATION DES SITES",
+    "admin.reg_tokens.btn.en" => "REGISTRATION TOKENS",
+    "admin.reg_tokens.btn.fr" => "JETONS D'INSCRIPTION",
+    "admin.clients.edit.en" => "EDIT",
+    "admin.clients.edit.fr" => "MODIFIER",
+    "admin.clients.archive.en" => "ARCHIVE",
+    "admin.clients.archive.fr" => "ARCHIVER",
+    "admin.clients.restore.en" => "RESTORE",
+    "admin.clients.restore.fr" => "RESTAURER",
+    "admin.clients.delete.en" => "DELETE",
+    "admin.clients.delete.fr" => "SUPPRIMER",
+    "admin.clients.archived.en" => "ARCHIVED CLIENT SITES",
+    "admin.clients.archived.fr" => "SITES CLIENTS ARCHIVÉS",
+    "admin.clients.confirm_archive.en" => "Archive this site? It is switched off and everyone is signed out of it. You can restore it later.",
+    "admin.clients.confirm_archive.fr" => "Archiver ce site ? Il est désactivé et tout le monde en est déconnecté. Vous pourrez le restaurer plus tard.",
+    "admin.clients.confirm_restore.en" => "Restore this site? It stays switched off until you activate it on its edit page.",
+    "admin.clients.confirm_restore.fr" => "Restaurer ce site ? Il reste désactivé jusqu'à ce que vous l'activiez sur sa page de modification.",
+    "admin.clients.confirm_delete.en" => "Delete this site for good? Its sessions, roles and restrictions are deleted with it. Only the audit log keeps its history. This can't be undone.",
+    "admin.clients.confirm_delete.fr" => "Supprimer ce site définitivement ? Ses sessions, rôles et restrictions sont supprimés avec lui. Seul le journal d'audit garde son historique. C'est irréversible.",
+    "admin.clients.confirm.en" => "YES, DO IT",
+    "admin.clients.confirm.fr" => "OUI, CONFIRMER",
+    "admin.clients.cancel.en" => "CANCEL",
+    "admin.clients.cancel.fr" => "ANNULER",
+
+    // CLIENT REVIEW QUEUE
+    "reviews.title.en" => "CLIENT REVIEWS",
+    "reviews.title.fr" => "VALIDATION DES SITES",
+    "reviews.message.en" => "Client sites registered by developers. They stay switched off until you approve them.",
+    "reviews.message.fr" => "Sites clients inscrits par des développeurs. Ils restent désactivés jusqu'à votre approbation.",
+    "reviews.col.name.en" => "Site",
+    "reviews.col.name.fr" => "Site",
+    "reviews.col.owner.en" => "Developer",
+    "reviews.col.owner.fr" => "Développeur",
+    "reviews.col.domain.en" => "Domain",
+    "reviews.col.domain.fr" => "Domaine",
+    "reviews.col.redirect.en" => "Redirect URI",
+    "reviews.col.redirect.fr" => "URI de redirection",
+    "reviews.col.type.en" => "Type / Category",
+    "reviews.col.type.fr" => "Type / Catégorie",
+    "reviews.col.submitted.en" => "Submitted",
+    "reviews.col.submitted.fr" => "Soumis le",
+    "reviews.approve.en" => "APPROVE",
+    "reviews.approve.fr" => "APPROUVER",
+    "reviews.reject.en" => "REJECT",
+    "reviews.reject.fr" => "REFUSER",
+    "reviews.none.en" => "Nothing to review.",
+    "reviews.none.fr" => "Rien à valider.",
+
+    // INITIAL ACCESS TOKENS (/oauth/register)
+    "reg_tokens.title.en" => "REGISTRATION TOKENS",
+    "reg_tokens.title.fr" => "JETONS D'INSCRIPTION",
+    "reg_tokens.message.en" => "Client sites can register themselves with POST /oauth/register and one of these tokens (Authorization: Bearer). They are live at once.",
+    "reg_tokens.message.fr" => "Les sites clients peuvent s'inscrire eux-mêmes avec POST /oauth/register et l'un de ces jetons (Authorization: Bearer). Ils sont actifs immédiatement.",
+    "reg_tokens.label.en" => "What it's for (ie: the game jam):",
+    "reg_tokens.label.fr" => "À quoi il sert (ex : la game jam) :",
+    "reg_tokens.max_uses.en" => "How many sites can register with it:",
+    "reg_tokens.max_uses.fr" => "Combien de sites peuvent s'inscrire avec :",
+    "reg_tokens.validity.en" => "Valid for {0} days.",
+    "reg_tokens.validity.fr" => "Valable {0} jours.",
+    "reg_tokens.issue.en" => "ISSUE TOKEN",
+    "reg_tokens.issue.fr" => "ÉMETTRE UN JETON",
+    "reg_tokens.issued.en" => "Here is the token. We will never show it again: copy it now.",
+    "reg_tokens.issued.fr" => "Voici le jeton. Il ne sera plus jamais affiché : copiez-le maintenant.",
+    "reg_tokens.usable.en" => "Usable tokens",
+    "reg_tokens.usable.fr" => "Jetons utilisables",
+    "reg_tokens.col.label.en" => "For",
+    "reg_tokens.col.label.fr" => "Pour",
+    "reg_tokens.col.issued_by.en" => "Issued by",
+    "reg_tokens.col.issued_by.fr" => "Émis par",
+    "reg_tokens.col.uses.en" => "Used",
+    "reg_tokens.col.uses.fr" => "Utilisé",
+    "reg_tokens.col.created.en" => "Issued",
+    "reg_tokens.col.created.fr" => "Émis le",
+    "reg_tokens.col.expires.en" => "Expires",
+    "reg_tokens.col.expires.fr" => "Expire le",
+    "reg_tokens.revoke.en" => "REVOKE",
+    "reg_tokens.revoke.fr" => "RÉVOQUER",
+    "reg_tokens.none.en" => "No usable tokens.",
+    "reg_tokens.none.fr" => "Aucun jeton utilisable.",
+    "reg_tokens.err.label.en" => "Say what the token is for (100 characters at most).",
+    "reg_tokens.err.label.fr" => "Indiquez à quoi sert le jeton (100 caractères au maximum).",
+    "reg_tokens.err.max_uses.en" => "Uses must be a whole number from 1 to 1000.",
+    "reg_tokens.err.max_uses.fr" => "Le nombre d'utilisations doit être un entier de 1 à 1000.",
+
+    // DEVELOPER PORTAL
+    "developer.title.en" => "DEVELOPER PORTAL",
+    "developer.title.fr" => "PORTAIL DÉVELOPPEUR",
+    "developer.message.en" => "Register your client sites and keep an eye on them.",
+    "developer.message.fr" => "Inscrivez vos sites clients et gardez un œil sur eux.",
+    "developer.register.en" => "REGISTER A CLIENT SITE",
+    "developer.register.fr" => "INSCRIRE UN SITE CLIENT",
+    "developer.register.note.en" => "A new site is reviewed by an admin before anyone can log in to it.",
+    "developer.register.note.fr" => "Un nouveau site est validé par un administrateur avant que quiconque puisse s'y connecter.",
+    "developer.mine.en" => "YOUR CLIENT SITES",
+    "developer.mine.fr" => "VOS SITES CLIENTS",
+    "developer.none.en" => "You haven't registered a site yet.",
+    "developer.none.fr" => "Vous n'avez encore inscrit aucun site.",
+    "developer.status.en" => "Status:",
+    "developer.status.fr" => "Statut :",
+    "developer.status.pending.en" => "Waiting for review",
+    "developer.status.pending.fr" => "En attente de validation",
+    "developer.status.rejected.en" => "Rejected",
+    "developer.status.rejected.fr" => "Refusé",
+    "developer.status.live.en" => "Live",
+    "developer.status.live.fr" => "En service",
+    "developer.status.inactive.en" => "Switched off by an admin",
+    "developer.status.inactive.fr" => "Désactivé par un administrateur",
+    "developer.created.en" => "Registered:",
+    "developer.created.fr" => "Inscrit le :",
+    "developer.sessions.en" => "Open sessions:",
+    "developer.sessions.fr" => "Sessions ouvertes :",
+    "developer.logins.en" => "Logins (last {0} days):",
+    "developer.logins.fr" => "Connexions ({0} derniers jours) :",
+    "developer.users.en" => "Users (last {0} days):",
+    "developer.users.fr" => "Utilisateurs ({0} derniers jours) :",
+    "developer.review_note.en" => "Changing the domain, redirect URI or client type of a live site sends it back for review. It is switched off until then.",
+    "developer.review_note.fr" => "Changer le domaine, l'URI de redirection ou le type de client d'un site en service le renvoie en validation. Il est désactivé d'ici là.",
+    "developer.secret.en" => "Here is the CLIENT_SECRET. We will never show it again: copy it now into the environment variables of your site.",
+    "developer.secret.fr" => "Voici le CLIENT_SECRET. Il ne sera plus jamais affiché : copiez-le maintenant dans les variables d'environnement de votre site.",
+
+    // ADMIN USERS CONSOLE
+    "admin.users.title.en" => "USERS",
+    "admin.users.title.fr" => "UTILISATEURS",
+    "admin.users.message.en" => "Find an account by part of its username or email.",
+    "admin.users.message.fr" => "Trouver un compte avec une partie de son nom d'utilisateur ou de son e-mail.",
+    "admin.users.search.en" => "Username or email",
+    "admin.users.search.fr" => "Nom d'utilisateur ou e-mail",
+    "admin.users.search.btn.en" => "SEARCH",
+    "admin.users.search.btn.fr" => "RECHERCHER",
+    "admin.users.col.username.en" => "Username",
+    "admin.users.col.username.fr" => "Nom d'utilisateur",
+    "admin.users.col.email.en" => "Email",
+    "admin.users.col.email.fr" => "E-mail",
+    "admin.users.col.role.en" => "Role",
+    "admin.users.col.role.fr" => "Rôle",
+    "admin.users.col.verified.en" => "Email verified",
+    "admin.users.col.verified.fr" => "E-mail vérifié",
+    "admin.users.col.created.en" => "Created",
+    "admin.users.col.created.fr" => "Créé",
+    "admin.users.none.en" => "No users found.",
+    "admin.users.none.fr" => "Aucun utilisateur trouvé.",
+    "admin.users.yes.en" => "Yes",
+    "admin.users.yes.fr" => "Oui",
+    "admin.users.no.en" => "No",
+    "admin.users.no.fr" => "Non",
+    "admin.users.prev.en" => "PREVIOUS",
+    "admin.users.prev.fr" => "PRÉCÉDENT",
+    "admin.users.next.en" => "NEXT",
+    "admin.users.next.fr" => "SUIVANT",
+    "admin.user.profile.en" => "Profile",
+    "admin.user.profile.fr" => "Profil",
+    "admin.user.first_name.en" => "First name",
+    "admin.user.first_name.fr" => "Prénom",
+    "admin.user.last_name.en" => "Last name",
+    "admin.user.last_name.fr" => "Nom de famille",
+    "admin.user.save_names.en" => "SAVE NAMES",
+    "admin.user.save_names.fr" => "ENREGISTRER LES NOMS",
+    "admin.user.save_role.en" => "SAVE ROLE",
+    "admin.user.save_role.fr" => "ENREGISTRER LE RÔLE",
+    "admin.user.extra_roles.en" => "Extra roles",
+    "admin.user.extra_roles.fr" => "Rôles supplémentaires",
+    "admin.user.add_role.en" => "ADD ROLE",
+    "admin.user.add_role.fr" => "AJOUTER LE RÔLE",
+    "admin.user.remove_role.en" => "REMOVE",
+    "admin.user.remove_role.fr" => "RETIRER",
+    "admin.user.verify_email.en" => "MARK EMAIL VERIFIED",
+    "admin.user.verify_email.fr" => "MARQUER L'E-MAIL COMME VÉRIFIÉ",
+    "admin.user.force_reset.en" => "FORCE PASSWORD RESET",
+    "admin.user.force_reset.fr" => "FORCER LA RÉINITIALISATION DU MOT DE PASSE",
+    "admin.user.force_reset.done.en" => "Signed out everywhere. A password reset link was emailed to them.",
+    "admin.user.force_reset.done.fr" => "Déconnecté partout. Un lien de réinitialisation du mot de passe lui a été envoyé.",
+    "admin.user.suspended.en" => "Suspended until {0}: {1}",
+    "admin.user.suspended.fr" => "Suspendu jusqu'au {0} : {1}",
+    "admin.user.sessions.en" => "Sessions",
+    "admin.user.sessions.fr" => "Sessions",
+    "admin.user.revoke.en" => "SIGN OUT",
+    "admin.user.revoke.fr" => "DÉCONNECTER",
+    "admin.user.revoke_all.en" => "SIGN OUT EVERYWHERE",
+    "admin.user.revoke_all.fr" => "DÉCONNECTER PARTOUT",
+    "admin.user.no_sessions.en" => "No active sessions.",
+    "admin.user.no_sessions.fr" => "Aucune session active.",
+    "admin.user.history.en" => "Security history",
+    "admin.user.history.fr" => "Historique de sécurité",
+    "admin.user.err.role.en" => "That role doesn't exist, or you may not give it.",
+    "admin.user.err.role.fr" => "Ce rôle n'existe pas, ou vous ne pouvez pas l'attribuer.",
+    "admin.user.err.self.en" => "You can't change your own role.",
+    "admin.user.err.self.fr" => "Vous ne pouvez pas modifier votre propre rôle.",
+    "admin.user.impersonate.en" => "LOG IN AS THIS USER",
+    "admin.user.impersonate.fr" => "SE CONNECTER EN TANT QUE CET UTILISATEUR",
+    "admin.user.impersonate.note.en" => "See the site as they do for {0} minutes. Every action is recorded. Password and secret changes are blocked.",
+    "admin.user.impersonate.note.fr" => "Voir le site comme lui pendant {0} minutes. Chaque action est enregistrée. Les changements de mot de passe et de secret sont bloqués.",
+    "admin.user.err.impersonate_self.en" => "You can't impersonate yourself.",
+    "admin.user.err.impersonate_self.fr" => "Vous ne pouvez pas vous usurper vous-même.",
+    "admin.user.err.impersonate_admin.en" => "Admins can't be impersonated.",
+    "admin.user.err.impersonate_admin.fr" => "Les administrateurs ne peuvent pas être usurpés.",
+
+
+    // SUSPENSIONS PAGE (admin)
+    "suspend.title.en" => "SUSPENSIONS",
+    "suspend.title.fr" => "SUSPENSIONS",
+    "suspend.message.en" => "Stop a player from logging in. They are signed out everywhere at once.",
+    "suspend.message.fr" => "Empêcher un joueur de se connecter. Il est immédiatement déconnecté partout.",
+    "suspend.username.en" => "Username:",
+    "suspend.username.fr" => "Nom d'utilisateur :",
+    "suspend.reason.en" => "Reason (the player will see this):",
+    "suspend.reason.fr" => "Raison (le joueur la verra) :",
+    "suspend.days.en" => "Days (leave empty to ban until further notice):",
+    "suspend.days.fr" => "Jours (laisser vide pour bannir jusqu'à nouvel ordre) :",
+    "suspend.btn.en" => "SUSPEND",
+    "suspend.btn.fr" => "SUSPENDRE",
+    "suspend.active.en" => "Suspensions in force",
+    "suspend.active.fr" => "Suspensions en vigueur",
+    "suspend.col.user.en" => "User",
+    "suspend.col.user.fr" => "Utilisateur",
+    "suspend.col.reason.en" => "Reason",
+    "suspend.col.reason.fr" => "Raison",
+    "suspend.col.issued_by.en" => "Issued by",
+    "suspend.col.issued_by.fr" => "Émise par",
+    "suspend.col.start.en" => "Since",
+    "suspend.col.start.fr" => "Depuis",
+    "suspend.col.end.en" => "Until",
+    "suspend.col.end.fr" => "Jusqu'au",
+    "suspend.indefinite.en" => "Further notice",
+    "suspend.indefinite.fr" => "Nouvel ordre",
+    "suspend.lift.en" => "LIFT",
+    "suspend.lift.fr" => "LEVER",
+    "suspend.none.en" => "Nobody is suspended.",
+    "suspend.none.fr" => "Personne n'est suspendu.",
+    "suspend.err.reason.en" => "A reason is required (500 characters at most).",
+    "suspend.err.reason.fr" => "Une raison est requise (500 caractères au maximum).",
+    "suspend.err.days.en" => "Days must be a whole number from 1 to 3650.",
+    "suspend.err.days.fr" => "Le nombre de jours doit être un entier de 1 à 3650.",
+    "suspend.err.admin.en" => "Admins cannot be suspended.",
+    "suspend.err.admin.fr" => "Les administrateurs ne peuvent pas être suspendus.",
+
+
+    // CLIENT RESTRICTIONS (on the edit client page)
+    "restrict.title.en" => "Blocked players",
+    "restrict.title.fr" => "Joueurs bloqués",
+    "restrict.message.en" => "Block a player from this site only. They are signed out of it at once, and can still use every other site.",
+    "restrict.message.fr" => "Bloquer un joueur sur ce site seulement. Il en est immédiatement déconnecté et peut toujours utiliser tous les autres sites.",
+    "restrict.btn.en" => "BLOCK",
+    "restrict.btn.fr" => "BLOQUER",
+    "restrict.remove.en" => "UNBLOCK",
+    "restrict.remove.fr" => "DÉBLOQUER",
+    "restrict.none.en" => "Nobody is blocked from this site.",
+    "restrict.none.fr" => "Personne n'est bloqué sur ce site.",
+    "restrict.by_client.en" => "The site itself",
+    "restrict.by_client.fr" => "Le site lui-même",
+
+
+    // CLIENT ROLES (on the edit client page)
+    "client_roles.title.en" => "Roles on this site",
+    "client_roles.title.fr" => "Rôles sur ce site",
+    "client_roles.message.en" => "Roles this site gives its own players (ie: moderator, beta_tester). They only mean something to this site.",
+    "client_roles.message.fr" => "Rôles que ce site donne à ses propres joueurs (ex : moderator, beta_tester). Ils n'ont de sens que pour ce site.",
+    "client_roles.role.en" => "Role",
+    "client_roles.role.fr" => "Rôle",
+    "client_roles.since.en" => "Since",
+    "client_roles.since.fr" => "Depuis",
+    "client_roles.add.en" => "ADD ROLE",
+    "client_roles.add.fr" => "AJOUTER LE RÔLE",
+    "client_roles.remove.en" => "REMOVE",
+    "client_roles.remove.fr" => "RETIRER",
+    "client_roles.none.en" => "Nobody has a role on this site.",
+    "client_roles.none.fr" => "Personne n'a de rôle sur ce site.",
+    "client_roles.err.name.en" => "Role names are 1 to 50 characters: lowercase letters, numbers, _ . -",
+    "client_roles.err.name.fr" => "Les noms de rôle font 1 à 50 caractères : lettres minuscules, chiffres, _ . -",
+
+
+    // SUSPENDED PAGE (what a suspended player sees)
+    "suspended.title.en" => "ACCOUNT SUSPENDED",
+    "suspended.title.fr" => "COMPTE SUSPENDU",
+    "suspended.message.en" => "Your account has been suspended, so you can't log in to Crankade or any of its games right now.",
+    "suspended.message.fr" => "Votre compte a été suspendu : vous ne pouvez pas vous connecter à Crankade ni à ses jeux pour le moment.",
+    "suspended.reason.en" => "Reason:",
+    "suspended.reason.fr" => "Raison :",
+    "suspended.since.en" => "Since:",
+    "suspended.since.fr" => "Depuis :",
+    "suspended.until.en" => "Until:",
+    "suspended.until.fr" => "Jusqu'au :",
+    "suspended.indefinite.en" => "Until further notice",
+    "suspended.indefinite.fr" => "Jusqu'à nouvel ordre",
+    "suspended.contact.en" => "If you think this is a mistake, contact us.",
+    "suspended.contact.fr" => "Si vous pensez qu'il s'agit d'une erreur, contactez-nous.",
+
+    // AUDIT LOG PAGE (admin)
+    "audit.title.en" => "SECURITY AUDIT LOG",
+    "audit.title.fr" => "JOURNAL D'AUDIT DE SÉCURITÉ",
+    "audit.message.en" => "Authentication events, newest first.",
+    "audit.message.fr" => "Événements d'authentification, les plus récents d'abord.",
+    "audit.filter.event.en" => "Event:",
+    "audit.filter.event.fr" => "Événement :",
+    "audit.filter.any.en" => "Any",
+    "audit.filter.any.fr" => "Tous",
+    "audit.filter.username.en" => "Username:",
+    "audit.filter.username.fr" => "Nom d'utilisateur :",
+    "audit.filter.client.en" => "Client ID:",
+    "audit.filter.client.fr" => "Client ID :",
+    "audit.filter.ip.en" => "IP Address:",
+    "audit.filter.ip.fr" => "Adresse IP :",
+    "audit.filter.btn.en" => "FILTER",
+    "audit.filter.btn.fr" => "FILTRER",
+    "audit.col.time.en" => "Time",
+    "audit.col.time.fr" => "Date",
+    "audit.col.event.en" => "Event",
+    "audit.col.event.fr" => "Événement",
+    "audit.col.user.en" => "User",
+    "audit.col.user.fr" => "Utilisateur",
+    "audit.col.actor.en" => "Actor ID",
+    "audit.col.actor.fr" => "ID de l'acteur",
+    "audit.col.client.en" => "Client",
+    "audit.col.client.fr" => "Client",
+    "audit.col.ip.en" => "IP Address",
+    "audit.col.ip.fr" => "Adresse IP",
+    "audit.col.device.en" => "Device",
+    "audit.col.device.fr" => "Appareil",
+    "audit.col.details.en" => "Details",
+    "audit.col.details.fr" => "Détails",
+    "audit.none.en" => "No events found.",
+    "audit.none.fr" => "Aucun événement trouvé.",
+    "audit.prev.en" => "NEWER",
+    "audit.prev.fr" => "PLUS RÉCENTS",
+    "audit.next.en" => "OLDER",
+    "audit.next.fr" => "PLUS ANCIENS",
+    // AUDIT EVENT NAMES
+    "audit.event.login_success.en" => "Logged in",
+    "audit.event.login_success.fr" => "Connexion",
+    "audit.event.login_failure.en" => "Failed login",
+    "audit.event.login_failure.fr" => "Échec de connexion",
+    "audit.event.registration.en" => "Registered",
+    "audit.event.registration.fr" => "Inscription",
+    "audit.event.registration_pending.en" => "Registration awaiting email",
+    "audit.event.registration_pending.fr" => "Inscription en attente de l'e-mail",
+    "audit.event.registration_confirmed.en" => "Registration confirmed",
+    "audit.event.registration_confirmed.fr" => "Inscription confirmée",
+    "audit.event.password_change.en" => "Password changed",
+    "audit.event.password_change.fr" => "Mot de passe modifié",
+    "audit.event.password_change_failure.en" => "Failed password change",
+    "audit.event.password_change_failure.fr" => "Échec de modification du mot de passe",
+    "audit.event.names_change.en" => "Names changed",
+    "audit.event.names_change.fr" => "Noms modifiés",
+    "audit.event.logout.en" => "Logged out",
+    "audit.event.logout.fr" => "Déconnexion",
+    "audit.event.session_revoked.en" => "Device signed out",
+    "audit.event.session_revoked.fr" => "Appareil déconnecté",
+    "audit.event.other_sessions_revoked.en" => "Signed out everywhere else",
+    "audit.event.other_sessions_revoked.fr" => "Déconnecté partout ailleurs",
+    "audit.event.client_created.en" => "Client site created",
+    "audit.event.client_created.fr" => "Site client créé",
+    "audit.event.client_updated.en" => "Client site updated",
+    "audit.event.client_updated.fr" => "Site client modifié",
+    "audit.event.secret_rotated.en" => "Client secret rotated",
+    "audit.event.secret_rotated.fr" => "Secret client renouvelé",
+    "audit.event.token_redeemed.en" => "Signed in to a game",
+    "audit.event.token_redeemed.fr" => "Connexion à un jeu",
+    "audit.event.token_redemption_failure.en" => "Failed auth code redemption",
+    "audit.event.token_redemption_failure.fr" => "Échec d'échange du code d'autorisation",
+    "audit.event.login_alert.en" => "Unusual login alert emailed",
+    "audit.event.login_alert.fr" => "Alerte de connexion inhabituelle envoyée",
+    "audit.event.account_secured.en" => "Signed out everywhere (\"this wasn't me\")",
+    "audit.event.account_secured.fr" => "Déconnexion partout (« ce n'était pas moi »)",
+    "audit.event.password_reset.en" => "Password reset",
+    "audit.event.password_reset.fr" => "Mot de passe réinitialisé",
+    "audit.event.user_suspended.en" => "Account suspended",
+    "audit.event.user_suspended.fr" => "Compte suspendu",
+    "audit.event.suspension_lifted.en" => "Suspension lifted",
+    "audit.event.suspension_lifted.fr" => "Suspension levée",
+    "audit.event.client_restriction_added.en" => "Blocked from a client site",
+    "audit.event.client_restriction_added.fr" => "Bloqué sur un site client",
+    "audit.event.client_restriction_removed.en" => "Client site block removed",
+    "audit.event.client_restriction_removed.fr" => "Blocage sur un site client retiré",
+    "audit.event.client_role_granted.en" => "Role given on a client site",
+    "audit.event.client_role_granted.fr" => "Rôle attribué sur un site client",
+    "audit.event.client_role_revoked.en" => "Role removed on a client site",
+    "audit.event.client_role_revoked.fr" => "Rôle retiré sur un site client",
+    "audit.event.user_updated.en" => "Account changed by an admin",
+    "audit.event.user_updated.fr" => "Compte modifié par un administrateur",
+    "audit.event.password_reset_forced.en" => "Password reset forced",
+    "audit.event.password_reset_forced.fr" => "Réinitialisation du mot de passe imposée",
+    "audit.event.all_sessions_revoked.en" => "Signed out everywhere",
+    "audit.event.all_sessions_revoked.fr" => "Déconnecté partout",
+    "audit.event.impersonation_started.en" => "Impersonation started",
+    "audit.event.impersonation_started.fr" => "Usurpation commencée",
+    "audit.event.impersonation_ended.en" => "Impersonation ended",
+    "audit.event.impersonation_ended.fr" => "Usurpation terminée",
+    "audit.event.step_up_success.en" => "Password confirmed",
+    "audit.event.step_up_success.fr" => "Mot de passe confirmé",
+    "audit.event.step_up_failure.en" => "Failed password confirmation",
+    "audit.event.step_up_failure.fr" => "Échec de confirmation du mot de passe",
+    "audit.event.client_archived.en" => "Client site archived",
+    "audit.event.client_archived.fr" => "Site client archivé",
+    "audit.event.client_restored.en" => "Client site restored",
+    "audit.event.client_restored.fr" => "Site client restauré",
+    "audit.event.client_deleted.en" => "Client site deleted",
+    "audit.event.client_deleted.fr" => "Site client supprimé",
+    "audit.event.client_approved.en" => "Client site approved",
+    "audit.event.client_approved.fr" => "Site client approuvé",
+    "audit.event.client_rejected.en" => "Client site rejected",
+    "audit.event.client_rejected.fr" => "Site client refusé",
+    "audit.event.client_registered.en" => "Client site registered itself",
+    "audit.event.client_registered.fr" => "Site client auto-inscrit",
+    "audit.event.registration_token_issued.en" => "Registration token issued",
+    "audit.event.registration_token_issued.fr" => "Jeton d'inscription émis",
+    "audit.event.registration_token_revoked.en" => "Registration token revoked",
+    "audit.event.registration_token_revoked.fr" => "Jeton d'inscription révoqué",
+
+
+    // ACCOUNT RECOVERY ("this wasn't me" and password reset pages)
+    "recover.not_me.title.en" => "SECURE YOUR ACCOUNT",
+    "recover.not_me.title.fr" => "SÉCURISER VOTRE COMPTE",
+    "recover.not_me.message.en" => "Didn't log in just now? We'll sign your account out of every device and site, then you can choose a new password.",
+    "recover.not_me.message.fr" => "Vous ne venez pas de vous connecter ? Nous allons déconnecter votre compte de tous les appareils et sites, puis vous pourrez choisir un nouveau mot de passe.",
+    "recover.not_me.btn.en" => "SIGN OUT EVERYWHERE",
+    "recover.not_me.btn.fr" => "DÉCONNECTER PARTOUT",
+    "recover.reset.title.en" => "CHOOSE A NEW PASSWORD",
+    "recover.reset.title.fr" => "CHOISIR UN NOUVEAU MOT DE PASSE",
+    "recover.reset.message.en" => "Every session has been signed out. Choose a new password for your account.",
+    "recover.reset.message.fr" => "Toutes les sessions ont été déconnectées. Choisissez un nouveau mot de passe pour votre compte.",
+    "recover.reset.btn.en" => "SAVE NEW PASSWORD",
+    "recover.reset.btn.fr" => "ENREGISTRER LE MOT DE PASSE",
+    "recover.reset.done.en" => "Your password has been changed. You can now log in with it.",
+    "recover.reset.done.fr" => "Votre mot de passe a été modifié. Vous pouvez maintenant vous connecter avec.",
+    "recover.mismatch.en" => "Passwords do not match",
+    "recover.mismatch.fr" => "Les mots de passe ne correspondent pas",
+    "recover.login_btn.en" => "LOG IN",
+    "recover.login_btn.fr" => "CONNEXION",
 
 
     // LOGIN PAGE
@@ -105,6 +571,8 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     // REGISTER BUTTONS
     "register.btn.en" => "REGISTER",
     "register.btn.fr" => "INSCRIPTION",
+    "register.confirmation_sent.en" => "Check your email for a link to finish registering.",
+    "register.confirmation_sent.fr" => "Consultez vos e-mails : un lien vous permettra de terminer votre inscription.",
 
     // NEW CLIENT SITE
     "new_client.title.en" => "NEW CLIENT SITE",
@@ -158,6 +626,12 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "nav.logout.fr" => "DÉCONNEXION",
     "nav.dashboard.en" => "DASHBOARD",
     "nav.dashboard.fr" => "TABLEAU DE BORD",
+    "nav.impersonating.en" => "IMPERSONATING",
+    "nav.impersonating.fr" => "USURPATION DE",
+    "nav.stop_impersonating.en" => "STOP",
+    "nav.stop_impersonating.fr" => "ARRÊTER",
+    "nav.developer.en" => "DEVELOPER",
+    "nav.developer.fr" => "DÉVELOPPEUR",
 
     // ERROR CODES AND TITLES FOR ERROR PAGE
     "err.400.title.en" => "Bad Request",
@@ -220,13 +694,98 @@ pub static TRANSLATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "err.504.body.en" => "Server did not respond in time.",
     "err.504.body.fr" => "Le serveur n'a pas répondu à temps.",
 
+    // EMAILS (plain text, sent by the mailer)
+    "mail.confirm_reg.subject.en" => "Confirm your Crankade account",
+    "mail.confirm_reg.subject.fr" => "Confirmez votre compte Crankade",
+    "mail.confirm_reg.body.en" => "Welcome, {0}!\n\nClick this link to finish creating your account:\n{1}\n\nIf you didn't sign up, you can ignore this email.",
+    "mail.confirm_reg.body.fr" => "Bienvenue, {0}!\n\nCliquez sur ce lien pour terminer la création de votre compte :\n{1}\n\nSi vous ne vous êtes pas inscrit, vous pouvez ignorer cet e-mail.",
+    "mail.reg_exists.subject.en" => "Someone tried to register with your email",
+    "mail.reg_exists.subject.fr" => "Quelqu'un a tenté de s'inscrire avec votre e-mail",
+    "mail.reg_exists.body.en" => "Someone tried to create a new Crankade account with this email address, but you already have an account.\n\nIf it was you, you can log in here:\n{0}\n\nIf it wasn't you, you can ignore this email.",
+    "mail.reg_exists.body.fr" => "Quelqu'un a tenté de créer un nouveau compte Crankade avec cette adresse e-mail, mais vous avez déjà un compte.\n\nSi c'était vous, vous pouvez vous connecter ici :\n{0}\n\nSinon, vous pouvez ignorer cet e-mail.",
+
+    "mail.pw_changed.subject.en" => "Your Crankade password was changed",
+    "mail.pw_changed.subject.fr" => "Votre mot de passe Crankade a été modifié",
+    "mail.pw_changed.body.en" => "Hello, {0}.\n\nThe password for your Crankade account was just changed, and every other device was signed out.\n\nIf you didn't do this, reset your password right away and contact us.",
+    "mail.pw_changed.body.fr" => "Bonjour, {0}.\n\nLe mot de passe de votre compte Crankade vient d'être modifié et tous les autres appareils ont été déconnectés.\n\nSi ce n'était pas vous, réinitialisez immédiatement votre mot de passe et contactez-nous.",
+    "mail.forced_reset.subject.en" => "Reset your Crankade password",
+    "mail.forced_reset.subject.fr" => "Réinitialisez votre mot de passe Crankade",
+    "mail.forced_reset.body.en" => "Hello, {0}.\n\nFor your security, an administrator signed your Crankade account out everywhere. Please choose a new password with this link:\n{1}\n\nThe link expires in {2} minutes.",
+    "mail.forced_reset.body.fr" => "Bonjour, {0}.\n\nPour votre sécurité, un administrateur a déconnecté votre compte Crankade partout. Veuillez choisir un nouveau mot de passe avec ce lien :\n{1}\n\nLe lien expire dans {2} minutes.",
+    "mail.login_alert.subject.en" => "New login to your Crankade account",
+    "mail.login_alert.subject.fr" => "Nouvelle connexion à votre compte Crankade",
+    "mail.login_alert.body.en" => "Hello, {0}.\n\nSomeone just logged in to your Crankade account.\n\nWhat we noticed:\n- {1}\n\nWhen: {2}\nWhere (approximately): {3}\nSite: {4}\nDevice: {5}\n\nIf this was you, you don't need to do anything.\n\nIf this wasn't you, open this link to sign out everywhere and choose a new password:\n{6}",
+    "mail.login_alert.body.fr" => "Bonjour, {0}.\n\nQuelqu'un vient de se connecter à votre compte Crankade.\n\nCe que nous avons remarqué :\n- {1}\n\nQuand : {2}\nOù (approximativement) : {3}\nSite : {4}\nAppareil : {5}\n\nSi c'était vous, vous n'avez rien à faire.\n\nSi ce n'était pas vous, ouvrez ce lien pour vous déconnecter partout et choisir un nouveau mot de passe :\n{6}",
+    "mail.login_alert.new_device.en" => "A device we haven't seen on your account before",
+    "mail.login_alert.new_device.fr" => "Un appareil que nous n'avions jamais vu sur votre compte",
+    "mail.login_alert.new_location.en" => "A network we haven't seen on your account before",
+    "mail.login_alert.new_location.fr" => "Un réseau que nous n'avions jamais vu sur votre compte",
+    "mail.login_alert.after_failures.en" => "Several failed login attempts just before it",
+    "mail.login_alert.after_failures.fr" => "Plusieurs tentatives de connexion échouées juste avant",
+
+    // PASSWORD POLICY (field-level reasons for the password forms)
+    "pw.too_short.en" => "Password must be at least {0} characters long.",
+    "pw.too_short.fr" => "Le mot de passe doit contenir au moins {0} caractères.",
+    "pw.too_long.en" => "Password must be at most {0} characters long.",
+    "pw.too_long.fr" => "Le mot de passe doit contenir au plus {0} caractères.",
+    "pw.control_chars.en" => "Password must not contain control characters.",
+    "pw.control_chars.fr" => "Le mot de passe ne doit pas contenir de caractères de contrôle.",
+    "pw.breached.en" => "This password has appeared in a data breach. Please choose another.",
+    "pw.breached.fr" => "Ce mot de passe est apparu dans une fuite de données. Veuillez en choisir un autre.",
+    "pw.too_weak.en" => "Password is too easy to guess. Try a longer passphrase of several words.",
+    "pw.too_weak.fr" => "Le mot de passe est trop facile à deviner. Essayez une phrase de passe plus longue de plusieurs mots.",
+
     // AD-HOC ERRORS FOR JSON
     "err.empty_creds.en" => "Invalid Credentials: Empty Field.",
     "err.empty_creds.fr" => "Identifiants invalides : champ vide.",
     "err.invalid_creds.en" => "Invalid Credentials.",
     "err.invalid_creds.fr" => "Identifiants invalides.",
+    "err.challenge_required.en" => "Please wait while we check you're not a bot.",
+    "err.challenge_required.fr" => "Veuillez patienter pendant que nous vérifions que vous n'êtes pas un robot.",
+    "err.suspended.en" => "This account is suspended.",
+    "err.suspended.fr" => "Ce compte est suspendu.",
+    "err.client_restricted.en" => "Your account is blocked from this site. Reason: {0}",
+    "err.client_restricted.fr" => "Votre compte est bloqué sur ce site. Raison : {0}",
+    "err.pkce_required.en" => "This site must send a valid PKCE code challenge (S256) to log in.",
+    "err.pkce_required.fr" => "Ce site doit envoyer un code challenge PKCE (S256) valide pour se connecter.",
     "err.user_not_found.en" => "User not found.",
     "err.user_not_found.fr" => "Utilisateur non trouvé.",
+    "err.client_inactive.en" => "This site is unavailable. It has been disabled.",
+    "err.client_inactive.fr" => "Ce site est indisponible. Il a été désactivé.",
+    "err.client_inactive.title.en" => "Site Unavailable",
+    "err.client_inactive.title.fr" => "Site indisponible",
+    "err.client_inactive.body.en" => "This site has been disabled by the administrators. You can't log in to it for now.",
+    "err.client_inactive.body.fr" => "Ce site a été désactivé par les administrateurs. Vous ne pouvez pas vous y connecter pour le moment.",
+    "err.auth_client_inactive.en" => "The auth site itself can't be deactivated.",
+    "err.auth_client_inactive.fr" => "Le site d'authentification lui-même ne peut pas être désactivé.",
+    "err.client_archived.en" => "This site is archived. Restore it first.",
+    "err.client_archived.fr" => "Ce site est archivé. Restaurez-le d'abord.",
+    "err.client_not_approved.en" => "This site hasn't been approved. Approve it in the review queue.",
+    "err.client_not_approved.fr" => "Ce site n'a pas été approuvé. Approuvez-le dans la file de validation.",
+    "err.client_id_taken.en" => "That Client ID is taken.",
+    "err.client_id_taken.fr" => "Ce Client ID est déjà pris.",
+    "err.client_no_secret.en" => "Only confidential clients have a secret.",
+    "err.client_no_secret.fr" => "Seuls les clients confidentiels ont un secret.",
+    "err.impersonating.en" => "Not allowed while impersonating a user.",
+    "err.impersonating.fr" => "Interdit pendant l'usurpation d'un utilisateur.",
+    "err.step_up_required.en" => "Please confirm your password to continue.",
+    "err.step_up_required.fr" => "Veuillez confirmer votre mot de passe pour continuer.",
+    "err.step_up_failed.en" => "Wrong password.",
+    "err.step_up_failed.fr" => "Mot de passe incorrect.",
+    "err.step_up_locked.en" => "Too many wrong passwords. Please try again in {0} minutes.",
+    "err.step_up_locked.fr" => "Trop de mots de passe incorrects. Veuillez réessayer dans {0} minutes.",
+
+    // STEP-UP (confirm your password before a sensitive admin action)
+    "step_up.title.en" => "Confirm it's you",
+    "step_up.title.fr" => "Confirmez votre identité",
+    "step_up.message.en" => "This action needs your password again.",
+    "step_up.message.fr" => "Cette action demande à nouveau votre mot de passe.",
+    "step_up.password.en" => "Password",
+    "step_up.password.fr" => "Mot de passe",
+    "step_up.confirm.en" => "CONFIRM",
+    "step_up.confirm.fr" => "CONFIRMER",
+    "step_up.cancel.en" => "CANCEL",
+    "step_up.cancel.fr" => "ANNULER",
 };
 
 
@@ -19,18 +19,24 @@
 use actix_web::{
     web, HttpResponse, HttpRequest,
     Responder, http::StatusCode, http::header,
-    get, post, web::Redirect };
-use actix_web::cookie::{ Cookie };
+    get, post, put, delete, web::Redirect };
 use askama::Template;
 use sqlx::{ MySqlPool };
 
 // local modules, loaded as crates (declared as mods in main.rs)
 use crate::{
     resources::get_translation,
-    db, utils, auth,
+    db, utils, auth, config, mailer, hash_pool, login_alerts, pow,
+    middleware::{ self, SecurityPolicy },
+    audit::{ self, AuditEvent },
+    auth::PasswordVerification,
+    password_policy::{ self, PasswordProblem },
+    rbac::Permission,
     resource_mgr::{
         HomeTexts, LoginTexts, RegisterTexts, AdminTexts,
         ErrorTexts, EditClientTexts, NewClientTexts, DashboardTexts,
+        AuditTexts, RecoveryTexts, SuspensionTexts, SuspendedTexts,
+        DeveloperTexts, ClientReviewTexts, RegistrationTokenTexts,
         ErrorData, error_by_code
      },
      auth_code_shared::{
@@ -39,7 +45,16 @@ use crate::{
         RefreshCheckRequest,
         RefreshCheckError,
         RefreshCheckSuccess,
-        RefreshCheckResponse
+        RefreshCheckResponse,
+        RestrictUserRequest,
+        UnrestrictUserRequest,
+        RestrictionUpdated,
+        RestrictionResponse,
+        ClientRoleRequest,
+        ClientRolesUpdated,
+        ClientRoleResponse,
+        ClientApiError,
+        ClientRegistrationRequest
     },
     routes_utils::{*}
 };
@@ -80,24 +95,41 @@ async fn register_post(
     info: web::Json<RegisterCredentials>
 ) -> HttpResponse {    
 
-    // check credentials against regex and size ranges
+    // Every registration needs a solved proof-of-work challenge (bots)
+    let client_ip: String = utils::client_ip(req.peer_addr(), req.headers());
+    if !pow::verify_solution(&pool, &info.pow_token, &info.pow_nonce, &client_ip).await {
+        return return_challenge_required_json(&req);
+    }
+
+    // check credentials against regex and size ranges, and the password policy
     let username_valid: bool = utils::validate_username(&info.username);
     let email_valid: bool = utils::validate_email(&info.email);
-    let password_valid: bool = utils::validate_password(&info.password);
+    let password_problems: Vec<PasswordProblem> = password_policy::check_password(
+        &info.password,
+        &[&info.username, &info.email]);
+    let password_valid: bool = password_problems.is_empty();
     let credentials_are_ok: bool = username_valid && email_valid && password_valid;
 
     if !credentials_are_ok {
         // One of the fields doesn't match the regex
+        let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
         let bad_creds_data: BadRegistrationInputs = BadRegistrationInputs {
             email_valid,
             username_valid,
             password_valid,
+            password_problems: password_policy::problem_messages(&password_problems, &lang),
             code: 422,
         };
 
         return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(bad_creds_data);
     }
 
+    // With enumeration resistance on, registration finishes by email
+    // and we never say whether an email address is already taken.
+    if config::enumeration_resistance() {
+        return register_with_confirmation(&pool, &req, &info).await;
+    }
+
     /* Input credentials are acceptable format.
      * Try to enter them in the database.
      * if username or email already exists, send a 409
@@ -113,6 +145,7 @@ async fn register_post(
             email_valid: !email_exists,
             username_valid: !username_exists,
             password_valid,
+            password_problems: Vec::new(),
             code: 409,
         };
 
@@ -121,12 +154,18 @@ async fn register_post(
 
     // NOW we've done our pre-checks. Time to add User to DATABASE
     // We can still send errors if there's a duplicate or a problem
-    
-    let user_id_result: Result<i32, anyhow::Error> = db::add_user(
+
+    let password_hash: String = match hash_pool::hash_password(info.password.clone()).await {
+        Ok(hash) => hash,
+        Err(e) => return hash_pool_err_json(&e)
+    };
+
+    let user_id_result: Result<i32, anyhow::Error> = db::add_user_with_hash(
         &pool,
         &info.username,
         &info.email,
-        info.password.clone()
+        &password_hash,
+        false
     ).await;
 
     let user_id: i32 = match user_id_result {
@@ -140,19 +179,21 @@ async fn register_post(
         }
     };
 
+    audit::record(&pool, &req, AuditEvent::Registration, Some(user_id), None, "").await;
+
     // get user object from DB
     match db::get_user_by_id(&pool, user_id).await {
         Ok(Some(user)) => {
             // User may now receive JWT and refresh token.
-            match get_user_auth_cookies(&pool, &user).await {
+            match get_user_auth_cookies(&pool, &req, &user).await {
                 Ok(cookies) => {
 
-                    return HttpResponse::Ok()
+                    HttpResponse::Ok()
                         .cookie(cookies.jwt_cookie)
                         .cookie(cookies.refresh_token_cookie)
                         .json(FreshLoginData {
                             username: user.get_username().to_owned()
-                    });
+                    })
                 },
                 Err(error_response) => {
                     HttpResponse::InternalServerError().json(error_response)
@@ -163,13 +204,13 @@ async fn register_post(
             let code: u16 = 404;
             let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
             let error: String = error_by_code(code.to_string(), &lang).to_string();
-            return HttpResponse::NotFound().json(ErrorResponse { error, code });
+            HttpResponse::NotFound().json(ErrorResponse { error, code })
         },
         Err(_e) => {
             let code: u16 = 500;
             let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
             let error: String = error_by_code(code.to_string(), &lang).to_string();
-            return HttpResponse::InternalServerError().json(ErrorResponse { error, code });
+            HttpResponse::InternalServerError().json(ErrorResponse { error, code })
         }
     }
 }
@@ -189,7 +230,7 @@ async fn login_post(
         // Worse than not finding something. Something broke.
         let code: u16 = 500;
         let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
-        let error: String = error_by_code(code.to_string(), &lang).to_string();
+        let error: String = error_by_code(code.to_string(), lang).to_string();
         HttpResponse::InternalServerError().json(ErrorResponse { error, code })
     };
 
@@ -204,6 +245,31 @@ async fn login_post(
         });
     }
 
+    // A deactivated client site gets no logins at all
+    if info.client_id != utils::auth_client_id() {
+        match db::is_client_active(&pool, &info.client_id).await {
+            Ok(true) => {},
+            Ok(false) => return return_client_inactive_json(&req),
+            Err(_e) => return server_error
+        }
+
+        // Clients without a secret need PKCE: the code is useless without the verifier
+        let client_has_secret: bool = match db::get_client_secret(&pool, &info.client_id).await {
+            Ok(Some(client_secret)) => client_secret.client_type.has_secret(),
+            Ok(None) => return return_client_inactive_json(&req),
+            Err(_e) => return server_error
+        };
+        let challenge_is_ok: bool = match &info.code_challenge {
+            Some(code_challenge) => auth::is_valid_code_challenge(code_challenge),
+            None => client_has_secret
+        };
+        if !challenge_is_ok {
+            let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
+            let error: String = get_translation("err.pkce_required", lang, None);
+            return HttpResponse::BadRequest().json(ErrorResponse { error, code: 400 });
+        }
+    }
+
     // TRYING TO GET A USER:
 
     // Find out if pattern matches email (and retrieve use by email), else treat as username (and
@@ -215,37 +281,107 @@ async fn login_post(
             db::get_user_by_username(&pool, &info.username_or_email).await
     };
 
+    // After repeated failures, no password is checked without a solved challenge
+    let known_user_id: Option<i32> = match &user_result {
+        Ok(Some(user)) => Some(user.get_id()),
+        _ => None
+    };
+    let client_ip: String = utils::client_ip(req.peer_addr(), req.headers());
+
+    if pow::login_needs_challenge(&pool, known_user_id, &client_ip).await &&
+        !pow::verify_solution(&pool, &info.pow_token, &info.pow_nonce, &client_ip).await {
+        return return_challenge_required_json(&req);
+    }
+
     let user: db::User = match user_result {
         Ok(Some(user)) => {
 
-            // Now check the input password against password from DB
-            if auth::verify_password(&info.password, user.get_password_hash()) {
-                user
-            } else {
-                // Auth clearly failed
+            // Now check the input password against password from DB.
+            // If the hash pool is swamped, shed load with a 503.
+            let verification: PasswordVerification = match hash_pool::verify_password(
+                info.password.to_owned(),
+                user.get_password_hash().to_owned()
+            ).await {
+                Ok(verification) => verification,
+                Err(e) => return hash_pool_err_json(&e)
+            };
+
+            match verification {
+                PasswordVerification::Match => user,
+                PasswordVerification::MatchOutdated => {
+                    // Right password, old Argon2 settings. Upgrade it now.
+                    upgrade_password_hash(&pool, user.get_id(), &info.password).await;
+                    user
+                },
+                PasswordVerification::Mismatch => {
+                    // Auth clearly failed
+                    audit::record(
+                        &pool, &req, AuditEvent::LoginFailure,
+                        Some(user.get_id()), Some(&info.client_id), "wrong password").await;
+
+                    let code: u16 = 401;
+                    let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
+                    let error: String = get_translation(
+                        "err.invalid_creds", lang, None);
+                    return HttpResponse::Unauthorized().json(ErrorResponse { error, code });
+                }
+            }
+        },
+        Ok(None) => {
+            // Not what they typed: it could be a mistyped password, or someone else's address
+            audit::record(
+                &pool, &req, AuditEvent::LoginFailure, None, Some(&info.client_id),
+                "unknown user").await;
+
+            // With enumeration resistance on, an unknown user must look exactly
+            // like a wrong password: same response, and the same Argon2 work.
+            if config::enumeration_resistance() {
+                if let Err(e) = hash_pool::dummy_verify_password(info.password.to_owned()).await {
+                    return hash_pool_err_json(&e);
+                }
+
                 let code: u16 = 401;
                 let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
                 let error: String = get_translation(
-                    "err.invalid_creds", &lang, None);
+                    "err.invalid_creds", lang, None);
                 return HttpResponse::Unauthorized().json(ErrorResponse { error, code });
             }
-        },
-        Ok(None) => {
+
             let code: u16 = 404;
             let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
             let error: String = get_translation(
-                "err.user_not_found", &lang, None);
+                "err.user_not_found", lang, None);
             return HttpResponse::NotFound().json(ErrorResponse { error, code });
         },
         Err(_e) => return server_error
     };
 
+    // Right password, but a suspended account can't log in
+    match db::get_active_suspension(&pool, user.get_id()).await {
+        Ok(Some(_suspension)) => {
+            audit::record(
+                &pool, &req, AuditEvent::LoginFailure,
+                Some(user.get_id()), Some(&info.client_id), "account suspended").await;
+            return return_suspended_json(&pool, &req, user.get_id()).await;
+        },
+        Ok(None) => {},
+        Err(_e) => return server_error
+    }
+
+    // Compare against the account's history BEFORE this login becomes part of it
+    let login_risk: login_alerts::LoginRisk =
+        login_alerts::assess_login(&pool, user.get_id(), &session_device(&req)).await;
+
+    if login_risk.is_suspicious() {
+        login_alerts::send_login_alert(&pool, &req, &user, &info.client_id, &login_risk).await;
+    }
 
-    // create auth_token if site is external
-    println!("Client id: {}", info.client_id);
+    audit::record(
+        &pool, &req, AuditEvent::LoginSuccess,
+        Some(user.get_id()), Some(&info.client_id), "").await;
 
     // get cookies for local login
-    let two_auth_cookies: TwoAuthCookies = match get_user_auth_cookies(&pool, &user).await {
+    let two_auth_cookies: TwoAuthCookies = match get_user_auth_cookies(&pool, &req, &user).await {
         Ok(cookies) => cookies,
         Err(error_response) => {
             return HttpResponse::InternalServerError().json(error_response);
@@ -265,19 +401,34 @@ async fn login_post(
         });
     }
 
+    // Blocked from this site only: they're still logged in here, but get no auth code
+    match db::get_active_client_restriction(&pool, &info.client_id, user.get_id()).await {
+        Ok(Some(restriction)) => {
+            let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
+            let error: String = get_translation(
+                "err.client_restricted", lang, Some(&[&restriction.reason]));
+            return HttpResponse::Forbidden()
+                .cookie(two_auth_cookies.jwt_cookie)
+                .cookie(two_auth_cookies.refresh_token_cookie)
+                .json(ErrorResponse { error, code: 403 });
+        },
+        Ok(None) => {},
+        Err(_e) => return server_error
+    }
+
     // It's an external site. So let's get an auth_token and redirect
     let auth_code: String = match db::add_auth_code(
         &pool,
         user.get_id(),
         &info.client_id,
-        auth::generate_auth_code()
+        auth::generate_auth_code(),
+        info.code_challenge.as_ref(),
+        &session_device(&req)
     ).await {
         Ok(code) => code,
         Err(_e) => return server_error
     };
 
-    println!("Auth code: {}", auth_code);
-
     let redirect_uri_option: Option<String>  =
         match db::get_redirect_uri(&pool, &info.client_id).await {
             Ok(option) => option,
@@ -308,7 +459,7 @@ async fn login_post(
             let code: u16 = 404;
             let lang: &utils::SupportedLangs = &auth::get_user_req_data(&req).clone_lang();
             let error: String = get_translation(
-                "err.404.title", &lang, None);
+                "err.404.title", lang, None);
             HttpResponse::NotFound().json(ErrorResponse { error, code })
         }
     }
@@ -330,18 +481,37 @@ async fn req_secret_post(
 ) -> HttpResponse {
     println!("Seeking new Secret");
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
         return redirect_resp;
     }
+    if user_req_data.is_impersonated() {
+        return refuse_while_impersonating(&user_req_data.lang);
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    // Only confidential clients have a secret
+    match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(Some(client)) if !client.client_type.has_secret() => {
+            return no_client_secret_json(&user_req_data.lang);
+        },
+        Ok(Some(_client)) => {},
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    }
 
     let raw_client_secret_json: RawClientSecret = RawClientSecret {
         raw_client_secret: utils::generate_client_secret()
     };
 
-    let hashed_client_secret = auth::hash_password(
+    let hashed_client_secret: String = match hash_pool::hash_password(
        raw_client_secret_json.raw_client_secret.to_owned()
-    ).to_owned();
+    ).await {
+        Ok(hash) => hash,
+        Err(e) => return hash_pool_err_json(&e)
+    };
 
     match db::update_client_secret(
         &pool,
@@ -350,6 +520,10 @@ async fn req_secret_post(
     ).await {
         Ok(rows_affected) => {
             if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::SecretRotated,
+                    None, Some(&inputs.client_id), "").await;
+
                 HttpResponse::Ok()
                     .json(raw_client_secret_json)
             } else {
@@ -376,63 +550,27 @@ async fn new_client_post(
     mut inputs: web::Json<ClientInputs>
 ) -> HttpResponse {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
         return redirect_resp;
     }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
 
     // Trim every string
     inputs.trim_all_strings();
 
-    // Make sure the required fields are not empty
-    let domains_are_valid: bool =
-        utils::validate_url(&inputs.redirect_uri) &&
-        utils::validate_url(&inputs.site_domain);
-    
-    if !domains_are_valid {
-        println!("domains are not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Invalid domain format"),
-                code: 406
-            })
-    }
-
-    // Check all the fields.
-
-    let client_id_is_valid: bool = utils::string_length_valid(
-        utils::StringRange{ min: 2, max: 20 },
-        &inputs.client_id
-    ) && utils::has_no_whitespace(
-        &inputs.client_id
-    );
-
-    if !client_id_is_valid {
-        eprintln!("CLIENT ID is not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Client ID: 2-20 characters, no spaces"),
-                code: 406
-            });
-    }
-
-    let name_is_valid: bool = utils::string_length_valid(
-        utils::StringRange{ min: 2, max: 20 },
-        &inputs.site_name
-    );
-
-    if !name_is_valid {
-        eprintln!("NAME is not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Site name: 2-20 characters."),
-                code: 406
-            });
+    // Same checks as the developer portal and /oauth/register
+    if let Some(problem_resp) = client_inputs_problem(&inputs) {
+        return problem_resp;
     }
 
     // String checks passed. Enter into DB, generate secret, show admin secret
-    let raw_client_secret: String = utils::generate_client_secret();
-    let hashed_secret: String = auth::hash_password(raw_client_secret.to_owned());        
+    let (raw_client_secret, hashed_secret) = match new_client_secret(inputs.get_client_type()).await {
+        Ok(secret) => secret,
+        Err(error_response) => return error_response
+    };
 
     let client_data: db::NewClientData = db::NewClientData {
         site_domain: inputs.site_domain.to_owned(),
@@ -442,9 +580,13 @@ async fn new_client_post(
         hashed_client_secret: hashed_secret.to_owned(),
         logo_url: inputs.logo_url.to_owned(),
         description: inputs.description.to_owned(),
-        category: inputs.category.to_owned(),
-        client_type: inputs.client_type.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
         is_active: inputs.is_active,
+        owner_user_id: None,
+        status: String::from(db::CLIENT_APPROVED),
+        registration_token_hash: None,
+        initial_access_token_id: None,
     };
 
     let new_client_result: Result<u64, anyhow::Error> =
@@ -453,14 +595,18 @@ async fn new_client_post(
     match new_client_result {
         Ok(rows_affected) => {
             if rows_affected > 0 {
-                // We added it to the DB. Send the admin their raw secret.
+                audit::record(
+                    &pool, &req, AuditEvent::ClientCreated,
+                    None, Some(&inputs.client_id), &inputs.site_name).await;
+
+                // We added it to the DB. Send the admin their raw secret (if it has one).
                 let raw_client_secret_json: RawClientSecret = RawClientSecret {
                     raw_client_secret
                 };
-                return HttpResponse::Ok()
-                    .json(raw_client_secret_json);
+                HttpResponse::Ok()
+                    .json(raw_client_secret_json)
             } else {
-                return return_internal_err_json();
+                return_internal_err_json()
             }
         },
         Err(e) => {
@@ -490,105 +636,219 @@ async fn update_client_post(
     println!("UPDATING CLIENT");
 
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
         return redirect_resp;
     }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
 
     // Trim every string
     inputs.trim_all_strings();
 
-    // Make sure the required fields are not empty
-    let domains_are_valid: bool =
-        utils::validate_url(&inputs.redirect_uri) &&
-        utils::validate_url(&inputs.site_domain);
-    
-    if !domains_are_valid {
-        println!("domains are not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Invalid domain format"),
-                code: 406
-            })
+    // Same checks as adding one
+    if let Some(problem_resp) = client_inputs_problem(&inputs) {
+        return problem_resp;
     }
 
-    // Check all the fields.
-
-    let client_id_is_valid: bool = utils::string_length_valid(
-        utils::StringRange{ min: 2, max: 20 },
-        &inputs.client_id
-    ) && utils::has_no_whitespace(
-        &inputs.client_id
-    );
-
-    if !client_id_is_valid {
-        println!("CLIENT ID is not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Site name: 2-20 characters."),
-                code: 406
+    // An archived client is only edited once it's restored
+    match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(Some(client)) if client.is_archived() => {
+            return HttpResponse::Conflict().json(ErrorResponse{
+                error: get_translation("err.client_archived", &user_req_data.lang, None),
+                code: 409
+            });
+        },
+        // A developer's client is switched on by approving it (review queue)
+        Ok(Some(client)) if inputs.is_active && !client.is_approved() => {
+            return HttpResponse::Conflict().json(ErrorResponse{
+                error: get_translation("err.client_not_approved", &user_req_data.lang, None),
+                code: 409
             });
+        },
+        Ok(_) => {},
+        Err(_e) => return return_internal_err_json()
     }
 
-    let name_is_valid: bool = utils::string_length_valid(
-        utils::StringRange{ min: 2, max: 20 },
-        &inputs.site_name
-    );
-
-    if !name_is_valid {
-        println!("NAME is not valid");
-        return HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
+    // Switching off the auth site would lock the admins out of this page
+    if !inputs.is_active && inputs.client_id == utils::auth_client_id() {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
             .json(ErrorResponse{
-                error: String::from("Site name: 2-20 characters."),
-                code: 406
+                error: get_translation("err.auth_client_inactive", &user_req_data.lang, None),
+                code: 422
             });
     }
 
-    // If string checks passed, enter into DB, generate secret, show admin secret
-    if domains_are_valid && client_id_is_valid && name_is_valid {
-        let client_data: db::UpdateClientData = db::UpdateClientData {
-            site_domain: inputs.site_domain.to_owned(),
-            site_name: inputs.site_name.to_owned(),
-            client_id: inputs.client_id.to_owned(),
-            redirect_uri: inputs.redirect_uri.to_owned(),
-            logo_url: inputs.logo_url.to_owned(),
-            description: inputs.description.to_owned(),
-            category: inputs.category.to_owned(),
-            client_type: inputs.client_type.to_owned(),
-            is_active: inputs.is_active,
-        };
-    
-        let update_client_result: Result<i32, anyhow::Error> =
-            db::update_external_client(&pool, client_data).await;
-        
-        match update_client_result {
-            Ok(rows_affected) => {
-                if rows_affected > 0 {
-                    let update_data: UpdateData = UpdateData::new(true);
+    // String checks passed. Enter into DB.
+    let client_data: db::UpdateClientData = db::UpdateClientData {
+        site_domain: inputs.site_domain.to_owned(),
+        site_name: inputs.site_name.to_owned(),
+        client_id: inputs.client_id.to_owned(),
+        redirect_uri: inputs.redirect_uri.to_owned(),
+        logo_url: inputs.logo_url.to_owned(),
+        description: inputs.description.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
+        is_active: inputs.is_active,
+    };
 
-                    HttpResponse::Ok()
-                        .json(update_data)
-                } else {
-                    return_internal_err_json()
+    let update_client_result: Result<i32, anyhow::Error> =
+        db::update_external_client(&pool, client_data).await;
+    
+    match update_client_result {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                let mut details: String = format!("is_active: {}", inputs.is_active);
+
+                // An inactive client keeps no sessions or auth codes
+                if !inputs.is_active {
+                    match db::delete_all_client_sessions(&pool, &inputs.client_id).await {
+                        Ok(revoked) => details.push_str(&format!(", {revoked} sessions revoked")),
+                        Err(e) => {
+                            eprintln!("Database error: {e}");
+                            return return_internal_err_json();
+                        }
+                    }
                 }
 
-            },
-            Err(e) => {
-                // Database error
-                eprintln!("Error: {e}");
-                HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                    .json(ErrorResponse{
-                        error: format!("Error: {e}"),
-                        code: 500
-                    })
+                audit::record(
+                    &pool, &req, AuditEvent::ClientUpdated,
+                    None, Some(&inputs.client_id), &details).await;
+
+                let update_data: UpdateData = UpdateData::new(true);
+
+                HttpResponse::Ok()
+                    .json(update_data)
+            } else {
+                return_internal_err_json()
+            }
+
+        },
+        Err(e) => {
+            // Database error
+            eprintln!("Error: {e}");
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .json(ErrorResponse{
+                    error: format!("Error: {e}"),
+                    code: 500
+                })
+        }
+    }
+}
+
+
+/**
+ * Archive a client site: switched off, everyone signed out of it,
+ * and off the admin's client list. Restorable.
+ */
+#[post("/archive_client")]
+pub async fn archive_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    match db::archive_client(&pool, &inputs.client_id).await {
+        Ok(archived) => {
+            if archived {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientArchived,
+                    None, Some(&inputs.client_id), "").await;
             }
+            HttpResponse::Ok().json(UpdateData::new(archived))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Bring an archived client site back. It stays inactive until an admin
+ * switches it on from its edit page.
+ */
+#[post("/restore_client")]
+pub async fn restore_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    match db::restore_client(&pool, &inputs.client_id).await {
+        Ok(restored) => {
+            if restored {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientRestored,
+                    None, Some(&inputs.client_id), "").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(restored))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Delete a client site for good. Its sessions, auth codes, roles and
+ * restrictions go with it (foreign keys). Its audit history stays.
+ */
+#[post("/delete_client")]
+pub async fn delete_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    // Keep its name in the audit log: the row it points at is about to go
+    let name: String = match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(Some(client)) => client.name,
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    match db::delete_client(&pool, &inputs.client_id).await {
+        Ok(deleted) => {
+            if deleted {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientDeleted,
+                    None, Some(&inputs.client_id), &format!("name: {name}")).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(deleted))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
         }
-    } else {
-        HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
-            .json(ErrorResponse{
-                error: String::from("Invalid Inputs"),
-                code: 406
-            })
     }
 }
 
@@ -601,47 +861,109 @@ pub async fn update_password(
 ) -> HttpResponse {
 
     // make sure user is logged in
-    let user_id: i32 = match auth::get_user_req_data(&req).id {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let user_id: i32 = match user_req_data.id {
         Some(id) => id,
         None => return return_authentication_err_json()
     };
 
+    // An admin looking around as this user doesn't get to change their password
+    if user_req_data.is_impersonated() {
+        return refuse_while_impersonating(&user_req_data.lang);
+    }
+
     match db::get_user_by_id(&pool, user_id).await {
-        Ok(Some(_user)) =>{
+        Ok(Some(user)) =>{
             // User is real user
-            // check credentials against regex and size ranges
-            let password_valid: bool = utils::validate_password(&password_obj.password);
+            // A stolen JWT alone must not be enough to take over the account
+            match hash_pool::verify_password(
+                password_obj.current_password.to_owned(),
+                user.get_password_hash().to_owned()
+            ).await {
+                Ok(verification) => {
+                    if !verification.matches() {
+                        audit::record(
+                            &pool, &req, AuditEvent::PasswordChangeFailure,
+                            Some(user_id), None, "wrong current password").await;
+
+                        return HttpResponse::Forbidden()
+                            .json(WrongCurrentPassword::new(403));
+                    }
+                },
+                Err(e) => return hash_pool_err_json(&e)
+            }
 
-            if !password_valid {
-                // One of the fields doesn't match the regex
-                let bad_password_data: BadPassword = BadPassword::new(422);
+            // check the new password against the password policy
+            let password_problems: Vec<PasswordProblem> = password_policy::check_password(
+                &password_obj.password,
+                &[user.get_username(), user.get_email()]);
+
+            if !password_problems.is_empty() {
+                // Password breaks the policy. Tell them exactly how.
+                let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
+                let bad_password_data: BadPassword = BadPassword::new(
+                    422,
+                    password_policy::problem_messages(&password_problems, &lang));
                 return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
                     .json(bad_password_data);
             }
 
-            // Names are valid. Update the DB
+            // Password is valid. Hash it and update the DB
+            let password_hash: String =
+                match hash_pool::hash_password(password_obj.password.to_owned()).await {
+                    Ok(hash) => hash,
+                    Err(e) => return hash_pool_err_json(&e)
+                };
+
             let update_password_result: Result<i32, anyhow::Error> =
-                db::update_password(
+                db::update_password_hash(
                     &pool,
-                    &password_obj.password,
+                    &password_hash,
                     user_id
                 ).await;
             
             match update_password_result {
                 Ok(rows_affected) => {
-                    return HttpResponse::Ok()
+                    // Sign out every OTHER session (all devices, all client sites).
+                    // The session making this request keeps its refresh token.
+                    let current_token: String = current_refresh_token(&req);
+
+                    if let Err(e) = db::delete_other_refresh_tokens(
+                        &pool, user_id, &current_token).await {
+                        eprintln!("Database error: {e}");
+                        return return_internal_err_json();
+                    }
+
+                    if let Err(e) = db::delete_auth_codes_for_user(&pool, user_id).await {
+                        eprintln!("Database error: {e}");
+                    }
+
+                    audit::record(
+                        &pool, &req, AuditEvent::PasswordChange, Some(user_id), None, "").await;
+
+                    let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
+                    mailer::queue_mail(mailer::OutgoingMail {
+                        to: user.get_email().to_owned(),
+                        subject: get_translation("mail.pw_changed.subject", &lang, None),
+                        body: get_translation(
+                            "mail.pw_changed.body",
+                            &lang,
+                            Some(&[user.get_username()])),
+                    });
+
+                    HttpResponse::Ok()
                         .json(UpdateData::new(rows_affected > 0))
                 },
                 Err(_e) => {
-                    return return_internal_err_json();
+                    return_internal_err_json()
                 }
             }
         },
-        Ok(None) => { return return_authentication_err_json(); },
+        Ok(None) => { return_authentication_err_json() },
         Err(_e) => {
-            return return_internal_err_json();
+            return_internal_err_json()
         }
-    };
+    }
 }
 
 
@@ -687,17 +1009,20 @@ pub async fn update_names(
             
             match update_names_result {
                 Ok(rows_affected) => {
-                    return HttpResponse::Ok()
-                        .json(UpdateData::new(rows_affected > 0));
+                    audit::record(
+                        &pool, &req, AuditEvent::NamesChange, Some(user_id), None, "").await;
+
+                    HttpResponse::Ok()
+                        .json(UpdateData::new(rows_affected > 0))
                 },
                 Err(_e) => {
-                    return return_internal_err_json();
+                    return_internal_err_json()
                 }
             }
         },
-        Ok(None) => return return_authentication_err_json(),
-        Err(_e) => return return_authentication_err_json()
-    };
+        Ok(None) => return_authentication_err_json(),
+        Err(_e) => return_authentication_err_json()
+    }
 }
 
 
@@ -707,48 +1032,275 @@ pub async fn logout_post(
     req: HttpRequest
 ) -> HttpResponse {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
-    let user_id = match user_req_data.id {
-        Some(id) => id,
-        None => 0
+    // While impersonating, the session (refresh_token) is the admin's
+    let user_id = match (&user_req_data.impersonator, user_req_data.id) {
+        (Some(actor), _) => actor.sub,
+        (None, Some(id)) => id,
+        (None, None) => 0
     };
 
     // delete cookies
-    
-    let jwt_cookie: Cookie<'_> = Cookie::build("jwt", "")
-        .path("/")
-        .max_age(time::Duration::seconds(0))
-        .http_only(true)
-        .finish();
-
-    let refresh_cookie: Cookie<'_> = Cookie::build("refresh_token", "")
-        .path("/")
-        .max_age(time::Duration::seconds(0))
-        .http_only(true)
-        .finish();
-
-    // delete refresh_token from DB
-    match db::delete_refresh_token(&pool, user_id).await {
+    let expired_cookies: TwoAuthCookies = TwoAuthCookies::expired();
+
+    // delete this session's refresh_token from DB (other devices stay logged in)
+    match db::delete_refresh_token(&pool, user_id, &current_refresh_token(&req)).await {
         Ok(_rows_deleted) => {},
         Err(e) => {eprint!("Database error: {e}")}
     }
 
+    if let Some(id) = user_req_data.id {
+        audit::record(&pool, &req, AuditEvent::Logout, Some(id), None, "").await;
+    }
+
     HttpResponse::Ok()
-        .cookie(jwt_cookie)
-        .cookie(refresh_cookie)
+        .cookie(expired_cookies.jwt_cookie)
+        .cookie(expired_cookies.refresh_token_cookie)
         .json(LogoutData::new())
 }
 
 
+/**
+ * Every session (device + client site) holding a refresh token for this user.
+ * For the session list on the dashboard.
+ */
+#[get("/sessions")]
+pub async fn list_sessions(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_id: i32 = match auth::get_user_req_data(&req).id {
+        Some(id) => id,
+        None => return return_authentication_err_json()
+    };
 
+    let current_token: String = current_refresh_token(&req);
 
-/* 
- * 
- * 
- * 
- * 
- * 
- * 
- * 
+    match db::get_sessions_for_user(&pool, user_id).await {
+        Ok(rows) => {
+            let sessions: Vec<SessionItem> = rows.into_iter()
+                .map(|row| SessionItem::new(row, &current_token))
+                .collect();
+
+            HttpResponse::Ok().json(SessionList { sessions })
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Sign out one session (one device on one site) from the dashboard.
+ */
+#[post("/revoke_session")]
+pub async fn revoke_session(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<SessionId>
+) -> HttpResponse {
+    let user_id: i32 = match auth::get_user_req_data(&req).id {
+        Some(id) => id,
+        None => return return_authentication_err_json()
+    };
+
+    match db::delete_session(&pool, user_id, inputs.session_id).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                let details: String = format!("session {}", inputs.session_id);
+                audit::record(
+                    &pool, &req, AuditEvent::SessionRevoked, Some(user_id), None, &details).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Sign out everywhere else: every session except the one making this request.
+ */
+#[post("/revoke_other_sessions")]
+pub async fn revoke_other_sessions(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_id: i32 = match auth::get_user_req_data(&req).id {
+        Some(id) => id,
+        None => return return_authentication_err_json()
+    };
+
+    match db::delete_other_refresh_tokens(&pool, user_id, &current_refresh_token(&req)).await {
+        Ok(rows_affected) => {
+            let details: String = format!("{rows_affected} sessions");
+            audit::record(
+                &pool, &req, AuditEvent::OtherSessionsRevoked, Some(user_id), None, &details).await;
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+
+/**
+ * The admin is done looking at the site as someone else.
+ * Swap the impersonation JWT for their own (their refresh_token never changed)
+ * and send them back to the user's page in the admin console.
+ * The new JWT has no auth_time: sensitive actions will ask for their password.
+ */
+#[post("/stop_impersonating")]
+pub async fn stop_impersonating(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let (actor, user_id): (auth::Actor, i32) =
+        match (user_req_data.impersonator, user_req_data.id) {
+            (Some(actor), Some(user_id)) => (actor, user_id),
+            _ => return return_authentication_err_json()
+        };
+
+    let admin: db::User = match db::get_user_by_id(&pool, actor.sub).await {
+        Ok(Some(admin)) => admin,
+        Ok(None) => return return_authentication_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(&pool, &utils::auth_client_id(), admin.get_id()).await {
+            Ok(roles) => roles,
+            Err(_e) => return return_internal_err_json()
+        };
+
+    let jwt: String = match auth::generate_jwt(
+        admin.get_id(),
+        admin.get_username().to_owned(),
+        admin.get_role().to_owned(),
+        client_roles,
+        0
+    ) {
+        Ok(jwt) => jwt,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    audit::record(&pool, &req, AuditEvent::ImpersonationEnded, Some(user_id), None, "").await;
+
+    HttpResponse::Ok()
+        .cookie(auth::build_token_cookie(jwt, String::from("jwt")))
+        .json(FullRedirectUri { redirect_uri: format!("/admin/users/{user_id}") })
+}
+
+
+/**
+ * Step-up: the admin types their password again before a sensitive action
+ * (see routes_utils::require_step_up).
+ * A new JWT with a fresh auth_time replaces the old one. Same session.
+ * Someone guessing the password on a stolen session gets STEP_UP_FAILURES
+ * tries, then the password isn't even checked until the lockout is over.
+ */
+#[post("/step_up")]
+pub async fn step_up_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<StepUpInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let user_id: i32 = match user_req_data.id {
+        Some(id) => id,
+        None => return return_authentication_err_json()
+    };
+
+    // Nobody knows the impersonated user's password
+    if user_req_data.is_impersonated() {
+        return refuse_while_impersonating(&user_req_data.lang);
+    }
+
+    let user: db::User = match db::get_user_by_id(&pool, user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return return_authentication_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    let lockout_minutes: i64 = config::step_up_lockout_minutes();
+    let since: time::OffsetDateTime =
+        time::OffsetDateTime::now_utc() - time::Duration::minutes(lockout_minutes);
+
+    match db::count_audit_events_since(
+        &pool, user_id, AuditEvent::StepUpFailure.as_str(), since).await {
+        Ok(failures) if failures >= config::step_up_failures() => {
+            return HttpResponse::TooManyRequests().json(ErrorResponse {
+                error: get_translation(
+                    "err.step_up_locked",
+                    &user_req_data.lang,
+                    Some(&[&lockout_minutes.to_string()])),
+                code: 429
+            });
+        },
+        Ok(_) => {},
+        Err(_e) => return return_internal_err_json()
+    }
+
+    match hash_pool::verify_password(
+        inputs.password.to_owned(),
+        user.get_password_hash().to_owned()
+    ).await {
+        Ok(verification) => {
+            if !verification.matches() {
+                audit::record(
+                    &pool, &req, AuditEvent::StepUpFailure, Some(user_id), None, "").await;
+
+                return HttpResponse::Forbidden().json(ErrorResponse {
+                    error: get_translation("err.step_up_failed", &user_req_data.lang, None),
+                    code: 403
+                });
+            }
+        },
+        Err(e) => return hash_pool_err_json(&e)
+    }
+
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(&pool, &utils::auth_client_id(), user_id).await {
+            Ok(roles) => roles,
+            Err(_e) => return return_internal_err_json()
+        };
+
+    let jwt: String = match auth::generate_jwt(
+        user_id,
+        user.get_username().to_owned(),
+        user.get_role().to_owned(),
+        client_roles,
+        auth::auth_time_now()
+    ) {
+        Ok(jwt) => jwt,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    audit::record(&pool, &req, AuditEvent::StepUpSuccess, Some(user_id), None, "").await;
+
+    HttpResponse::Ok()
+        .cookie(auth::build_token_cookie(jwt, String::from("jwt")))
+        .json(UpdateData::new(true))
+}
+
+
+
+/* 
+ * 
+ * 
+ * 
+ * 
+ * 
+ * 
+ * 
  * 
  * 
  * ========================
@@ -771,6 +1323,316 @@ pub async fn logout_post(
 
 
 
+/**
+ * The link in the registration confirmation email lands here.
+ * Turn the pending registration into a real (email-verified) user,
+ * log them in, and send them to their dashboard.
+ */
+#[get("/confirm_registration/{token}")]
+async fn confirm_registration(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    token: web::Path<String>
+) -> HttpResponse {
+    let pending: db::PendingRegistration =
+        match db::get_pending_registration(&pool, &token).await {
+            Ok(Some(pending)) => pending,
+            Ok(None) => return return_error_page(&req, 404),
+            Err(_e) => return return_error_page(&req, 500)
+        };
+
+    if pending.is_expired() {
+        if let Err(e) = db::delete_pending_registration(&pool, &pending.token).await {
+            eprintln!("Database error: {e}");
+        }
+        return return_error_page(&req, 404);
+    }
+
+    // Username or email could have been taken since the registration was made
+    let user_id: i32 = match db::add_user_with_hash(
+        &pool,
+        &pending.username,
+        &pending.email,
+        &pending.password_hash,
+        true
+    ).await {
+        Ok(id) => id,
+        Err(_e) => return return_error_page(&req, 409)
+    };
+
+    if let Err(e) = db::delete_pending_registration(&pool, &pending.token).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(&pool, &req, AuditEvent::RegistrationConfirmed, Some(user_id), None, "").await;
+
+    match db::get_user_by_id(&pool, user_id).await {
+        Ok(Some(user)) => {
+            match get_user_auth_cookies(&pool, &req, &user).await {
+                Ok(cookies) => {
+                    HttpResponse::Found()
+                        .cookie(cookies.jwt_cookie)
+                        .cookie(cookies.refresh_token_cookie)
+                        .append_header((header::LOCATION, "/dashboard"))
+                        .finish()
+                },
+                Err(_error_response) => return_error_page(&req, 500)
+            }
+        },
+        Ok(None) => return_error_page(&req, 404),
+        Err(_e) => return_error_page(&req, 500)
+    }
+}
+
+
+/**
+ * A fresh proof-of-work challenge for the register and login pages.
+ */
+#[get("/challenge")]
+async fn challenge(req: HttpRequest) -> HttpResponse {
+    match pow::issue_challenge(&utils::client_ip(req.peer_addr(), req.headers())) {
+        Ok(challenge) => HttpResponse::Ok().json(challenge),
+        Err(e) => {
+            eprintln!("{e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * The "this wasn't me" link in a login alert email lands here.
+ * Only shows a confirm button: link scanners in mail clients open
+ * links too, and they must not sign anybody out.
+ */
+#[get("/not_me/{token}")]
+async fn not_me_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    token: web::Path<String>
+) -> HttpResponse {
+    match db::get_account_token(&pool, &token, db::TOKEN_NOT_ME).await {
+        Ok(Some(account_token)) if !account_token.is_expired() => {},
+        Ok(_) => return return_error_page(&req, 404),
+        Err(_e) => return return_error_page(&req, 500)
+    }
+
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let recovery_template: AccountRecoveryTemplate = AccountRecoveryTemplate {
+        texts: RecoveryTexts::new(&user_req_data),
+        user: user_req_data,
+        token: token.to_owned(),
+        is_reset: false,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(recovery_template.render().unwrap())
+}
+
+
+/**
+ * "This wasn't me" confirmed.
+ * Sign out every session on every device and client site,
+ * then hand back a password reset link to continue with.
+ */
+#[post("/not_me")]
+async fn not_me_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    info: web::Json<RecoveryToken>
+) -> HttpResponse {
+    let account_token: db::AccountToken =
+        match db::get_account_token(&pool, &info.token, db::TOKEN_NOT_ME).await {
+            Ok(Some(account_token)) if !account_token.is_expired() => account_token,
+            Ok(_) => return return_not_found_err_json(),
+            Err(_e) => return return_internal_err_json()
+        };
+
+    let user_id: i32 = account_token.user_id;
+
+    if let Err(e) = db::delete_all_refresh_tokens(&pool, user_id).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    if let Err(e) = db::delete_auth_codes_for_user(&pool, user_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    // Every alert link is used up: they all lead to the same place
+    if let Err(e) = db::delete_account_tokens(&pool, user_id, db::TOKEN_NOT_ME).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(&pool, &req, AuditEvent::AccountSecured, Some(user_id), None, "").await;
+
+    let reset_token: String = auth::generate_email_token();
+    if let Err(e) = db::add_account_token(
+        &pool,
+        user_id,
+        &reset_token,
+        db::TOKEN_PASSWORD_RESET,
+        time::Duration::minutes(config::password_reset_minutes())
+    ).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    // This browser may be logged in as the user too
+    let expired_cookies: TwoAuthCookies = TwoAuthCookies::expired();
+
+    HttpResponse::Ok()
+        .cookie(expired_cookies.jwt_cookie)
+        .cookie(expired_cookies.refresh_token_cookie)
+        .json(FullRedirectUri {
+            redirect_uri: format!("/auth/reset_password/{reset_token}")
+        })
+}
+
+
+#[get("/reset_password/{token}")]
+async fn reset_password_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    token: web::Path<String>
+) -> HttpResponse {
+    match db::get_account_token(&pool, &token, db::TOKEN_PASSWORD_RESET).await {
+        Ok(Some(account_token)) if !account_token.is_expired() => {},
+        Ok(_) => return return_error_page(&req, 404),
+        Err(_e) => return return_error_page(&req, 500)
+    }
+
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let recovery_template: AccountRecoveryTemplate = AccountRecoveryTemplate {
+        texts: RecoveryTexts::new(&user_req_data),
+        user: user_req_data,
+        token: token.to_owned(),
+        is_reset: true,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(recovery_template.render().unwrap())
+}
+
+
+/**
+ * Set a new password with a reset token (no current password needed).
+ * Same policy as a normal password change. Signs out every session.
+ */
+#[post("/reset_password")]
+async fn reset_password_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    info: web::Json<PasswordReset>
+) -> HttpResponse {
+    let account_token: db::AccountToken =
+        match db::get_account_token(&pool, &info.token, db::TOKEN_PASSWORD_RESET).await {
+            Ok(Some(account_token)) if !account_token.is_expired() => account_token,
+            Ok(_) => return return_not_found_err_json(),
+            Err(_e) => return return_internal_err_json()
+        };
+
+    let user: db::User = match db::get_user_by_id(&pool, account_token.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+    let user_id: i32 = user.get_id();
+    let lang: utils::SupportedLangs = auth::get_user_req_data(&req).clone_lang();
+
+    let password_problems: Vec<PasswordProblem> = password_policy::check_password(
+        &info.password,
+        &[user.get_username(), user.get_email()]);
+
+    if !password_problems.is_empty() {
+        let bad_password_data: BadPassword = BadPassword::new(
+            422,
+            password_policy::problem_messages(&password_problems, &lang));
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+            .json(bad_password_data);
+    }
+
+    let password_hash: String =
+        match hash_pool::hash_password(info.password.to_owned()).await {
+            Ok(hash) => hash,
+            Err(e) => return hash_pool_err_json(&e)
+        };
+
+    let rows_affected: i32 = match db::update_password_hash(&pool, &password_hash, user_id).await {
+        Ok(rows) => rows,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    // Anything that signed in since the reset began goes too
+    if let Err(e) = db::delete_all_refresh_tokens(&pool, user_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    if let Err(e) = db::delete_auth_codes_for_user(&pool, user_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    if let Err(e) = db::delete_account_tokens(&pool, user_id, db::TOKEN_PASSWORD_RESET).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(&pool, &req, AuditEvent::PasswordReset, Some(user_id), None, "").await;
+
+    mailer::queue_mail(mailer::OutgoingMail {
+        to: user.get_email().to_owned(),
+        subject: get_translation("mail.pw_changed.subject", &lang, None),
+        body: get_translation(
+            "mail.pw_changed.body",
+            &lang,
+            Some(&[user.get_username()])),
+    });
+
+    HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+}
+
+
+/**
+ * Why a suspended user can't log in.
+ * Reached from the login page, with the token login_post handed out.
+ */
+#[get("/suspended/{token}")]
+async fn suspended_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    token: web::Path<String>
+) -> HttpResponse {
+    let account_token: db::AccountToken =
+        match db::get_account_token(&pool, &token, db::TOKEN_SUSPENSION_NOTICE).await {
+            Ok(Some(account_token)) if !account_token.is_expired() => account_token,
+            Ok(_) => return return_error_page(&req, 404),
+            Err(_e) => return return_error_page(&req, 500)
+        };
+
+    // Lifted (or over) since the token was made: just log in again
+    let suspension: db::Suspension =
+        match db::get_active_suspension(&pool, account_token.user_id).await {
+            Ok(Some(suspension)) => suspension,
+            Ok(None) => return send_to_login(),
+            Err(_e) => return return_error_page(&req, 500)
+        };
+
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let suspended_template: SuspendedTemplate = SuspendedTemplate {
+        texts: SuspendedTexts::new(&user_req_data),
+        user: user_req_data,
+        reason: suspension.reason,
+        since: utils::format_utc(&suspension.start_timestamp),
+        until: suspension.end_timestamp.map(|end| utils::format_utc(&end)),
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(suspended_template.render().unwrap())
+}
+
+
 // if user just goes to /auth or /auth/
 pub async fn auth_home() -> impl Responder {
     Redirect::to("/auth/login")
@@ -821,9 +1683,21 @@ pub async fn login_page(
 ) -> impl Responder {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
 
-    // Get client site references to list on login site
+    // A link straight to a deactivated client site's login
+    if let Some(client_id) = &query.client_id {
+        match db::get_client_by_client_id(&pool, client_id).await {
+            Ok(Some(client)) if !client.get_is_active() => {
+                return redirect_to_err(String::from("403/client_inactive"))
+                    .respond_to(&req)
+                    .map_into_boxed_body();
+            },
+            _ => {}
+        }
+    }
+
+    // Get client site references to list on login site (active ones only)
     let client_refs: Vec<db::ClientRef> =
-        match db::get_client_refs(&pool).await {
+        match db::get_active_client_refs(&pool).await {
             Ok(refs) => refs,
             Err(e) => {
                 eprintln!("Error retrieving client references: {e}");
@@ -836,8 +1710,14 @@ pub async fn login_page(
         None => "".to_string()
     };
 
+    // PKCE: carried through to the login POST. We only do S256.
+    let code_challenge: String = match (&query.code_challenge, query.code_challenge_method.as_deref()) {
+        (Some(code_challenge), Some("S256")) => code_challenge.to_owned(),
+        _ => "".to_string()
+    };
+
     // Make sure there's a site to login to.
-    let login_is_available: bool = client_refs.len() > 0;
+    let login_is_available: bool = !client_refs.is_empty();
 
     let login_template: LoginTemplate = LoginTemplate {
         texts: LoginTexts::new(&user_req_data),
@@ -845,9 +1725,16 @@ pub async fn login_page(
         client_refs,
         login_is_available,
         selected_client_id,
+        code_challenge,
     };
 
-    HttpResponse::Ok()
+    // Client sites may show the login in a frame on their own pages
+    let login_frame_ancestors: String = config::login_frame_ancestors();
+    if !login_frame_ancestors.is_empty() {
+        middleware::override_security_policy(&req, SecurityPolicy::framed_by(&login_frame_ancestors));
+    }
+
+    HttpResponse::Ok()
         .content_type("text/html")
         .body(login_template.render().unwrap())
 }
@@ -869,40 +1756,1198 @@ pub async fn register_page(req: HttpRequest) -> impl Responder {
 
 
 /**
- * Main admin dashboard
- * if user just goes to /auth
+ * Main admin dashboard
+ * if user just goes to /auth
+ */
+pub async fn admin_home(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> impl Responder {
+    println!("ADMIN HOME");
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::AdminAccess) {
+        return redirect_resp;
+    }
+    
+    // Get client site references to list on admin site (if they may see them)
+    let (client_refs, archived_refs): (Vec<db::ClientRef>, Vec<db::ClientRef>) =
+        if user_req_data.has_permission(Permission::ClientsRead) {
+            match tokio::try_join!(
+                db::get_client_refs(&pool),
+                db::get_archived_client_refs(&pool)
+            ) {
+                Ok(refs) => refs,
+                Err(e) => {
+                    eprintln!("Error retrieving client references: {e}");
+                    (Vec::new(), Vec::new())
+                }
+            }
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+    let admin_template: AdminTemplate = AdminTemplate {
+        texts: AdminTexts::new(&user_req_data),
+        user: user_req_data,
+        client_refs,
+        archived_refs,
+        auth_client_id: utils::auth_client_id(),
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(admin_template.render().unwrap())  
+}
+
+
+/**
+ * Queue and wait-time metrics for the password hashing pool.
+ * Admin only. JSON.
+ */
+#[get("/hash_pool_stats")]
+pub async fn hash_pool_stats(req: HttpRequest) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::SystemRead) {
+        return redirect_resp;
+    }
+
+    HttpResponse::Ok().json(hash_pool::stats())
+}
+
+
+/**
+ * The security audit log, newest first, with filters.
+ * Admin only.
+ */
+#[get("/audit")]
+pub async fn audit_log_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    query: web::Query<AuditQuery>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::AuditRead) {
+        return redirect_resp;
+    }
+
+    let page_size: i64 = 50;
+    let page: u32 = query.page.unwrap_or(0);
+    let filter: db::AuditFilter = query.to_filter();
+
+    // Get one extra row to find out if there's an older page
+    let mut rows: Vec<db::AuditEventRow> = match db::get_audit_events(
+        &pool,
+        &filter,
+        page_size + 1,
+        page as i64 * page_size
+    ).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Error retrieving audit events: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let has_older: bool = rows.len() as i64 > page_size;
+    rows.truncate(page_size as usize);
+
+    let events: Vec<AuditEventView> = rows.into_iter()
+        .map(|row| AuditEventView::new(row, &user_req_data.lang))
+        .collect();
+
+    let event_types: Vec<EventTypeOption> = AuditEvent::all_types().into_iter()
+        .map(|value| EventTypeOption {
+            value,
+            label: get_translation(&format!("audit.event.{value}"), &user_req_data.lang, None),
+            selected: filter.event_type.as_deref() == Some(value),
+        })
+        .collect();
+
+    let audit_template: AuditLogTemplate = AuditLogTemplate {
+        texts: AuditTexts::new(&user_req_data),
+        user: user_req_data,
+        events,
+        event_types,
+        filter,
+        page,
+        has_older,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(audit_template.render().unwrap())
+}
+
+
+/**
+ * Suspensions in force, and the form to suspend someone.
+ */
+#[get("/suspensions")]
+pub async fn suspensions_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersSuspend) {
+        return redirect_resp;
+    }
+
+    let texts: SuspensionTexts = SuspensionTexts::new(&user_req_data);
+
+    let suspensions: Vec<SuspensionView> = match db::get_active_suspensions(&pool).await {
+        Ok(rows) => rows.into_iter()
+            .map(|row| SuspensionView::new(row, &texts.indefinite))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error retrieving suspensions: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let suspensions_template: SuspensionsTemplate = SuspensionsTemplate {
+        texts,
+        user: user_req_data,
+        suspensions,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(suspensions_template.render().unwrap())
+}
+
+
+/**
+ * Suspend a user: they're signed out of every device and client site,
+ * and can't log in again until it ends or an admin lifts it.
+ */
+#[post("/suspend")]
+pub async fn suspend_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<SuspendInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersSuspend) {
+        return redirect_resp;
+    }
+    let admin_id: i32 = user_req_data.id.unwrap_or(0);
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+
+    let (reason, end_timestamp) =
+        match validate_reason_and_days(&inputs.reason, inputs.days, lang) {
+            Ok(valid) => valid,
+            Err(error_response) => return error_response
+        };
+
+    let user: db::User = match db::get_user_by_username(&pool, &inputs.username.trim().to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: get_translation("err.user_not_found", lang, None),
+                code: 404
+            });
+        },
+        Err(_e) => return return_internal_err_json()
+    };
+
+    // Admins can't lock each other (or themselves) out
+    if user.get_role() == db::UserRole::Admin.as_str() {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: get_translation("suspend.err.admin", lang, None),
+            code: 403
+        });
+    }
+
+    if let Err(e) = db::add_suspension(
+        &pool, user.get_id(), &reason, admin_id, end_timestamp).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    // Sign them out everywhere
+    if let Err(e) = db::delete_all_refresh_tokens(&pool, user.get_id()).await {
+        eprintln!("Database error: {e}");
+    }
+
+    if let Err(e) = db::delete_auth_codes_for_user(&pool, user.get_id()).await {
+        eprintln!("Database error: {e}");
+    }
+
+    let until: String = match end_timestamp {
+        Some(end) => utils::format_utc(&end),
+        None => String::from("further notice")
+    };
+    audit::record(
+        &pool, &req, AuditEvent::UserSuspended, Some(user.get_id()), None,
+        &format!("until {until}: {reason}")).await;
+
+    HttpResponse::Ok().json(UpdateData::new(true))
+}
+
+
+#[post("/lift_suspension")]
+pub async fn lift_suspension_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<SuspendedUser>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersSuspend) {
+        return redirect_resp;
+    }
+
+    match db::lift_suspensions(&pool, inputs.user_id, user_req_data.id.unwrap_or(0)).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::SuspensionLifted,
+                    Some(inputs.user_id), None, "").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * An admin blocks a user from one client site (from the edit client page).
+ * Same rules as a suspension, but only for that site.
+ */
+#[post("/restrict_user")]
+pub async fn admin_restrict_user_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<RestrictInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+
+    let (reason, expires_timestamp) =
+        match validate_reason_and_days(&inputs.reason, inputs.days, lang) {
+            Ok(valid) => valid,
+            Err(error_response) => return error_response
+        };
+
+    match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(Some(_client)) => {},
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    }
+
+    let user: db::User = match db::get_user_by_username(&pool, &inputs.username.trim().to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: get_translation("err.user_not_found", lang, None),
+                code: 404
+            });
+        },
+        Err(_e) => return return_internal_err_json()
+    };
+
+    if let Err(e) = db::add_client_restriction(
+        &pool, &inputs.client_id, user.get_id(), &reason,
+        user_req_data.id, expires_timestamp).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    // Sign them out of that site only
+    if let Err(e) = db::delete_client_sessions(&pool, user.get_id(), &inputs.client_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(
+        &pool, &req, AuditEvent::ClientRestrictionAdded,
+        Some(user.get_id()), Some(&inputs.client_id), &reason).await;
+
+    HttpResponse::Ok().json(UpdateData::new(true))
+}
+
+
+#[post("/unrestrict_user")]
+pub async fn admin_unrestrict_user_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<UnrestrictInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+
+    match db::delete_client_restrictions(&pool, &inputs.client_id, inputs.user_id).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientRestrictionRemoved,
+                    Some(inputs.user_id), Some(&inputs.client_id), "").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * An admin gives a user a role on one client site (from the edit client page).
+ */
+#[post("/add_client_role")]
+pub async fn add_client_role_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientRoleInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+
+    let role_name: String = inputs.role_name.trim().to_string();
+    if !utils::validate_role_name(&role_name) {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(ErrorResponse {
+            error: get_translation("client_roles.err.name", lang, None),
+            code: 422
+        });
+    }
+
+    match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(Some(_client)) => {},
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    }
+
+    let user: db::User = match db::get_user_by_username(&pool, &inputs.username.trim().to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: get_translation("err.user_not_found", lang, None),
+                code: 404
+            });
+        },
+        Err(_e) => return return_internal_err_json()
+    };
+
+    match db::add_client_user_role(
+        &pool, &inputs.client_id, user.get_id(), &role_name, user_req_data.id).await {
+        Ok(added) => {
+            if added {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientRoleGranted,
+                    Some(user.get_id()), Some(&inputs.client_id), &role_name).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(added))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+#[post("/remove_client_role")]
+pub async fn remove_client_role_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<RemoveClientRoleInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    match db::remove_client_user_role(
+        &pool, &inputs.client_id, inputs.user_id, &inputs.role_name).await {
+        Ok(removed) => {
+            if removed {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientRoleRevoked,
+                    Some(inputs.user_id), Some(&inputs.client_id), &inputs.role_name).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(removed))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * The users console: search accounts by username or email.
+ */
+#[get("/users")]
+pub async fn admin_users_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    query: web::Query<UsersQuery>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersRead) {
+        return redirect_resp;
+    }
+
+    let page_size: i64 = 50;
+    let page: u32 = query.page.unwrap_or(0);
+    let search: Option<String> = query.clean_search();
+
+    // Get one extra row to find out if there's another page
+    let mut rows: Vec<db::UserListRow> = match db::search_users(
+        &pool, &search, page_size + 1, page as i64 * page_size).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Error searching users: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let has_more: bool = rows.len() as i64 > page_size;
+    rows.truncate(page_size as usize);
+
+    let users_template: AdminUsersTemplate = AdminUsersTemplate {
+        texts: AdminTexts::new(&user_req_data),
+        user: user_req_data,
+        users: rows.into_iter().map(AdminUserView::new).collect(),
+        search: search.unwrap_or_default(),
+        page,
+        has_more,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(users_template.render().unwrap())
+}
+
+
+/**
+ * One user's account: profile, roles, sessions and security history,
+ * with the admin's actions.
+ */
+#[get("/users/{user_id}")]
+pub async fn admin_user_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    user_id: web::Path<i32>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersRead) {
+        return redirect_resp;
+    }
+    let user_id: i32 = user_id.into_inner();
+
+    let account: db::User = match db::get_user_by_id(&pool, user_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return return_error_page(&req, 404),
+        Err(_e) => return return_error_page(&req, 500)
+    };
+
+    let texts: AdminTexts = AdminTexts::new(&user_req_data);
+    let suspend_texts: SuspensionTexts = SuspensionTexts::new(&user_req_data);
+
+    let (extra_roles, role_names, suspension, sessions, events) = match tokio::try_join!(
+        db::get_extra_role_names(&pool, user_id),
+        db::get_role_names(&pool),
+        db::get_active_suspension(&pool, user_id),
+        db::get_sessions_for_user(&pool, user_id),
+        db::get_audit_events_for_user(&pool, user_id, 50)
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error loading user {user_id}: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let suspension: Option<String> = suspension.map(|suspension| {
+        let until: String = match suspension.end_timestamp {
+            Some(end) => utils::format_utc(&end),
+            None => suspend_texts.indefinite.to_owned()
+        };
+        get_translation(
+            "admin.user.suspended", &user_req_data.lang, Some(&[&until, &suspension.reason]))
+    });
+
+    let role_options: Vec<RoleOption> = role_names.into_iter()
+        .map(|name| RoleOption { selected: &name == account.get_role(), name })
+        .collect();
+
+    // Not yourself, not another admin, and not while already being someone else
+    let can_impersonate: bool =
+        user_req_data.has_permission(Permission::UsersImpersonate) &&
+        !user_req_data.is_impersonated() &&
+        user_req_data.id != Some(account.get_id()) &&
+        account.get_role() != db::UserRole::Admin.as_str();
+
+    let user_template: AdminUserTemplate = AdminUserTemplate {
+        account: AdminUserView {
+            id: account.get_id(),
+            username: account.get_username().to_owned(),
+            email: account.get_email().to_owned(),
+            role: account.get_role().to_owned(),
+            email_verified: account.get_email_verified(),
+            created: utils::format_utc(account.get_created_timestamp()),
+        },
+        first_name: account.get_first_name(),
+        last_name: account.get_last_name(),
+        extra_roles,
+        role_options,
+        suspension,
+        can_impersonate,
+        sessions: sessions.into_iter().map(AdminSessionView::new).collect(),
+        events: events.into_iter()
+            .map(|row| AuditEventView::new(row, &user_req_data.lang))
+            .collect(),
+        texts,
+        suspend_texts,
+        user: user_req_data,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(user_template.render().unwrap())
+}
+
+
+#[post("/users/update_names")]
+pub async fn admin_update_names_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserNames>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+
+    let names_valid: bool = utils::validate_real_name(&inputs.first_name) &&
+        utils::validate_real_name(&inputs.last_name);
+
+    if !names_valid {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+            .json(BadNames::new(422));
+    }
+
+    match db::update_real_names(&pool, &inputs.first_name, &inputs.last_name, inputs.user_id).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::UserUpdated, Some(inputs.user_id), None,
+                    "names").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * Change a user's base role.
+ * Nobody changes their own (no locking yourself out, no promoting yourself),
+ * and only admins make or unmake admins.
+ */
+#[post("/users/update_role")]
+pub async fn admin_update_role_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserRole>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+
+    if user_req_data.id == Some(inputs.user_id) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: get_translation("admin.user.err.self", lang, None),
+            code: 403
+        });
+    }
+
+    let account: db::User = match db::get_user_by_id(&pool, inputs.user_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    let admin_role: &str = db::UserRole::Admin.as_str();
+    let touches_admin: bool = inputs.role == admin_role || account.get_role() == admin_role;
+
+    if touches_admin && !user_req_data.is_admin() {
+        return return_role_refused_json(lang);
+    }
+
+    match db::update_user_role(&pool, inputs.user_id, &inputs.role).await {
+        // Nothing changed: no such role (or it was already theirs)
+        Ok(0) if &inputs.role != account.get_role() => return_role_refused_json(lang),
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::UserUpdated, Some(inputs.user_id), None,
+                    &format!("role: {} -> {}", account.get_role(), inputs.role)).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * Grant or take away an extra role (on top of the base role).
+ * Same rules as the base role: never your own, and only admins deal in admin.
+ */
+#[post("/users/add_role")]
+pub async fn admin_add_role_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserRole>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+    if let Some(refused_resp) = extra_role_refusal(&pool, &user_req_data, &inputs).await {
+        return refused_resp;
+    }
+
+    match db::get_role_names(&pool).await {
+        Ok(role_names) if role_names.contains(&inputs.role) => {},
+        Ok(_) => return return_role_refused_json(&user_req_data.lang),
+        Err(_e) => return return_internal_err_json()
+    }
+
+    match db::add_user_role(&pool, inputs.user_id, &inputs.role).await {
+        Ok(granted) => {
+            if granted {
+                audit::record(
+                    &pool, &req, AuditEvent::UserUpdated, Some(inputs.user_id), None,
+                    &format!("extra role added: {}", inputs.role)).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(granted))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+#[post("/users/remove_role")]
+pub async fn admin_remove_role_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserRole>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+    if let Some(refused_resp) = extra_role_refusal(&pool, &user_req_data, &inputs).await {
+        return refused_resp;
+    }
+
+    match db::remove_user_role(&pool, inputs.user_id, &inputs.role).await {
+        Ok(removed) => {
+            if removed {
+                audit::record(
+                    &pool, &req, AuditEvent::UserUpdated, Some(inputs.user_id), None,
+                    &format!("extra role removed: {}", inputs.role)).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(removed))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+#[post("/users/verify_email")]
+pub async fn admin_verify_email_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+
+    match db::set_email_verified(&pool, inputs.user_id, true).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::UserUpdated, Some(inputs.user_id), None,
+                    "email verified").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * Sign the user out everywhere and email them a password reset link
+ * (ie: their account looks compromised).
+ * Their current password keeps working until they use the link.
+ */
+#[post("/users/force_password_reset")]
+pub async fn admin_force_reset_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+
+    let account: db::User = match db::get_user_by_id(&pool, inputs.user_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    if let Err(e) = db::delete_all_refresh_tokens(&pool, inputs.user_id).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    if let Err(e) = db::delete_auth_codes_for_user(&pool, inputs.user_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    let reset_token: String = auth::generate_email_token();
+    if let Err(e) = db::add_account_token(
+        &pool,
+        inputs.user_id,
+        &reset_token,
+        db::TOKEN_PASSWORD_RESET,
+        time::Duration::minutes(config::password_reset_minutes())
+    ).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    // The email goes out in the user's language... which we don't store. Use the admin's.
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+    let reset_url: String =
+        format!("{}/auth/reset_password/{}", config::auth_domain(), reset_token);
+
+    mailer::queue_mail(mailer::OutgoingMail {
+        to: account.get_email().to_owned(),
+        subject: get_translation("mail.forced_reset.subject", lang, None),
+        body: get_translation(
+            "mail.forced_reset.body",
+            lang,
+            Some(&[
+                account.get_username(),
+                &reset_url,
+                &config::password_reset_minutes().to_string()
+            ])),
+    });
+
+    audit::record(
+        &pool, &req, AuditEvent::PasswordResetForced, Some(inputs.user_id), None, "").await;
+
+    HttpResponse::Ok().json(UpdateData::new(true))
+}
+
+
+#[post("/users/revoke_session")]
+pub async fn admin_revoke_session_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserSession>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+
+    match db::delete_session(&pool, inputs.user_id, inputs.session_id).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::SessionRevoked, Some(inputs.user_id), None,
+                    &format!("session {}", inputs.session_id)).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+#[post("/users/revoke_sessions")]
+pub async fn admin_revoke_sessions_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersWrite) {
+        return redirect_resp;
+    }
+
+    let rows_affected: i32 = match db::delete_all_refresh_tokens(&pool, inputs.user_id).await {
+        Ok(rows_affected) => rows_affected,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    if let Err(e) = db::delete_auth_codes_for_user(&pool, inputs.user_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(
+        &pool, &req, AuditEvent::AllSessionsRevoked, Some(inputs.user_id), None,
+        &format!("{rows_affected} sessions")).await;
+
+    HttpResponse::Ok().json(UpdateData::new(rows_affected > 0))
+}
+
+
+/**
+ * Log in as this user to see the site the way they do.
+ * The admin gets a short JWT for the user, with themself in the act claim.
+ * Their refresh_token cookie stays: when the JWT runs out the middleware
+ * makes them themself again (see login_status_middleware).
+ * Admins can't be impersonated, so this never grants more than the user has.
+ */
+#[post("/users/impersonate")]
+pub async fn admin_impersonate_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<AdminUserId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::UsersImpersonate) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+    let lang: &utils::SupportedLangs = &user_req_data.lang;
+
+    let (admin_id, admin_username): (i32, String) =
+        match (user_req_data.id, user_req_data.impersonator.is_none()) {
+            (Some(id), true) => (id, user_req_data.username.to_owned().unwrap_or_default()),
+            _ => return refuse_while_impersonating(lang)
+        };
+
+    if admin_id == inputs.user_id {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: get_translation("admin.user.err.impersonate_self", lang, None),
+            code: 403
+        });
+    }
+
+    let account: db::User = match db::get_user_by_id(&pool, inputs.user_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    if account.get_role() == db::UserRole::Admin.as_str() {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: get_translation("admin.user.err.impersonate_admin", lang, None),
+            code: 403
+        });
+    }
+
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(&pool, &utils::auth_client_id(), account.get_id()).await {
+            Ok(roles) => roles,
+            Err(_e) => return return_internal_err_json()
+        };
+
+    let jwt: String = match auth::generate_impersonation_jwt(
+        account.get_id(),
+        account.get_username().to_owned(),
+        account.get_role().to_owned(),
+        client_roles,
+        auth::Actor { sub: admin_id, username: admin_username }
+    ) {
+        Ok(jwt) => jwt,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    audit::record(
+        &pool, &req, AuditEvent::ImpersonationStarted, Some(account.get_id()), None,
+        &format!("for {} minutes", config::impersonation_minutes())).await;
+
+    HttpResponse::Ok()
+        .cookie(auth::build_token_cookie(jwt, String::from("jwt")))
+        .json(FullRedirectUri { redirect_uri: String::from("/dashboard") })
+}
+
+
+/**
+ * The review queue: client sites developers registered,
+ * waiting for an admin to approve or reject them.
+ */
+#[get("/client_reviews")]
+pub async fn client_reviews_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsReview) {
+        return redirect_resp;
+    }
+
+    let clients: Vec<PendingClientView> = match db::get_pending_clients(&pool).await {
+        Ok(rows) => rows.into_iter().map(PendingClientView::new).collect(),
+        Err(e) => {
+            eprintln!("Error retrieving pending clients: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let reviews_template: ClientReviewsTemplate = ClientReviewsTemplate {
+        texts: ClientReviewTexts::new(&user_req_data),
+        user: user_req_data,
+        clients,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(reviews_template.render().unwrap())
+}
+
+
+// Approve: the client site is switched on, users can log in to it
+#[post("/approve_client")]
+pub async fn approve_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    review_client(pool, req, inputs, true).await
+}
+
+
+// Reject: it stays switched off. The developer sees it rejected.
+#[post("/reject_client")]
+pub async fn reject_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    review_client(pool, req, inputs, false).await
+}
+
+
+// approve_client_post and reject_client_post
+async fn review_client(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>,
+    approve: bool
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsReview) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    match db::review_client(
+        &pool,
+        &inputs.client_id,
+        approve,
+        user_req_data.id.unwrap_or_default()
+    ).await {
+        Ok(reviewed) => {
+            if reviewed {
+                let event: AuditEvent =
+                    if approve { AuditEvent::ClientApproved } else { AuditEvent::ClientRejected };
+                audit::record(&pool, &req, event, None, Some(&inputs.client_id), "").await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(reviewed))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Initial access tokens for /oauth/register: the ones still usable,
+ * and the form to issue one.
  */
-pub async fn admin_home(
+#[get("/registration_tokens")]
+pub async fn registration_tokens_page(
     pool: web::Data<MySqlPool>,
     req: HttpRequest
-) -> impl Responder {
-    println!("ADMIN HOME");
+) -> HttpResponse {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
 
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
         return redirect_resp;
     }
-    
-    // Get client site references to list on admin site
-    let client_refs: Vec<db::ClientRef> =
-        match db::get_client_refs(&pool).await {
-            Ok(refs) => refs,
+
+    let tokens: Vec<InitialAccessTokenView> =
+        match db::get_usable_initial_access_tokens(&pool).await {
+            Ok(rows) => rows.into_iter().map(InitialAccessTokenView::new).collect(),
             Err(e) => {
-                eprintln!("Error retrieving client references: {e}");
-                Vec::new()
+                eprintln!("Error retrieving initial access tokens: {e}");
+                return return_error_page(&req, 500);
             }
         };
 
-    let admin_template: AdminTemplate = AdminTemplate {
-        texts: AdminTexts::new(&user_req_data),
+    let tokens_template: RegistrationTokensTemplate = RegistrationTokensTemplate {
+        texts: RegistrationTokenTexts::new(&user_req_data),
         user: user_req_data,
-        client_refs
+        tokens,
     };
 
     HttpResponse::Ok()
         .content_type("text/html")
-        .body(admin_template.render().unwrap())  
+        .body(tokens_template.render().unwrap())
+}
+
+
+/**
+ * Issue an initial access token. The admin sees it ONCE:
+ * we only keep its hash.
+ */
+#[post("/issue_registration_token")]
+pub async fn issue_registration_token_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    mut inputs: web::Json<IssueRegistrationTokenInput>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+    if user_req_data.is_impersonated() {
+        return refuse_while_impersonating(&user_req_data.lang);
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    inputs.label = inputs.label.trim().to_string();
+
+    if inputs.label.is_empty() || inputs.label.chars().count() > 100 {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+            .json(ErrorResponse{
+                error: get_translation("reg_tokens.err.label", &user_req_data.lang, None),
+                code: 422
+            });
+    }
+
+    if inputs.max_uses < 1 || inputs.max_uses > 1000 {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+            .json(ErrorResponse{
+                error: get_translation("reg_tokens.err.max_uses", &user_req_data.lang, None),
+                code: 422
+            });
+    }
+
+    let token: String = auth::generate_registration_token();
+
+    match db::add_initial_access_token(
+        &pool,
+        &auth::hash_registration_token(&token),
+        &inputs.label,
+        user_req_data.id.unwrap_or_default(),
+        inputs.max_uses,
+        time::Duration::days(config::initial_access_token_days())
+    ).await {
+        Ok(id) => {
+            audit::record(
+                &pool, &req, AuditEvent::RegistrationTokenIssued, None, None,
+                &format!("#{id} {}, {} uses", inputs.label, inputs.max_uses)).await;
+
+            HttpResponse::Ok().json(RawRegistrationToken { token })
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+// Clients it already registered keep working
+#[post("/revoke_registration_token")]
+pub async fn revoke_registration_token_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<RegistrationTokenId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
+        return redirect_resp;
+    }
+
+    match db::revoke_initial_access_token(&pool, inputs.id).await {
+        Ok(revoked) => {
+            if revoked {
+                audit::record(
+                    &pool, &req, AuditEvent::RegistrationTokenRevoked, None, None,
+                    &format!("#{}", inputs.id)).await;
+            }
+            HttpResponse::Ok().json(UpdateData::new(revoked))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
 }
 
 
@@ -919,8 +2964,8 @@ pub async fn admin_redirect() -> impl Responder {
 pub async fn new_client_site_form_page(req: HttpRequest) -> impl Responder {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
 
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsWrite) {
         return redirect_resp;
     }
     
@@ -947,8 +2992,8 @@ pub async fn edit_client_site_form_page(
 ) -> impl Responder {
     let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
 
-    // check if they're admin
-    if let Some(redirect_resp) = redirect_non_admin(&user_req_data, &req) {
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::ClientsRead) {
         return redirect_resp;
     }
 
@@ -962,10 +3007,30 @@ pub async fn edit_client_site_form_page(
 
     match client_data_result.unwrap() {
         Some(client_data) => {
+            let texts: EditClientTexts = EditClientTexts::new(&user_req_data);
+
+            let restrictions: Vec<ClientRestrictionView> =
+                match db::get_client_restrictions(&pool, &client_data.client_id).await {
+                    Ok(rows) => rows.into_iter()
+                        .map(|row| ClientRestrictionView::new(row, &texts))
+                        .collect(),
+                    Err(_e) => return return_error_page(&req, 500)
+                };
+
+            let role_assignments: Vec<ClientRoleView> =
+                match db::get_client_role_assignments(&pool, &client_data.client_id).await {
+                    Ok(rows) => rows.into_iter()
+                        .map(|row| ClientRoleView::new(row, &texts))
+                        .collect(),
+                    Err(_e) => return return_error_page(&req, 500)
+                };
+
             let new_client_template: EditClientTemplate = EditClientTemplate {
-                texts: EditClientTexts::new(&user_req_data),
+                texts,
                 user: user_req_data,
-                client_data
+                client_data,
+                restrictions,
+                role_assignments
             };
             
             HttpResponse::Ok()
@@ -995,26 +3060,39 @@ pub async fn dashboard_page(
 
     match db::get_user_by_id(&pool, id).await {
         Ok(Some(user)) =>{
+            // The user's own recent security history
+            let security_events: Vec<AuditEventView> =
+                match db::get_audit_events_for_user(&pool, id, 20).await {
+                    Ok(rows) => rows.into_iter()
+                        .map(|row| AuditEventView::new(row, &user_req_data.lang))
+                        .collect(),
+                    Err(e) => {
+                        eprintln!("Error retrieving security history: {e}");
+                        Vec::new()
+                    }
+                };
+
             let dashboard_template: DashboardTemplate<'_> = DashboardTemplate {
                 user_data: &user,
                 texts: DashboardTexts::new(&user_req_data),
-                user: user_req_data
+                user: user_req_data,
+                security_events
             };
 
-            return HttpResponse::Ok()
+            HttpResponse::Ok()
                 .content_type("text/html")
-                .body(dashboard_template.render().unwrap());
+                .body(dashboard_template.render().unwrap())
         },
         Ok(None) => {
-            return send_to_login();
+            send_to_login()
         },
         Err(_e) => {
             // redirect to ERROR PAGE
-            return HttpResponse::Found()
+            HttpResponse::Found()
                 .append_header((header::LOCATION, "/error"))
-                .finish();
+                .finish()
         }
-    };
+    }
 }
 
 
@@ -1050,6 +3128,26 @@ async fn error_page(req: HttpRequest, path: web::Path<String>) -> HttpResponse {
 }
 
 
+// A known error code with a more specific explanation (ie: "/error/403/client_inactive")
+#[get("/error/{code}/{reason}")]
+async fn error_reason_page(req: HttpRequest, path: web::Path<(String, String)>) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    let (code, reason): (String, String) = path.into_inner();
+
+    let error_data: ErrorData = ErrorData::with_reason(code, &reason, &user_req_data.lang);
+
+    let error_template: ErrorTemplate<> = ErrorTemplate {
+        error_data,
+        texts: ErrorTexts::new(&user_req_data),
+        user: user_req_data
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(error_template.render().unwrap())
+}
+
+
 #[get("/error")]
 async fn error_root() -> HttpResponse {
     HttpResponse::Found()
@@ -1067,6 +3165,331 @@ async fn error_root_2() -> HttpResponse {
 
 
 
+/* 
+ * 
+ * 
+ * 
+ * 
+ * ==============================
+ * ==============================
+ * =====                    =====
+ * =====  DEVELOPER PORTAL  =====
+ * =====                    =====
+ * ==============================
+ * ==============================
+ * 
+ * 
+ * 
+ * Developers (Permission::DeveloperAccess) register and run their own
+ * client sites. A new one waits, switched off, until an admin approves it
+ * (/admin/client_reviews). A developer only sees and touches the clients
+ * they own (client_sites.owner_user_id): anyone else's is a 404.
+ * 
+ * 
+ */
+
+
+/**
+ * The developer's client site, if it's theirs (and not archived).
+ * Otherwise the 404 to send back.
+ */
+async fn get_owned_client(
+    pool: &MySqlPool,
+    user_req_data: &auth::UserReqData,
+    client_id: &String
+) -> Result<db::ClientData, HttpResponse> {
+    match db::get_client_by_client_id(pool, client_id).await {
+        Ok(Some(client)) => {
+            if client.owner_user_id.is_some()
+                && client.owner_user_id == user_req_data.id
+                && !client.is_archived() {
+                Ok(client)
+            } else {
+                Err(HttpResponse::NotFound().json(ErrorResponse{
+                    error: get_translation("err.404.title", &user_req_data.lang, None),
+                    code: 404
+                }))
+            }
+        },
+        Ok(None) => Err(HttpResponse::NotFound().json(ErrorResponse{
+            error: get_translation("err.404.title", &user_req_data.lang, None),
+            code: 404
+        })),
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            Err(return_internal_err_json())
+        }
+    }
+}
+
+
+/**
+ * The developer's own client sites, with their status and usage,
+ * and the form to register a new one.
+ */
+pub async fn developer_page(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::DeveloperAccess) {
+        return redirect_resp;
+    }
+
+    let texts: DeveloperTexts = DeveloperTexts::new(&user_req_data);
+    let usage_since: time::OffsetDateTime =
+        time::OffsetDateTime::now_utc() - time::Duration::days(config::developer_usage_days());
+
+    let clients: Vec<DeveloperClientView> = match db::get_developer_clients(
+        &pool,
+        user_req_data.id.unwrap_or_default(),
+        usage_since
+    ).await {
+        Ok(rows) => rows.into_iter()
+            .map(|row| DeveloperClientView::new(row, &texts))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error retrieving developer clients: {e}");
+            return return_error_page(&req, 500);
+        }
+    };
+
+    let developer_template: DeveloperTemplate = DeveloperTemplate {
+        texts,
+        user: user_req_data,
+        clients,
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(developer_template.render().unwrap())
+}
+
+
+/**
+ * A developer registers a client site. It's theirs, and it waits
+ * (switched off) in the review queue. They get the raw secret ONCE.
+ */
+#[post("/add_client")]
+async fn developer_add_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    mut inputs: web::Json<ClientInputs>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::DeveloperAccess) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    inputs.trim_all_strings();
+
+    if let Some(problem_resp) = client_inputs_problem(&inputs) {
+        return problem_resp;
+    }
+
+    match db::get_client_by_client_id(&pool, &inputs.client_id).await {
+        Ok(None) => {},
+        Ok(Some(_client)) => {
+            return HttpResponse::Conflict().json(ErrorResponse{
+                error: get_translation("err.client_id_taken", &user_req_data.lang, None),
+                code: 409
+            });
+        },
+        Err(_e) => return return_internal_err_json()
+    }
+
+    let (raw_client_secret, hashed_secret) = match new_client_secret(inputs.get_client_type()).await {
+        Ok(secret) => secret,
+        Err(error_response) => return error_response
+    };
+
+    let client_data: db::NewClientData = db::NewClientData {
+        site_domain: inputs.site_domain.to_owned(),
+        site_name: inputs.site_name.to_owned(),
+        client_id: inputs.client_id.to_owned(),
+        redirect_uri: inputs.redirect_uri.to_owned(),
+        hashed_client_secret: hashed_secret,
+        logo_url: inputs.logo_url.to_owned(),
+        description: inputs.description.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
+        is_active: false, // until an admin approves it
+        owner_user_id: user_req_data.id,
+        status: String::from(db::CLIENT_PENDING),
+        registration_token_hash: None,
+        initial_access_token_id: None,
+    };
+
+    match db::add_external_client(&pool, client_data).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientCreated, None, Some(&inputs.client_id),
+                    &format!("{} (pending review)", inputs.site_name)).await;
+
+                HttpResponse::Ok().json(RawClientSecret { raw_client_secret })
+            } else {
+                return_internal_err_json()
+            }
+        },
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * A developer edits their own client site. They can't switch it on or off.
+ * A new domain, redirect_uri or client type on an approved site sends it
+ * back to the review queue (and signs everyone out of it).
+ */
+#[post("/update_client")]
+async fn developer_update_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    mut inputs: web::Json<ClientInputs>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::DeveloperAccess) {
+        return redirect_resp;
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    inputs.trim_all_strings();
+
+    if let Some(problem_resp) = client_inputs_problem(&inputs) {
+        return problem_resp;
+    }
+
+    let client: db::ClientData =
+        match get_owned_client(&pool, &user_req_data, &inputs.client_id).await {
+            Ok(client) => client,
+            Err(resp) => return resp
+        };
+
+    let back_to_review: bool = client.is_approved() && (
+        client.redirect_uri != inputs.redirect_uri ||
+        client.domain != inputs.site_domain ||
+        client.client_type != inputs.get_client_type());
+
+    let client_data: db::UpdateClientData = db::UpdateClientData {
+        site_domain: inputs.site_domain.to_owned(),
+        site_name: inputs.site_name.to_owned(),
+        client_id: inputs.client_id.to_owned(),
+        redirect_uri: inputs.redirect_uri.to_owned(),
+        logo_url: inputs.logo_url.to_owned(),
+        description: inputs.description.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
+        is_active: client.get_is_active(), // not theirs to change
+    };
+
+    match db::update_owned_client(
+        &pool,
+        client_data,
+        user_req_data.id.unwrap_or_default(),
+        back_to_review
+    ).await {
+        Ok(rows_affected) => {
+            if rows_affected == 0 {
+                return return_internal_err_json();
+            }
+
+            let mut details: String = String::from("by owner");
+
+            if back_to_review {
+                match db::delete_all_client_sessions(&pool, &inputs.client_id).await {
+                    Ok(revoked) => details.push_str(
+                        &format!(", back to review, {revoked} sessions revoked")),
+                    Err(e) => {
+                        eprintln!("Database error: {e}");
+                        return return_internal_err_json();
+                    }
+                }
+            }
+
+            audit::record(
+                &pool, &req, AuditEvent::ClientUpdated,
+                None, Some(&inputs.client_id), &details).await;
+
+            HttpResponse::Ok().json(UpdateData::new(true))
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * A developer rotates their own client site's secret.
+ * Same as the admin's: the raw secret is shown ONCE.
+ */
+#[post("/req_new_client_secret")]
+async fn developer_secret_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientId>
+) -> HttpResponse {
+    let user_req_data: auth::UserReqData = auth::get_user_req_data(&req);
+    if let Some(redirect_resp) =
+        require_permission(&user_req_data, &req, Permission::DeveloperAccess) {
+        return redirect_resp;
+    }
+    if user_req_data.is_impersonated() {
+        return refuse_while_impersonating(&user_req_data.lang);
+    }
+    if let Some(step_up_resp) = require_step_up(&user_req_data) {
+        return step_up_resp;
+    }
+
+    match get_owned_client(&pool, &user_req_data, &inputs.client_id).await {
+        Ok(client) if !client.client_type.has_secret() => {
+            return no_client_secret_json(&user_req_data.lang);
+        },
+        Ok(_client) => {},
+        Err(resp) => return resp
+    }
+
+    let raw_client_secret: String = utils::generate_client_secret();
+    let hashed_client_secret: String = match hash_pool::hash_password(raw_client_secret.to_owned()).await {
+        Ok(hash) => hash,
+        Err(e) => return hash_pool_err_json(&e)
+    };
+
+    match db::update_client_secret(&pool, &inputs.client_id, &hashed_client_secret).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::SecretRotated,
+                    None, Some(&inputs.client_id), "by owner").await;
+
+                HttpResponse::Ok().json(RawClientSecret { raw_client_secret })
+            } else {
+                return_internal_err_json()
+            }
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+
 /* 
  * 
  * 
@@ -1100,13 +3523,10 @@ async fn error_root_2() -> HttpResponse {
 #[post("/verify_auth_code")]
 async fn verify_auth_code(
     pool: web::Data<MySqlPool>,
+    req: HttpRequest,
     inputs: web::Json<AuthCodeRequest>
 ) -> HttpResponse {
 
-    println!("Code: {}", inputs.code);
-    println!("Id: {}", inputs.client_id);
-    println!("Secret: {}", inputs.client_secret);
-
     /* 
      * From DB gather:
      * * THINGS TO CHECK
@@ -1133,7 +3553,12 @@ async fn verify_auth_code(
             Ok(option) => {
                 match option {
                     Some(data) => data,
-                    None => { return return_not_found_err_json() }
+                    None => {
+                        audit::record(
+                            &pool, &req, AuditEvent::TokenRedemptionFailure,
+                            None, Some(&inputs.client_id), "unknown code").await;
+                        return return_not_found_err_json()
+                    }
                 }
             },
             Err(_e) => { return return_internal_err_json() }
@@ -1141,32 +3566,82 @@ async fn verify_auth_code(
 
     // Make sure it's not expired
     if auth_code_data.is_expired() {
-        eprint!("Expired auth code");
-        println!("auth code id: {}", auth_code_data.id);
+        audit::record(
+            &pool, &req, AuditEvent::TokenRedemptionFailure,
+            Some(auth_code_data.user_id), Some(&inputs.client_id), "expired code").await;
         return return_authentication_err_json();
     }
 
     // GOT the auth_code_data. Now check it against the input data
-    // make sure client_id and client_secret are the right ones.
+    // make sure client_id and client_secret are the right ones,
+    // and the code_verifier if the login came with a code_challenge.
 
-    let hashed_client_secret: String =
-        match db::get_client_secret(&pool, &inputs.client_id).await {
-            Ok(option) => {
-                match option {
-                    Some(secret_obj) => secret_obj.hashed_client_secret,
-                    None => { return return_not_found_err_json() }
-                }
-            },
-            Err(_e) => { return return_internal_err_json() }
+    let secrets_match: bool =
+        match verify_code_client(
+            &pool,
+            &inputs.client_id,
+            &inputs.client_secret,
+            auth_code_data.code_challenge.as_ref(),
+            &inputs.code_verifier
+        ).await {
+            Ok(matches) => matches,
+            Err(error_response) => return error_response
         };
 
-    let secrets_match: bool = auth::verify_password(&inputs.client_secret, &hashed_client_secret);
     let client_ids_match: bool = inputs.client_id == auth_code_data.client_id;
 
     // TODO: check auth_code EXPIRY date
 
     if secrets_match && client_ids_match {
-        println!("SUCCESS: ALL MATCH");
+
+        // The client site may have been deactivated since the code was issued
+        match db::is_client_active(&pool, &auth_code_data.client_id).await {
+            Ok(true) => {},
+            Ok(false) => {
+                audit::record(
+                    &pool, &req, AuditEvent::TokenRedemptionFailure,
+                    Some(auth_code_data.user_id), Some(&inputs.client_id),
+                    "client inactive").await;
+                return HttpResponse::Forbidden().json(ErrorResponse {
+                    error: String::from("Client Inactive"),
+                    code: 403
+                });
+            },
+            Err(_e) => return return_internal_err_json()
+        }
+
+        // The user may have been suspended since they logged in
+        match db::get_active_suspension(&pool, auth_code_data.user_id).await {
+            Ok(Some(_suspension)) => {
+                audit::record(
+                    &pool, &req, AuditEvent::TokenRedemptionFailure,
+                    Some(auth_code_data.user_id), Some(&inputs.client_id),
+                    "account suspended").await;
+                return HttpResponse::Forbidden().json(ErrorResponse {
+                    error: String::from("Account Suspended"),
+                    code: 403
+                });
+            },
+            Ok(None) => {},
+            Err(_e) => return return_internal_err_json()
+        }
+
+        // Or blocked from this one client site
+        match db::get_active_client_restriction(
+            &pool, &auth_code_data.client_id, auth_code_data.user_id).await {
+            Ok(Some(_restriction)) => {
+                audit::record(
+                    &pool, &req, AuditEvent::TokenRedemptionFailure,
+                    Some(auth_code_data.user_id), Some(&inputs.client_id),
+                    "client restriction").await;
+                return HttpResponse::Forbidden().json(ErrorResponse {
+                    error: String::from("User Restricted"),
+                    code: 403
+                });
+            },
+            Ok(None) => {},
+            Err(_e) => return return_internal_err_json()
+        }
 
         let username_and_role: db::UsernameAndRole =
             match db::get_username_and_role_by_id(&pool, auth_code_data.user_id).await {
@@ -1179,36 +3654,62 @@ async fn verify_auth_code(
                 Err(_e) => { return return_internal_err_json() }
             };
 
+        // Use the code up before it buys a session. Every redemption would
+        // otherwise make another one, so of two at once only one may win.
+        match db::use_auth_code(&pool, &inputs.code).await {
+            Ok(true) => {},
+            Ok(false) => {
+                audit::record(
+                    &pool, &req, AuditEvent::TokenRedemptionFailure,
+                    Some(auth_code_data.user_id), Some(&inputs.client_id),
+                    "code already used").await;
+                return return_authentication_err_json();
+            },
+            Err(_e) => return return_internal_err_json()
+        }
+
         // CREATE the refresh token and save to DB
         // create a refresh_token and put it in the DB
+        // The session belongs to the device that logged in, not the client's server
         let refresh_token: String = match db::add_refresh_token(
             &pool,
             auth_code_data.user_id,
-            auth_code_data.client_id,
-            auth::generate_refresh_token()
+            auth_code_data.client_id.to_owned(),
+            auth::generate_refresh_token(),
+            &auth_code_data.get_session_device()
         ).await {
             Ok(refresh_token) => refresh_token,
             Err(_e) =>  return return_internal_err_json()
         };
 
+        let client_roles: Vec<String> = match db::get_client_user_roles(
+            &pool, &auth_code_data.client_id, auth_code_data.user_id).await {
+            Ok(roles) => roles,
+            Err(_e) => return return_internal_err_json()
+        };
+
         let user_data: AuthCodeSuccess = AuthCodeSuccess {
             user_id: auth_code_data.user_id,
             username: username_and_role.username,
             user_role: username_and_role.role,
-            refresh_token
+            refresh_token,
+            client_roles
         };
 
-        // now DELETE the auth token
-
-        println!("SUCCESS: SENDING");
+        audit::record(
+            &pool, &req, AuditEvent::TokenRedeemed,
+            Some(auth_code_data.user_id), Some(&auth_code_data.client_id), "").await;
 
         return HttpResponse::Ok()
             .json(user_data);
     }
 
-
-    println!("FAILURE: NO MATCH");
     // RETURN FAILURE
+    let details: &str = if client_ids_match { "wrong client secret" } else { "client_id mismatch" };
+    audit::record(
+        &pool, &req, AuditEvent::TokenRedemptionFailure,
+        Some(auth_code_data.user_id), Some(&inputs.client_id), details).await;
+
     return_authentication_err_json()
 }
 
@@ -1230,27 +3731,566 @@ async fn check_refresh(
 
     // get the inputs and check them all
 
+    // find the session this token belongs to
     let r_db_token: db::RefreshToken =
-        match db::get_refresh_token(
-            &pool,
-            inputs.user_id,
-            inputs.client_id.to_owned()
-        ).await {
+        match db::get_refresh_token(&pool, &inputs.token).await {
             Ok(option) => {
                 match option {
                     Some(token) => token,
-                    None => return err_response
+                    None => {
+                        return HttpResponse::Ok()
+                            .json(RefreshCheckResponse::Ok(RefreshCheckSuccess::new(false)));
+                    }
                 }
             }, Err(_e) => return err_response
         };
 
-    let token_is_valid: bool = 
-        inputs.token.as_str() == r_db_token.get_token() &&
+    let mut token_is_valid: bool =
+        r_db_token.get_user_id() == inputs.user_id &&
+        r_db_token.get_client_id() == &inputs.client_id &&
         !r_db_token.is_expired();
 
+    // A deactivated client's sessions are deleted too, same race
+    if token_is_valid {
+        match db::is_client_active(&pool, &inputs.client_id).await {
+            Ok(active) => token_is_valid = active,
+            Err(_e) => return err_response
+        }
+    }
+
+    // A restricted user's sessions are deleted, but one could slip in while it's set
+    if token_is_valid {
+        match db::get_active_client_restriction(
+            &pool, &inputs.client_id, inputs.user_id).await {
+            Ok(restriction) => token_is_valid = restriction.is_none(),
+            Err(_e) => return err_response
+        }
+    }
+
+    if !token_is_valid {
+        return HttpResponse::Ok()
+            .json(RefreshCheckResponse::Ok(RefreshCheckSuccess::new(false)));
+    }
+
+    // the client's server is asking, so we don't know the device's IP. Keep the old one.
+    if let Err(e) = db::touch_refresh_token(
+        &pool, r_db_token.get_id(), r_db_token.get_ip_address()).await {
+        eprintln!("Database error: {e}");
+    }
+
+    // Current roles, so the client can put them in its fresh access token
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(&pool, &inputs.client_id, inputs.user_id).await {
+            Ok(roles) => roles,
+            Err(_e) => return err_response
+        };
+
     let token_response: RefreshCheckResponse =
-        RefreshCheckResponse::Ok(RefreshCheckSuccess::new(token_is_valid));
+        RefreshCheckResponse::Ok(RefreshCheckSuccess::with_client_roles(true, client_roles));
+
+    HttpResponse::Ok()
+        .json(token_response)
+}
+
+
+
+/**
+ * A client site's backend blocks one of its users (ie: caught cheating).
+ * They're signed out of that site and get no more auth codes or
+ * refresh tokens for it until the restriction expires or is removed.
+ */
+#[post("/restrict_user")]
+async fn restrict_user(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<RestrictUserRequest>
+) -> HttpResponse {
+    let secrets_match: bool =
+        match verify_client_secret(&pool, &inputs.client_id, &inputs.client_secret).await {
+            Ok(matches) => matches,
+            Err(error_response) => return error_response
+        };
+
+    if !secrets_match {
+        return return_authentication_err_json();
+    }
+
+    let (reason, expires_timestamp) = match validate_reason_and_days(
+        &inputs.reason, inputs.expires_in_days, &utils::SupportedLangs::English) {
+        Ok(valid) => valid,
+        Err(_error_response) => {
+            return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+                .json(RestrictionResponse::Err(ClientApiError {
+                    error_code: 422,
+                    message: String::from("reason must be 1-500 characters, expires_in_days 1-3650")
+                }));
+        }
+    };
+
+    match db::get_user_by_id(&pool, inputs.user_id).await {
+        Ok(Some(_user)) => {},
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    }
+
+    if let Err(e) = db::add_client_restriction(
+        &pool, &inputs.client_id, inputs.user_id, &reason, None, expires_timestamp).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    if let Err(e) = db::delete_client_sessions(&pool, inputs.user_id, &inputs.client_id).await {
+        eprintln!("Database error: {e}");
+    }
+
+    audit::record(
+        &pool, &req, AuditEvent::ClientRestrictionAdded,
+        Some(inputs.user_id), Some(&inputs.client_id), &reason).await;
+
+    HttpResponse::Ok().json(RestrictionResponse::Ok(RestrictionUpdated { success: true }))
+}
+
+
+/**
+ * A client site's backend lifts its restrictions on one of its users.
+ * success is false if there weren't any.
+ */
+#[post("/unrestrict_user")]
+async fn unrestrict_user(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<UnrestrictUserRequest>
+) -> HttpResponse {
+    let secrets_match: bool =
+        match verify_client_secret(&pool, &inputs.client_id, &inputs.client_secret).await {
+            Ok(matches) => matches,
+            Err(error_response) => return error_response
+        };
+
+    if !secrets_match {
+        return return_authentication_err_json();
+    }
+
+    match db::delete_client_restrictions(&pool, &inputs.client_id, inputs.user_id).await {
+        Ok(rows_affected) => {
+            if rows_affected > 0 {
+                audit::record(
+                    &pool, &req, AuditEvent::ClientRestrictionRemoved,
+                    Some(inputs.user_id), Some(&inputs.client_id), "").await;
+            }
+            HttpResponse::Ok().json(
+                RestrictionResponse::Ok(RestrictionUpdated { success: rows_affected > 0 }))
+        },
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+/**
+ * A client site's backend gives one of its users a role on that site.
+ * The role names are the client's own: we just store them.
+ */
+#[post("/add_user_role")]
+async fn add_user_role(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientRoleRequest>
+) -> HttpResponse {
+    update_user_role(&pool, &req, &inputs, true).await
+}
+
+
+#[post("/remove_user_role")]
+async fn remove_user_role(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    inputs: web::Json<ClientRoleRequest>
+) -> HttpResponse {
+    update_user_role(&pool, &req, &inputs, false).await
+}
+
+
+/**
+ * Both client role routes: check the client's secret and the role name,
+ * then add or remove the role.
+ * success is false if nothing changed (they already had it, or never did).
+ */
+async fn update_user_role(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    inputs: &ClientRoleRequest,
+    adding: bool
+) -> HttpResponse {
+    let secrets_match: bool =
+        match verify_client_secret(pool, &inputs.client_id, &inputs.client_secret).await {
+            Ok(matches) => matches,
+            Err(error_response) => return error_response
+        };
+
+    if !secrets_match {
+        return return_authentication_err_json();
+    }
+
+    if !utils::validate_role_name(&inputs.role_name) {
+        return HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY)
+            .json(ClientRoleResponse::Err(ClientApiError {
+                error_code: 422,
+                message: String::from("role_name must be 1-50 of: a-z 0-9 _ . -")
+            }));
+    }
+
+    match db::get_user_by_id(pool, inputs.user_id).await {
+        Ok(Some(_user)) => {},
+        Ok(None) => return return_not_found_err_json(),
+        Err(_e) => return return_internal_err_json()
+    }
+
+    let update_result: Result<bool, anyhow::Error> = if adding {
+        db::add_client_user_role(
+            pool, &inputs.client_id, inputs.user_id, &inputs.role_name, None).await
+    } else {
+        db::remove_client_user_role(
+            pool, &inputs.client_id, inputs.user_id, &inputs.role_name).await
+    };
+
+    let changed: bool = match update_result {
+        Ok(changed) => changed,
+        Err(_e) => return return_internal_err_json()
+    };
+
+    if changed {
+        let event: AuditEvent =
+            if adding { AuditEvent::ClientRoleGranted } else { AuditEvent::ClientRoleRevoked };
+        audit::record(
+            pool, req, event, Some(inputs.user_id), Some(&inputs.client_id),
+            &inputs.role_name).await;
+    }
+
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(pool, &inputs.client_id, inputs.user_id).await {
+            Ok(roles) => roles,
+            Err(_e) => return return_internal_err_json()
+        };
+
+    HttpResponse::Ok().json(ClientRoleResponse::Ok(ClientRolesUpdated {
+        success: changed,
+        client_roles
+    }))
+}
+
+
+
+/* 
+ * 
+ * 
+ * 
+ * 
+ * =================================
+ * =================================
+ * =====                       =====
+ * =====  CLIENT REGISTRATION  =====
+ * =====                       =====
+ * =================================
+ * =================================
+ * 
+ * 
+ * 
+ * Clients registering themselves with a JSON request (RFC 7591),
+ * ie: test environments and game jams, instead of the admin's form.
+ * 
+ * POST /oauth/register needs an initial access token an admin issued
+ * (/admin/registration_tokens) as a bearer token. The client gets its
+ * client_id, its secret and a registration access token, each ONCE.
+ * With that last one it reads, updates or deletes its own registration
+ * at /oauth/register/{client_id} (RFC 7592).
+ * 
+ * Same checks as the admin's new client form (client_inputs_error).
+ * No CSRF check on /oauth: these come from servers, not browsers.
+ * 
+ * 
+ */
+
+
+#[post("/register")]
+async fn register_client_post(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    registration: web::Json<ClientRegistrationRequest>
+) -> HttpResponse {
+    let initial_access_token: String = match bearer_token(&req) {
+        Some(token) => token,
+        None => return registration_err_json(
+            StatusCode::UNAUTHORIZED, "invalid_token", "Initial access token required")
+    };
+
+    let client_id: String = utils::generate_client_id();
+    let inputs: ClientInputs = ClientInputs::from_registration(&registration, &client_id);
+
+    // Check before spending a use of the token
+    if let Some(problem_resp) = registration_inputs_problem(&registration, &inputs) {
+        return problem_resp;
+    }
+
+    let initial_access_token_id: i32 = match db::use_initial_access_token(
+        &pool,
+        &auth::hash_registration_token(&initial_access_token)
+    ).await {
+        Ok(Some(id)) => id,
+        Ok(None) => return registration_err_json(
+            StatusCode::UNAUTHORIZED, "invalid_token",
+            "Initial access token is unknown, used up, expired or revoked"),
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return return_internal_err_json();
+        }
+    };
+
+    let (raw_client_secret, hashed_secret) = match new_client_secret(inputs.get_client_type()).await {
+        Ok(secret) => secret,
+        Err(error_response) => return error_response
+    };
+
+    let registration_access_token: String = auth::generate_registration_token();
+
+    let client_data: db::NewClientData = db::NewClientData {
+        site_domain: inputs.site_domain.to_owned(),
+        site_name: inputs.site_name.to_owned(),
+        client_id: client_id.to_owned(),
+        redirect_uri: inputs.redirect_uri.to_owned(),
+        hashed_client_secret: hashed_secret,
+        logo_url: inputs.logo_url.to_owned(),
+        description: inputs.description.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
+        is_active: true, // the admin who issued the token vouched for it
+        owner_user_id: None,
+        status: String::from(db::CLIENT_APPROVED),
+        registration_token_hash: Some(auth::hash_registration_token(&registration_access_token)),
+        initial_access_token_id: Some(initial_access_token_id),
+    };
+
+    if let Err(e) = db::add_external_client(&pool, client_data).await {
+        eprintln!("Error: {e}");
+        return return_internal_err_json();
+    }
+
+    let client: db::ClientData = match db::get_client_by_client_id(&pool, &client_id).await {
+        Ok(Some(client)) => client,
+        Ok(None) => return return_internal_err_json(),
+        Err(_e) => return return_internal_err_json()
+    };
+
+    audit::record(
+        &pool, &req, AuditEvent::ClientRegistered, None, Some(&client_id),
+        &format!("{} (initial access token #{initial_access_token_id})", client.name)).await;
+
+    HttpResponse::Created()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(registration_response(
+            &client,
+            Some(raw_client_secret).filter(|secret| !secret.is_empty()),
+            Some(registration_access_token)))
+}
+
+
+/**
+ * The client this request's registration access token manages.
+ * Otherwise the 401 to send back.
+ */
+async fn get_registered_client(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    client_id: &String
+) -> Result<db::ClientData, HttpResponse> {
+    let registration_access_token: String = match bearer_token(req) {
+        Some(token) => token,
+        None => return Err(registration_err_json(
+            StatusCode::UNAUTHORIZED, "invalid_token", "Registration access token required"))
+    };
+
+    match db::get_client_by_registration_token(
+        pool,
+        client_id,
+        &auth::hash_registration_token(&registration_access_token)
+    ).await {
+        Ok(Some(client)) => Ok(client),
+        Ok(None) => Err(registration_err_json(
+            StatusCode::UNAUTHORIZED, "invalid_token", "Invalid registration access token")),
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            Err(return_internal_err_json())
+        }
+    }
+}
+
+
+#[get("/register/{client_id}")]
+async fn read_registration(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    client_id: web::Path<String>
+) -> HttpResponse {
+    match get_registered_client(&pool, &req, &client_id).await {
+        Ok(client) => HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(registration_response(&client, None, None)),
+        Err(resp) => resp
+    }
+}
+
+
+/**
+ * Replace the client's metadata. Whether it's switched on stays the admin's call.
+ */
+#[put("/register/{client_id}")]
+async fn update_registration(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    client_id: web::Path<String>,
+    registration: web::Json<ClientRegistrationRequest>
+) -> HttpResponse {
+    let client: db::ClientData = match get_registered_client(&pool, &req, &client_id).await {
+        Ok(client) => client,
+        Err(resp) => return resp
+    };
+
+    let inputs: ClientInputs = ClientInputs::from_registration(&registration, &client.client_id);
+
+    if let Some(problem_resp) = registration_inputs_problem(&registration, &inputs) {
+        return problem_resp;
+    }
+
+    // Nobody reviews these, so how the client authenticates is fixed at registration
+    if inputs.get_client_type() != client.client_type {
+        return registration_err_json(
+            StatusCode::BAD_REQUEST,
+            "invalid_client_metadata",
+            "client_type can't be changed. Register a new client instead.");
+    }
+
+    let client_data: db::UpdateClientData = db::UpdateClientData {
+        site_domain: inputs.site_domain.to_owned(),
+        site_name: inputs.site_name.to_owned(),
+        client_id: client.client_id.to_owned(),
+        redirect_uri: inputs.redirect_uri.to_owned(),
+        logo_url: inputs.logo_url.to_owned(),
+        description: inputs.description.to_owned(),
+        category: inputs.get_category(),
+        client_type: inputs.get_client_type(),
+        is_active: client.get_is_active(),
+    };
+
+    if let Err(e) = db::update_external_client(&pool, client_data).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    audit::record(
+        &pool, &req, AuditEvent::ClientUpdated,
+        None, Some(&client.client_id), "via /oauth/register").await;
+
+    match db::get_client_by_client_id(&pool, &client.client_id).await {
+        Ok(Some(updated)) => HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(registration_response(&updated, None, None)),
+        Ok(None) => return_internal_err_json(),
+        Err(_e) => return_internal_err_json()
+    }
+}
+
+
+// For good, like the admin's delete (sessions, roles, restrictions go with it)
+#[delete("/register/{client_id}")]
+async fn delete_registration(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    client_id: web::Path<String>
+) -> HttpResponse {
+    let client: db::ClientData = match get_registered_client(&pool, &req, &client_id).await {
+        Ok(client) => client,
+        Err(resp) => return resp
+    };
+
+    match db::delete_client(&pool, &client.client_id).await {
+        Ok(_deleted) => {
+            audit::record(
+                &pool, &req, AuditEvent::ClientDeleted,
+                None, Some(&client.client_id), "via /oauth/register").await;
+            HttpResponse::NoContent().finish()
+        },
+        Err(e) => {
+            eprintln!("Database error: {e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{ App, test };
+
+    // RFC 7636, appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    // A user, a public client, and one auth code (with PKCE) for them
+    async fn issue_code(pool: &MySqlPool) -> String {
+        let user_id: u64 = sqlx::query(
+            "INSERT INTO users (username, email, password_hash)
+                VALUES ('replay', 'replay@example.com', '')")
+            .execute(pool).await.unwrap()
+            .last_insert_id();
+
+        sqlx::query(
+            "INSERT INTO client_sites (client_id, name, domain, redirect_uri, client_type, category)
+                VALUES ('replay_client', 'Replay', 'replay.example.com',
+                    'https://replay.example.com/auth', 'public', 'game')")
+            .execute(pool).await.unwrap();
+
+        let device: db::SessionDevice = db::SessionDevice {
+            device_label: String::from("Firefox on Linux"),
+            user_agent: String::from("Mozilla/5.0 (X11; Linux x86_64) Firefox/140.0"),
+            ip_address: String::from("203.0.113.7"),
+        };
+
+        db::add_auth_code(
+            pool,
+            user_id as i32,
+            &String::from("replay_client"),
+            auth::generate_auth_code(),
+            Some(&String::from(CHALLENGE)),
+            &device
+        ).await.unwrap()
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server at DATABASE_URL"]
+    async fn auth_code_is_redeemed_only_once(pool: MySqlPool) {
+        let code: String = issue_code(&pool).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(verify_auth_code)
+        ).await;
+
+        let redeem = || test::TestRequest::post()
+            .uri("/verify_auth_code")
+            .set_json(AuthCodeRequest {
+                client_id: String::from("replay_client"),
+                client_secret: String::new(),
+                code: code.to_owned(),
+                code_verifier: String::from(VERIFIER),
+            })
+            .to_request();
+
+        assert_eq!(test::call_service(&app, redeem()).await.status(), StatusCode::OK);
+        assert!(test::call_service(&app, redeem()).await.status().is_client_error());
 
-    return HttpResponse::Ok()
-        .json(token_response);
-}
\ No newline at end of file
+        // The replay made no second session
+        let sessions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM refresh_tokens")
+            .fetch_one(&pool).await.unwrap();
+        assert_eq!(sessions, 1);
+    }
+}
@@ -27,12 +27,20 @@ use sqlx::{MySqlPool };
 
 // local modules, loaded as crates (declared as mods in main.rs)
 use crate::{
-    db, utils,
-    auth::{ self, UserReqData },
+    db, utils, config, mailer,
+    audit::{ self, AuditEvent },
+    auth::{ self, UserReqData, PasswordVerification },
+    hash_pool::{ self, HashPoolError },
+    rbac::Permission,
+    resources::get_translation,
+    auth_code_shared::{
+        ClientRegistrationRequest, ClientRegistrationResponse, ClientRegistrationError
+    },
     resource_mgr::{
         HomeTexts, LoginTexts, RegisterTexts, AdminTexts,
         ErrorTexts, EditClientTexts, NewClientTexts, DashboardTexts,
-        ErrorData
+        AuditTexts, RecoveryTexts, SuspensionTexts, SuspendedTexts,
+        DeveloperTexts, ClientReviewTexts, RegistrationTokenTexts, ErrorData
      }
 };
 
@@ -86,6 +94,7 @@ pub struct BadRegistrationInputs {
     pub email_valid: bool,
     pub username_valid: bool,
     pub password_valid: bool,
+    pub password_problems: Vec<String>,
     pub code: u16,
 }
 
@@ -105,10 +114,64 @@ pub struct RawClientSecret {
 #[derive(Serialize)]
 pub struct BadPassword {
     pub password_valid: bool,
+    pub password_problems: Vec<String>,
     pub code: u16,
 }
 
 
+// Password change was refused because the current password was wrong
+#[derive(Serialize)]
+pub struct WrongCurrentPassword {
+    pub current_password_valid: bool,
+    pub code: u16,
+}
+
+
+// Confirm the password (POST /auth/step_up) and send the request again
+#[derive(Serialize)]
+pub struct StepUpRequired {
+    pub error: String,
+    pub code: u16,
+    pub step_up_required: bool,
+}
+
+
+// Solve a proof-of-work challenge (GET /auth/challenge) and send the form again
+// An initial access token, shown to the admin once
+#[derive(Serialize)]
+pub struct RawRegistrationToken {
+    pub token: String,
+}
+
+
+#[derive(Serialize)]
+pub struct ChallengeRequired {
+    pub error: String,
+    pub code: u16,
+    pub challenge_required: bool,
+}
+
+
+// Login refused: the account is suspended. The page at redirect_uri explains why.
+#[derive(Serialize)]
+pub struct AccountSuspended {
+    pub error: String,
+    pub code: u16,
+    pub suspended: bool,
+    pub redirect_uri: String,
+}
+
+
+// Login refused: the client site is inactive. The page at redirect_uri says so.
+#[derive(Serialize)]
+pub struct ClientInactive {
+    pub error: String,
+    pub code: u16,
+    pub client_inactive: bool,
+    pub redirect_uri: String,
+}
+
+
 // Upon successful Registration or login, send back auth token (JWT token)
 #[derive(Serialize)]
 pub struct FreshLoginData {
@@ -116,6 +179,14 @@ pub struct FreshLoginData {
 }
 
 
+// Registration is waiting for the user to click the link in their email
+#[derive(Serialize)]
+pub struct ConfirmationSent {
+    pub confirmation_sent: bool,
+    pub message: String,
+}
+
+
 #[derive(Serialize)]
 pub struct LogoutData {
     pub logout: bool,
@@ -133,18 +204,351 @@ pub struct FullRedirectUri {
 }
 
 
+// One session in the dashboard's session list.
+// Times are unix timestamps (seconds) so the browser can show them in local time.
+#[derive(Serialize)]
+pub struct SessionItem {
+    pub id: i32,
+    pub client_name: String,
+    pub client_logo_url: String,
+    pub device_label: String,
+    pub ip_address: String,
+    pub created: i64,
+    pub last_used: i64,
+    pub expires: i64,
+    pub is_current: bool, // the session making this request
+}
+
+
+#[derive(Serialize)]
+pub struct SessionList {
+    pub sessions: Vec<SessionItem>,
+}
+
+
+// One audit event, ready for a template (translated name, readable time)
+pub struct AuditEventView {
+    pub time: String,
+    pub event_type: String,
+    pub event_label: String,
+    pub username: String,
+    pub actor_id: String,
+    pub client_id: String,
+    pub ip_address: String,
+    pub device_label: String,
+    pub details: String,
+}
+
+
 impl LogoutData {
     pub fn new() -> Self {
         LogoutData { logout: true } }
 }
 
 
+impl ConfirmationSent {
+    pub fn new(lang: &utils::SupportedLangs) -> Self {
+        ConfirmationSent {
+            confirmation_sent: true,
+            message: get_translation("register.confirmation_sent", lang, None),
+        }
+    }
+}
+
+
 impl UpdateData {
     pub fn new(success: bool) -> Self {
         UpdateData { success } }
 }
 
 
+// One suspension in force, ready for the admin's table
+pub struct SuspensionView {
+    pub user_id: i32,
+    pub username: String,
+    pub reason: String,
+    pub issued_by: String,
+    pub start: String,
+    pub end: String,
+}
+
+
+impl SuspensionView {
+    pub fn new(row: db::SuspensionRow, indefinite: &String) -> Self {
+        SuspensionView {
+            user_id: row.user_id,
+            username: row.username,
+            reason: row.reason,
+            issued_by: row.issued_by_username.unwrap_or_default(),
+            start: utils::format_utc(&row.start_timestamp),
+            end: match row.end_timestamp {
+                Some(end) => utils::format_utc(&end),
+                None => indefinite.to_owned()
+            },
+        }
+    }
+}
+
+
+// One client restriction in force, for the edit client page
+pub struct ClientRestrictionView {
+    pub user_id: i32,
+    pub username: String,
+    pub reason: String,
+    pub issued_by: String,
+    pub start: String,
+    pub end: String,
+}
+
+
+impl ClientRestrictionView {
+    pub fn new(row: db::ClientRestrictionRow, texts: &EditClientTexts) -> Self {
+        ClientRestrictionView {
+            user_id: row.user_id,
+            username: row.username,
+            reason: row.reason,
+            // No admin: the client site's backend set it
+            issued_by: row.issued_by_username.unwrap_or_else(|| texts.by_client.to_owned()),
+            start: utils::format_utc(&row.created_timestamp),
+            end: match row.expires_timestamp {
+                Some(end) => utils::format_utc(&end),
+                None => texts.indefinite.to_owned()
+            },
+        }
+    }
+}
+
+
+// One role on a client site, for the edit client page
+pub struct ClientRoleView {
+    pub user_id: i32,
+    pub username: String,
+    pub role_name: String,
+    pub granted_by: String,
+    pub since: String,
+}
+
+
+impl ClientRoleView {
+    pub fn new(row: db::ClientUserRoleRow, texts: &EditClientTexts) -> Self {
+        ClientRoleView {
+            user_id: row.user_id,
+            username: row.username,
+            role_name: row.role_name,
+            // No admin: the client site's backend gave it
+            granted_by: row.granted_by_username.unwrap_or_else(|| texts.by_client.to_owned()),
+            since: utils::format_utc(&row.created_timestamp),
+        }
+    }
+}
+
+
+// One user in the admin's search results
+pub struct AdminUserView {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub email_verified: bool,
+    pub created: String,
+}
+
+
+impl AdminUserView {
+    pub fn new(row: db::UserListRow) -> Self {
+        AdminUserView {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            role: row.role,
+            email_verified: row.email_verified != 0,
+            created: utils::format_utc(&row.created_timestamp),
+        }
+    }
+}
+
+
+// One of a user's sessions, for the admin's user page
+pub struct AdminSessionView {
+    pub id: i32,
+    pub client_name: String,
+    pub device_label: String,
+    pub ip_address: String,
+    pub last_used: String,
+    pub expires: String,
+}
+
+
+impl AdminSessionView {
+    pub fn new(row: db::SessionRow) -> Self {
+        AdminSessionView {
+            id: row.id,
+            client_name: row.client_name,
+            device_label: row.device_label,
+            ip_address: row.ip_address,
+            last_used: utils::format_utc(&row.last_used_timestamp),
+            expires: utils::format_utc(&row.expires_timestamp),
+        }
+    }
+}
+
+
+// One of a developer's own client sites, for the developer portal
+pub struct DeveloperClientView {
+    pub client_id: String,
+    pub name: String,
+    pub domain: String,
+    pub redirect_uri: String,
+    pub logo_url: String,
+    pub description: String,
+    pub category: db::ClientCategory,
+    pub client_type: db::ClientType,
+    pub status: String,
+    pub is_live: bool, // approved and switched on: users can log in to it
+    pub created: String,
+    pub open_sessions: i64,
+    pub recent_logins: i64,
+    pub recent_users: i64,
+}
+
+
+impl DeveloperClientView {
+    pub fn new(row: db::DeveloperClientRow, texts: &DeveloperTexts) -> Self {
+        let is_live: bool = row.get_is_active() && row.status == db::CLIENT_APPROVED;
+        let status: String = match row.status.as_str() {
+            db::CLIENT_PENDING => texts.status_pending.to_owned(),
+            db::CLIENT_REJECTED => texts.status_rejected.to_owned(),
+            _ if is_live => texts.status_live.to_owned(),
+            _ => texts.status_inactive.to_owned()
+        };
+
+        DeveloperClientView {
+            client_id: row.client_id,
+            name: row.name,
+            domain: row.domain,
+            redirect_uri: row.redirect_uri,
+            logo_url: row.logo_url,
+            description: row.description,
+            category: row.category,
+            client_type: row.client_type,
+            status,
+            is_live,
+            created: utils::format_utc(&row.created_timestamp),
+            open_sessions: row.open_sessions,
+            recent_logins: row.recent_logins,
+            recent_users: row.recent_users,
+        }
+    }
+}
+
+
+// One client site in the admin's review queue
+pub struct PendingClientView {
+    pub client_id: String,
+    pub name: String,
+    pub domain: String,
+    pub redirect_uri: String,
+    pub description: String,
+    pub category: db::ClientCategory,
+    pub client_type: db::ClientType,
+    pub owner: String,
+    pub submitted: String,
+}
+
+
+impl PendingClientView {
+    pub fn new(row: db::PendingClientRow) -> Self {
+        PendingClientView {
+            client_id: row.client_id,
+            name: row.name,
+            domain: row.domain,
+            redirect_uri: row.redirect_uri,
+            description: row.description,
+            category: row.category,
+            client_type: row.client_type,
+            // The developer's account is gone
+            owner: row.owner_username.unwrap_or_default(),
+            submitted: utils::format_utc(&row.created_timestamp),
+        }
+    }
+}
+
+
+// One usable initial access token, for the admin's list
+pub struct InitialAccessTokenView {
+    pub id: i32,
+    pub label: String,
+    pub issued_by: String,
+    pub uses: String,
+    pub created: String,
+    pub expires: String,
+}
+
+
+impl InitialAccessTokenView {
+    pub fn new(row: db::InitialAccessTokenRow) -> Self {
+        InitialAccessTokenView {
+            id: row.id,
+            label: row.label,
+            issued_by: row.issued_by_username.unwrap_or_default(),
+            uses: format!("{} / {}", row.use_count, row.max_uses),
+            created: utils::format_utc(&row.created_timestamp),
+            expires: utils::format_utc(&row.expires_timestamp),
+        }
+    }
+}
+
+
+// For the role <select> on the admin's user page
+pub struct RoleOption {
+    pub name: String,
+    pub selected: bool,
+}
+
+
+// For the event type <select> on the audit log page
+pub struct EventTypeOption {
+    pub value: &'static str,
+    pub label: String,
+    pub selected: bool,
+}
+
+
+impl AuditEventView {
+    pub fn new(row: db::AuditEventRow, lang: &utils::SupportedLangs) -> Self {
+        AuditEventView {
+            time: utils::format_utc(&row.created_timestamp),
+            event_label: get_translation(&format!("audit.event.{}", row.event_type), lang, None),
+            event_type: row.event_type,
+            username: row.username.unwrap_or_default(),
+            actor_id: row.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            client_id: row.client_id.unwrap_or_default(),
+            device_label: utils::device_label(&row.user_agent),
+            ip_address: row.ip_address,
+            details: row.details,
+        }
+    }
+}
+
+
+impl SessionItem {
+    // Never send the token itself. Just say whether it's the caller's.
+    pub fn new(row: db::SessionRow, current_token: &String) -> Self {
+        SessionItem {
+            id: row.id,
+            is_current: &row.token == current_token,
+            client_name: row.client_name,
+            client_logo_url: row.client_logo_url,
+            device_label: row.device_label,
+            ip_address: row.ip_address,
+            created: row.created_timestamp.unix_timestamp(),
+            last_used: row.last_used_timestamp.unix_timestamp(),
+            expires: row.expires_timestamp.unix_timestamp(),
+        }
+    }
+}
+
+
 impl BadNames {
     pub fn new(code: u16) -> Self {
         BadNames {
@@ -155,10 +559,20 @@ impl BadNames {
 }
 
 impl BadPassword {
-    pub fn new(code: u16) -> Self {
+    pub fn new(code: u16, password_problems: Vec<String>) -> Self {
         BadPassword {
             code,
             password_valid: false,
+            password_problems,
+        }
+    }
+}
+
+impl WrongCurrentPassword {
+    pub fn new(code: u16) -> Self {
+        WrongCurrentPassword {
+            code,
+            current_password_valid: false,
         }
     }
 }
@@ -201,6 +615,8 @@ impl SendToError {
 #[derive(Deserialize)]
 pub struct LoginQuery {
     pub client_id: Option<String>,
+    pub code_challenge: Option<String>, // PKCE, for clients without a secret
+    pub code_challenge_method: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -214,6 +630,8 @@ pub struct RegisterCredentials {
     pub username: String,
     pub email: String,
     pub password: String,
+    pub pow_token: Option<String>, // solved proof-of-work challenge (see pow.rs)
+    pub pow_nonce: Option<String>,
 }
 
 
@@ -223,6 +641,9 @@ pub struct LoginCredentials{
     pub username_or_email: String,
     pub password: String,
     pub client_id: String,
+    pub pow_token: Option<String>, // only needed after repeated failures
+    pub pow_nonce: Option<String>,
+    pub code_challenge: Option<String>, // PKCE (S256), from the login page's query string
 }
 
 #[derive(Deserialize)]
@@ -232,12 +653,177 @@ pub struct RealNames {
 }
 
 
+#[derive(Deserialize)]
+pub struct StepUpInput {
+    pub password: String,
+}
+
+
 #[derive(Deserialize)]
 pub struct NewPassword {
+    pub current_password: String,
     pub password: String,
 }
 
 
+// Admin suspends a user. No days: until further notice.
+#[derive(Deserialize)]
+pub struct SuspendInput {
+    pub username: String,
+    pub reason: String,
+    pub days: Option<i64>,
+}
+
+
+#[derive(Deserialize)]
+pub struct SuspendedUser {
+    pub user_id: i32,
+}
+
+
+// An admin blocking a user from one client site (edit client page)
+#[derive(Deserialize)]
+pub struct RestrictInput {
+    pub client_id: String,
+    pub username: String,
+    pub reason: String,
+    pub days: Option<i64>,
+}
+
+
+#[derive(Deserialize)]
+pub struct UnrestrictInput {
+    pub client_id: String,
+    pub user_id: i32,
+}
+
+
+// An admin giving a user a role on one client site (edit client page)
+#[derive(Deserialize)]
+pub struct ClientRoleInput {
+    pub client_id: String,
+    pub username: String,
+    pub role_name: String,
+}
+
+
+#[derive(Deserialize)]
+pub struct RemoveClientRoleInput {
+    pub client_id: String,
+    pub user_id: i32,
+    pub role_name: String,
+}
+
+
+// From the "this wasn't me" link in a login alert email
+#[derive(Deserialize)]
+pub struct RecoveryToken {
+    pub token: String,
+}
+
+
+// From a password reset link: no current password, the token proves who they are
+#[derive(Deserialize)]
+pub struct PasswordReset {
+    pub token: String,
+    pub password: String,
+}
+
+
+// Filters (and page) for the admin's audit log. Empty fields mean "any".
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub event_type: Option<String>,
+    pub username: Option<String>,
+    pub client_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub page: Option<u32>,
+}
+
+impl AuditQuery {
+    pub fn to_filter(&self) -> db::AuditFilter {
+        // "" from an empty form field means no filter
+        let clean = |field: &Option<String>| -> Option<String> {
+            field.as_ref()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        db::AuditFilter {
+            event_type: clean(&self.event_type),
+            username: clean(&self.username),
+            client_id: clean(&self.client_id),
+            ip_address: clean(&self.ip_address),
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+pub struct SessionId {
+    pub session_id: i32,
+}
+
+
+// Search (and page) for the admin's users console
+#[derive(Deserialize)]
+pub struct UsersQuery {
+    pub search: Option<String>,
+    pub page: Option<u32>,
+}
+
+impl UsersQuery {
+    // "" from an empty search box means everyone
+    pub fn clean_search(&self) -> Option<String> {
+        self.search.as_ref()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+
+// The admin's actions on one user
+#[derive(Deserialize)]
+pub struct AdminUserId {
+    pub user_id: i32,
+}
+
+
+#[derive(Deserialize)]
+pub struct AdminUserNames {
+    pub user_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+
+#[derive(Deserialize)]
+pub struct AdminUserRole {
+    pub user_id: i32,
+    pub role: String,
+}
+
+
+#[derive(Deserialize)]
+pub struct AdminUserSession {
+    pub user_id: i32,
+    pub session_id: i32,
+}
+
+
+#[derive(Deserialize)]
+pub struct IssueRegistrationTokenInput {
+    pub label: String,
+    pub max_uses: i32,
+}
+
+
+#[derive(Deserialize)]
+pub struct RegistrationTokenId {
+    pub id: i32,
+}
+
+
 #[derive(Deserialize)]
 pub struct ClientDataReq {
     pub client_id: String,
@@ -269,6 +855,26 @@ impl ClientInputs {
         self.category = self.category.trim().to_string();
     }
 
+    // What a client sent to /oauth/register, as the client form
+    pub fn from_registration(
+        registration: &ClientRegistrationRequest,
+        client_id: &String
+    ) -> ClientInputs {
+        let mut inputs: ClientInputs = ClientInputs {
+            site_domain: registration.client_uri.to_owned(),
+            site_name: registration.client_name.to_owned(),
+            client_id: client_id.to_owned(),
+            redirect_uri: registration.redirect_uris.first().cloned().unwrap_or_default(),
+            logo_url: registration.logo_uri.to_owned(),
+            description: registration.description.to_owned(),
+            category: registration.category.to_owned(),
+            client_type: registration.client_type.to_owned(),
+            is_active: true,
+        };
+        inputs.trim_all_strings();
+        inputs
+    }
+
     pub fn print_all_strings(&self) {
         println!("client_id: {}", self.client_id);
         println!("domain: {}", self.site_domain);
@@ -279,6 +885,15 @@ impl ClientInputs {
         println!("desc: {}", self.description);
         println!("category: {}", self.category);
     }
+
+    // Only once client_inputs_error has passed them. Until then: the defaults.
+    pub fn get_client_type(&self) -> db::ClientType {
+        db::ClientType::parse(&self.client_type).unwrap_or_default()
+    }
+
+    pub fn get_category(&self) -> db::ClientCategory {
+        db::ClientCategory::parse(&self.category).unwrap_or_default()
+    }
 }
 
 // OTHER STRUCTS
@@ -290,6 +905,25 @@ pub struct TwoAuthCookies {
 }
 
 
+impl TwoAuthCookies {
+    // Empty, already-expired cookies. Sending these logs the browser out.
+    pub fn expired() -> TwoAuthCookies {
+        TwoAuthCookies {
+            jwt_cookie: Cookie::build("jwt", "")
+                .path("/")
+                .max_age(time::Duration::seconds(0))
+                .http_only(true)
+                .finish(),
+            refresh_token_cookie: Cookie::build("refresh_token", "")
+                .path("/")
+                .max_age(time::Duration::seconds(0))
+                .http_only(true)
+                .finish(),
+        }
+    }
+}
+
+
 
 /* 
  * 
@@ -327,6 +961,7 @@ pub struct LoginTemplate {
     pub client_refs: Vec<db::ClientRef>,
     pub login_is_available: bool,
     pub selected_client_id: String,
+    pub code_challenge: String, // empty if the client didn't send one
 }
 
 #[derive(Template)]
@@ -335,6 +970,8 @@ pub struct AdminTemplate {
     pub texts: AdminTexts,
     pub user: auth::UserReqData,
     pub client_refs: Vec<db::ClientRef>,
+    pub archived_refs: Vec<db::ClientRef>,
+    pub auth_client_id: String, // the auth site can't be archived or deleted
 }
 
 
@@ -352,6 +989,8 @@ pub struct EditClientTemplate {
     pub user: auth::UserReqData,
     pub texts: EditClientTexts,
     pub client_data: db::ClientData,
+    pub restrictions: Vec<ClientRestrictionView>,
+    pub role_assignments: Vec<ClientRoleView>,
 }
 
 
@@ -378,6 +1017,100 @@ pub struct DashboardTemplate<'a> {
     pub texts: DashboardTexts,
     pub user_data: &'a db::User,
     pub user: auth::UserReqData,
+    pub security_events: Vec<AuditEventView>,
+}
+
+
+#[derive(Template)]
+#[template(path ="audit_log.html")]
+pub struct AuditLogTemplate {
+    pub texts: AuditTexts,
+    pub user: auth::UserReqData,
+    pub events: Vec<AuditEventView>,
+    pub event_types: Vec<EventTypeOption>,
+    pub filter: db::AuditFilter,
+    pub page: u32,
+    pub has_older: bool,
+}
+
+#[derive(Template)]
+#[template(path ="suspensions.html")]
+pub struct SuspensionsTemplate {
+    pub texts: SuspensionTexts,
+    pub user: auth::UserReqData,
+    pub suspensions: Vec<SuspensionView>,
+}
+
+#[derive(Template)]
+#[template(path ="admin_users.html")]
+pub struct AdminUsersTemplate {
+    pub texts: AdminTexts,
+    pub user: auth::UserReqData,
+    pub users: Vec<AdminUserView>,
+    pub search: String,
+    pub page: u32,
+    pub has_more: bool,
+}
+
+#[derive(Template)]
+#[template(path ="admin_user.html")]
+pub struct AdminUserTemplate {
+    pub texts: AdminTexts,
+    pub suspend_texts: SuspensionTexts,
+    pub user: auth::UserReqData,
+    pub account: AdminUserView,
+    pub first_name: String,
+    pub last_name: String,
+    pub extra_roles: Vec<String>,
+    pub role_options: Vec<RoleOption>,
+    pub suspension: Option<String>,
+    pub can_impersonate: bool,
+    pub sessions: Vec<AdminSessionView>,
+    pub events: Vec<AuditEventView>,
+}
+
+#[derive(Template)]
+#[template(path ="suspended.html")]
+pub struct SuspendedTemplate {
+    pub texts: SuspendedTexts,
+    pub user: auth::UserReqData,
+    pub reason: String,
+    pub since: String,
+    pub until: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path ="developer.html")]
+pub struct DeveloperTemplate {
+    pub texts: DeveloperTexts,
+    pub user: auth::UserReqData,
+    pub clients: Vec<DeveloperClientView>,
+}
+
+#[derive(Template)]
+#[template(path ="client_reviews.html")]
+pub struct ClientReviewsTemplate {
+    pub texts: ClientReviewTexts,
+    pub user: auth::UserReqData,
+    pub clients: Vec<PendingClientView>,
+}
+
+#[derive(Template)]
+#[template(path ="registration_tokens.html")]
+pub struct RegistrationTokensTemplate {
+    pub texts: RegistrationTokenTexts,
+    pub user: auth::UserReqData,
+    pub tokens: Vec<InitialAccessTokenView>,
+}
+
+// Same page for both steps: "this wasn't me", then the new password
+#[derive(Template)]
+#[template(path ="account_recovery.html")]
+pub struct AccountRecoveryTemplate {
+    pub texts: RecoveryTexts,
+    pub user: auth::UserReqData,
+    pub token: String,
+    pub is_reset: bool,
 }
 
 
@@ -416,9 +1149,9 @@ pub fn send_to_login() -> HttpResponse {
 
 pub fn non_admin_rejection(req: &HttpRequest) -> HttpResponse {
     // send to unauthorized error page
-    return redirect_to_err(String::from("403"))
+    redirect_to_err(String::from("403"))
         .respond_to(req)
-        .map_into_boxed_body();
+        .map_into_boxed_body()
 }
 
 
@@ -426,32 +1159,33 @@ pub fn non_admin_rejection(req: &HttpRequest) -> HttpResponse {
  * Redirect to error page with a simple and easy function
  */
 pub fn return_error_page(req: &HttpRequest, code: u16) -> HttpResponse {
-    return redirect_to_err(code.to_string())
+    redirect_to_err(code.to_string())
         .respond_to(req)
-        .map_into_boxed_body();
+        .map_into_boxed_body()
 }
 
 
 /**
- * We often check if the user is admin.
- * This returns the appropriate redirect depending on
- * which kind of non-admin the user is.
+ * Routes that need a permission (see rbac) start with this.
+ * It returns the appropriate redirect depending on
+ * which kind of user is missing the permission.
  */
-pub fn redirect_non_admin(
+pub fn require_permission(
     user_req_data: &UserReqData,
-    req: &HttpRequest
+    req: &HttpRequest,
+    permission: Permission
 ) -> Option<HttpResponse> {
     // Send guest to login
     if user_req_data.id.is_none() {
         return Some(send_to_login());
     }
 
-    // If they're not admin send them to error page
-    if !user_req_data.is_admin() {
-        return Some(non_admin_rejection(&req));
+    // If they don't have it send them to error page
+    if !user_req_data.has_permission(permission) {
+        return Some(non_admin_rejection(req));
     }
 
-    // The user is an admin
+    // The user is allowed
     None
 }
 
@@ -477,6 +1211,118 @@ pub fn return_authentication_err_json() -> HttpResponse {
 }
 
 
+/**
+ * The hash pool refused the job. A full queue means we're shedding load:
+ * tell the caller to try again shortly (503). Anything else is a 500.
+ */
+pub fn hash_pool_err_json(e: &HashPoolError) -> HttpResponse {
+    match e {
+        HashPoolError::Saturated => {
+            HttpResponse::ServiceUnavailable()
+                .append_header((header::RETRY_AFTER, "1"))
+                .json(ErrorResponse{
+                    error: String::from("Service Unavailable"),
+                    code: 503
+                })
+        },
+        _ => {
+            eprintln!("{e}");
+            return_internal_err_json()
+        }
+    }
+}
+
+
+/**
+ * Refuse a login from a suspended user, with a link to a page that says why.
+ * The link holds a short-lived token: nobody else can read the reason.
+ */
+pub async fn return_suspended_json(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    user_id: i32
+) -> HttpResponse {
+    let lang: utils::SupportedLangs = auth::get_user_req_data(req).clone_lang();
+    let token: String = auth::generate_email_token();
+
+    if let Err(e) = db::add_account_token(
+        pool, user_id, &token, db::TOKEN_SUSPENSION_NOTICE, time::Duration::hours(1)).await {
+        eprintln!("Database error: {e}");
+        return return_internal_err_json();
+    }
+
+    HttpResponse::Forbidden().json(AccountSuspended {
+        error: get_translation("err.suspended", &lang, None),
+        code: 403,
+        suspended: true,
+        redirect_uri: format!("/auth/suspended/{token}"),
+    })
+}
+
+
+// Where anyone trying to use a deactivated client site ends up
+pub const CLIENT_INACTIVE_PAGE: &str = "/error/403/client_inactive";
+
+
+/**
+ * The user tried to log in to a client site that's been deactivated.
+ */
+pub fn return_client_inactive_json(req: &HttpRequest) -> HttpResponse {
+    let lang: utils::SupportedLangs = auth::get_user_req_data(req).clone_lang();
+    HttpResponse::Forbidden().json(ClientInactive {
+        error: get_translation("err.client_inactive", &lang, None),
+        code: 403,
+        client_inactive: true,
+        redirect_uri: String::from(CLIENT_INACTIVE_PAGE),
+    })
+}
+
+
+/**
+ * Check the reason and length of a suspension or client restriction.
+ * Gives back the trimmed reason and the end time (None: until further notice),
+ * or the 422 to send.
+ */
+pub fn validate_reason_and_days(
+    reason: &str,
+    days: Option<i64>,
+    lang: &utils::SupportedLangs
+) -> Result<(String, Option<time::OffsetDateTime>), HttpResponse> {
+    let reason: String = reason.trim().to_string();
+    if reason.is_empty() || reason.chars().count() > 500 {
+        return Err(HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(ErrorResponse {
+            error: get_translation("suspend.err.reason", lang, None),
+            code: 422
+        }));
+    }
+
+    let end_timestamp: Option<time::OffsetDateTime> = match days {
+        Some(days) if (1..=3650).contains(&days) => {
+            Some(time::OffsetDateTime::now_utc() + time::Duration::days(days))
+        },
+        Some(_days) => {
+            return Err(HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(ErrorResponse {
+                error: get_translation("suspend.err.days", lang, None),
+                code: 422
+            }));
+        },
+        None => None
+    };
+
+    Ok((reason, end_timestamp))
+}
+
+
+pub fn return_challenge_required_json(req: &HttpRequest) -> HttpResponse {
+    let lang: utils::SupportedLangs = auth::get_user_req_data(req).clone_lang();
+    HttpResponse::build(StatusCode::PRECONDITION_REQUIRED).json(ChallengeRequired {
+        error: get_translation("err.challenge_required", &lang, None),
+        code: 428,
+        challenge_required: true,
+    })
+}
+
+
 // If something is not found
 pub fn return_not_found_err_json() -> HttpResponse {
     HttpResponse::Unauthorized().json(ErrorResponse{
@@ -486,6 +1332,298 @@ pub fn return_not_found_err_json() -> HttpResponse {
 }
 
 
+pub fn return_role_refused_json(lang: &utils::SupportedLangs) -> HttpResponse {
+    HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(ErrorResponse {
+        error: get_translation("admin.user.err.role", lang, None),
+        code: 422
+    })
+}
+
+
+/**
+ * Extra roles follow the base role's rules: nobody changes their own,
+ * and only admins deal with admin (granting it, or touching an admin's roles).
+ * None means go ahead.
+ */
+pub async fn extra_role_refusal(
+    pool: &MySqlPool,
+    user_req_data: &UserReqData,
+    inputs: &AdminUserRole
+) -> Option<HttpResponse> {
+    if user_req_data.id == Some(inputs.user_id) {
+        return Some(HttpResponse::Forbidden().json(ErrorResponse {
+            error: get_translation("admin.user.err.self", &user_req_data.lang, None),
+            code: 403
+        }));
+    }
+
+    let account: db::User = match db::get_user_by_id(pool, inputs.user_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return Some(return_not_found_err_json()),
+        Err(_e) => return Some(return_internal_err_json())
+    };
+
+    let admin_role: &str = db::UserRole::Admin.as_str();
+    let touches_admin: bool = inputs.role == admin_role || account.get_role() == admin_role;
+
+    if touches_admin && !user_req_data.is_admin() {
+        return Some(return_role_refused_json(&user_req_data.lang));
+    }
+
+    None
+}
+
+
+/**
+ * A new client secret: the raw one to show ONCE, and the hash to store.
+ * Only confidential clients get one. Anyone else gets two empty strings.
+ */
+pub async fn new_client_secret(client_type: db::ClientType) -> Result<(String, String), HttpResponse> {
+    if !client_type.has_secret() {
+        return Ok((String::new(), String::new()));
+    }
+
+    let raw_client_secret: String = utils::generate_client_secret();
+    let hashed_client_secret: String = hash_pool::hash_password(raw_client_secret.to_owned())
+        .await
+        .map_err(|e| hash_pool_err_json(&e))?;
+
+    Ok((raw_client_secret, hashed_client_secret))
+}
+
+
+// Asking for a new secret for a public or native client
+pub fn no_client_secret_json(lang: &utils::SupportedLangs) -> HttpResponse {
+    HttpResponse::Conflict().json(ErrorResponse{
+        error: get_translation("err.client_no_secret", lang, None),
+        code: 409
+    })
+}
+
+
+/**
+ * Checks the client form (admin, developer or /oauth/register)
+ * after trim_all_strings. Some(what's wrong with it).
+ */
+pub fn client_inputs_error(inputs: &ClientInputs) -> Option<&'static str> {
+    let domains_are_valid: bool =
+        utils::validate_url(&inputs.redirect_uri) &&
+        utils::validate_url(&inputs.site_domain);
+
+    if !domains_are_valid {
+        return Some("Invalid domain format");
+    }
+
+    let client_type: db::ClientType = match db::ClientType::parse(&inputs.client_type) {
+        Some(client_type) => client_type,
+        None => return Some("Client type: confidential, public or native")
+    };
+
+    if db::ClientCategory::parse(&inputs.category).is_none() {
+        return Some("Category: game, tool or service");
+    }
+
+    // Only an app on the user's own machine redirects to it.
+    // (And the auth site itself, run locally.)
+    if utils::is_loopback_url(&inputs.redirect_uri)
+        && !client_type.allows_loopback()
+        && inputs.client_id != utils::auth_client_id() {
+        return Some("Only native clients can redirect to localhost");
+    }
+
+    let client_id_is_valid: bool = utils::string_length_valid(
+        utils::StringRange{ min: 2, max: 20 },
+        &inputs.client_id
+    ) && utils::has_no_whitespace(
+        &inputs.client_id
+    );
+
+    if !client_id_is_valid {
+        return Some("Client ID: 2-20 characters, no spaces");
+    }
+
+    let name_is_valid: bool = utils::string_length_valid(
+        utils::StringRange{ min: 2, max: 20 },
+        &inputs.site_name
+    );
+
+    if !name_is_valid {
+        return Some("Site name: 2-20 characters.");
+    }
+
+    None
+}
+
+
+/**
+ * The same checks, for /oauth/register: the answer is an RFC 7591 error.
+ * Our clients have one redirect URI, so redirect_uris must have exactly one.
+ */
+pub fn registration_inputs_problem(
+    registration: &ClientRegistrationRequest,
+    inputs: &ClientInputs
+) -> Option<HttpResponse> {
+    if registration.redirect_uris.len() != 1 {
+        return Some(registration_err_json(
+            StatusCode::BAD_REQUEST,
+            "invalid_redirect_uri",
+            "Exactly one redirect URI is required"));
+    }
+
+    if !utils::validate_url(&inputs.redirect_uri) {
+        return Some(registration_err_json(
+            StatusCode::BAD_REQUEST,
+            "invalid_redirect_uri",
+            "Invalid redirect URI format"));
+    }
+
+    if utils::is_loopback_url(&inputs.redirect_uri) && !inputs.get_client_type().allows_loopback() {
+        return Some(registration_err_json(
+            StatusCode::BAD_REQUEST,
+            "invalid_redirect_uri",
+            "Only native clients can redirect to localhost"));
+    }
+
+    client_inputs_error(inputs).map(|error| registration_err_json(
+        StatusCode::BAD_REQUEST,
+        "invalid_client_metadata",
+        error))
+}
+
+
+/**
+ * An RFC 7591 error. A bad (or missing) bearer token gets
+ * the WWW-Authenticate header RFC 6750 asks for.
+ */
+pub fn registration_err_json(
+    status: StatusCode,
+    error: &str,
+    error_description: &str
+) -> HttpResponse {
+    let mut response = HttpResponse::build(status);
+
+    if status == StatusCode::UNAUTHORIZED {
+        response.append_header((
+            header::WWW_AUTHENTICATE,
+            format!("Bearer error=\"{error}\"")));
+    }
+
+    response.json(ClientRegistrationError {
+        error: String::from(error),
+        error_description: String::from(error_description),
+    })
+}
+
+
+// The bearer token in the Authorization header, if there's one
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+
+/**
+ * What /oauth/register sends back about a client.
+ * The raw secret and registration access token only when they're new.
+ */
+pub fn registration_response(
+    client: &db::ClientData,
+    client_secret: Option<String>,
+    registration_access_token: Option<String>
+) -> ClientRegistrationResponse {
+    ClientRegistrationResponse {
+        client_id: client.client_id.to_owned(),
+        client_secret,
+        client_id_issued_at: client.created_timestamp.unix_timestamp(),
+        client_secret_expires_at: 0,
+        registration_access_token,
+        registration_client_uri: format!(
+            "{}/oauth/register/{}", config::auth_domain(), client.client_id),
+        redirect_uris: vec![client.redirect_uri.to_owned()],
+        client_name: client.name.to_owned(),
+        client_uri: client.domain.to_owned(),
+        logo_uri: client.logo_url.to_owned(),
+        description: client.description.to_owned(),
+        category: client.category.to_string(),
+        client_type: client.client_type.to_string(),
+    }
+}
+
+
+// client_inputs_error as the 406 the client forms expect
+pub fn client_inputs_problem(inputs: &ClientInputs) -> Option<HttpResponse> {
+    client_inputs_error(inputs).map(|error| {
+        eprintln!("Client inputs not valid: {error}");
+        HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
+            .json(ErrorResponse{
+                error: String::from(error),
+                code: 406
+            })
+    })
+}
+
+
+/**
+ * Sensitive admin routes start with this (after require_permission).
+ * A valid JWT isn't enough: it may have been refreshed for days.
+ * The admin must have typed their password in the last few minutes,
+ * otherwise the page asks for it (step_up.js) and tries again.
+ */
+pub fn require_step_up(user_req_data: &UserReqData) -> Option<HttpResponse> {
+    if user_req_data.authenticated_within(config::step_up_minutes()) {
+        return None;
+    }
+
+    Some(HttpResponse::Unauthorized().json(StepUpRequired {
+        error: get_translation("err.step_up_required", &user_req_data.lang, None),
+        code: 401,
+        step_up_required: true,
+    }))
+}
+
+
+/**
+ * An admin impersonating a user can look around,
+ * but not change the user's password or a client secret.
+ */
+pub fn refuse_while_impersonating(lang: &utils::SupportedLangs) -> HttpResponse {
+    HttpResponse::Forbidden().json(ErrorResponse{
+        error: get_translation("err.impersonating", lang, None),
+        code: 403
+    })
+}
+
+
+/**
+ * The refresh token of the session making this request (from its cookie).
+ * Empty if there isn't one.
+ */
+pub fn current_refresh_token(req: &HttpRequest) -> String {
+    match req.cookie("refresh_token") {
+        Some(cookie) => cookie.value().to_string(),
+        None => String::new()
+    }
+}
+
+
+/**
+ * Which device is making this request (user agent and IP address).
+ * Every session and auth code remembers its device.
+ */
+pub fn session_device(req: &HttpRequest) -> db::SessionDevice {
+    let user_agent: String = match req.headers().get(header::USER_AGENT) {
+        Some(value) => value.to_str().unwrap_or("").to_string(),
+        None => String::new()
+    };
+
+    db::SessionDevice::new(user_agent, utils::client_ip(req.peer_addr(), req.headers()))
+}
+
+
 /**
  * We only do this once the user has been authenticated.
  * Calls functions to generate JWT and refresh token,
@@ -496,6 +1634,7 @@ pub fn return_not_found_err_json() -> HttpResponse {
  */
 pub async fn get_user_auth_cookies(
     pool: &MySqlPool,
+    req: &HttpRequest,
     user: &db::User
 ) -> Result<TwoAuthCookies, ErrorResponse> {
     // generate JWT. Don't send user obj (with password) back
@@ -504,11 +1643,23 @@ pub async fn get_user_auth_cookies(
         code: 500
     };
 
+    // The user's roles on this (auth) site go in the claims
+    let client_roles: Vec<String> =
+        match db::get_client_user_roles(pool, &utils::auth_client_id(), user.get_id()).await {
+            Ok(roles) => roles,
+            Err(e) => {
+                eprint!("Internal Server Error: {e}");
+                return Err(jwt_err_500)
+            }
+        };
+
     // Generate a token String
     let jwt: String = match auth::generate_jwt(
         user.get_id(),
         user.get_username().to_owned(),
-        user.get_role().to_owned()
+        user.get_role().to_owned(),
+        client_roles,
+        auth::auth_time_now()
     ) {
         Ok(token) => token,
         Err(e) => {
@@ -518,11 +1669,13 @@ pub async fn get_user_auth_cookies(
     };
 
     // create a refresh_token and put it in the DB
+    // a new session for the device making this request
     match db::add_refresh_token(
-        &pool,
+        pool,
         user.get_id(),
         utils::auth_client_id(),
-        auth::generate_refresh_token()
+        auth::generate_refresh_token(),
+        &session_device(req)
     ).await {
         Ok(refresh_token) => {
             // Refresh token successfully inserted into DB
@@ -549,6 +1702,203 @@ pub async fn get_user_auth_cookies(
 }
 
 
+/**
+ * Registration when enumeration resistance is on.
+ * Usernames are public anyway, so a taken username is still reported.
+ * Email addresses are not. Whether or not the address is taken, the
+ * response is the same and the owner of the address gets an email:
+ * either a link to finish registering, or a notice that someone tried
+ * to register with an address that already has an account.
+ */
+pub async fn register_with_confirmation(
+    pool: &MySqlPool,
+    req: &HttpRequest,
+    info: &RegisterCredentials
+) -> HttpResponse {
+    if db::username_taken(pool, &info.username).await {
+        let bad_creds_data: BadRegistrationInputs = BadRegistrationInputs {
+            email_valid: true,
+            username_valid: false,
+            password_valid: true,
+            password_problems: Vec::new(),
+            code: 409,
+        };
+
+        return HttpResponse::Conflict().json(bad_creds_data);
+    }
+
+    let lang: utils::SupportedLangs = auth::get_user_req_data(req).clone_lang();
+
+    // Hash in both cases so the two paths take about the same time
+    let password_hash: String = match hash_pool::hash_password(info.password.clone()).await {
+        Ok(hash) => hash,
+        Err(e) => return hash_pool_err_json(&e)
+    };
+
+    let mail: mailer::OutgoingMail = if db::email_taken(pool, &info.email).await {
+        let login_url: String = format!("{}/auth/login", config::auth_domain());
+
+        mailer::OutgoingMail {
+            to: info.email.to_owned(),
+            subject: get_translation("mail.reg_exists.subject", &lang, None),
+            body: get_translation("mail.reg_exists.body", &lang, Some(&[&login_url])),
+        }
+    } else {
+        let token: String = auth::generate_email_token();
+
+        if let Err(_e) = db::add_pending_registration(
+            pool,
+            &info.username,
+            &info.email,
+            &password_hash,
+            &token
+        ).await {
+            return return_internal_err_json();
+        }
+
+        let confirm_url: String = format!(
+            "{}/auth/confirm_registration/{}",
+            config::auth_domain(),
+            token);
+
+        mailer::OutgoingMail {
+            to: info.email.to_owned(),
+            subject: get_translation("mail.confirm_reg.subject", &lang, None),
+            body: get_translation(
+                "mail.confirm_reg.body",
+                &lang,
+                Some(&[&info.username, &confirm_url])),
+        }
+    };
+
+    if let Err(e) = mailer::send_mail(mail).await {
+        eprintln!("Failed to send registration email: {e}");
+        return return_internal_err_json();
+    }
+
+    // Don't record which of the two emails went out: that's the secret we're keeping
+    audit::record(
+        pool, req, AuditEvent::RegistrationPending, None, None,
+        &format!("username: {}", info.username)).await;
+
+    HttpResponse::Accepted().json(ConfirmationSent::new(&lang))
+}
+
+
+/**
+ * Client backends authenticate with their client_id and raw client_secret.
+ * Check the secret against the stored hash (on the hash pool), and rehash it
+ * if it was made with old Argon2 settings.
+ * Only confidential clients have a secret: anyone else never matches.
+ * Unknown client is a 404. Pool or DB trouble comes back as the response to send.
+ */
+pub async fn verify_client_secret(
+    pool: &MySqlPool,
+    client_id: &String,
+    raw_client_secret: &String
+) -> Result<bool, HttpResponse> {
+    let client_secret: db::ClientSecret = get_client_secret_or_err(pool, client_id).await?;
+    check_client_secret(pool, client_id, raw_client_secret, client_secret).await
+}
+
+
+/**
+ * verify_client_secret, for redeeming an auth code.
+ * If the login came with a PKCE code_challenge, the code_verifier must match it.
+ * Public and native clients can't keep a secret, so for them that's the only proof
+ * (login_post won't give them a code without a challenge).
+ * Confidential clients still need their secret either way.
+ */
+pub async fn verify_code_client(
+    pool: &MySqlPool,
+    client_id: &String,
+    raw_client_secret: &String,
+    code_challenge: Option<&String>,
+    code_verifier: &str
+) -> Result<bool, HttpResponse> {
+    let client_secret: db::ClientSecret = get_client_secret_or_err(pool, client_id).await?;
+
+    if let Some(code_challenge) = code_challenge {
+        if !auth::verify_code_verifier(code_verifier, code_challenge) {
+            return Ok(false);
+        }
+        if !client_secret.client_type.has_secret() {
+            return Ok(true);
+        }
+    }
+    check_client_secret(pool, client_id, raw_client_secret, client_secret).await
+}
+
+
+async fn get_client_secret_or_err(
+    pool: &MySqlPool,
+    client_id: &String
+) -> Result<db::ClientSecret, HttpResponse> {
+    match db::get_client_secret(pool, client_id).await {
+        Ok(Some(client_secret)) => Ok(client_secret),
+        Ok(None) => Err(return_not_found_err_json()),
+        Err(_e) => Err(return_internal_err_json())
+    }
+}
+
+
+async fn check_client_secret(
+    pool: &MySqlPool,
+    client_id: &String,
+    raw_client_secret: &String,
+    client_secret: db::ClientSecret
+) -> Result<bool, HttpResponse> {
+    // ie: it was public until just now, and hasn't been given one yet
+    if !client_secret.client_type.has_secret() || client_secret.hashed_client_secret.is_empty() {
+        return Ok(false);
+    }
+    let hashed_client_secret: String = client_secret.hashed_client_secret;
+
+    let verification: PasswordVerification = hash_pool::verify_password(
+        raw_client_secret.to_owned(),
+        hashed_client_secret
+    ).await.map_err(|e| hash_pool_err_json(&e))?;
+
+    // Client secrets are Argon2 hashes too. Keep them on current settings.
+    if verification == PasswordVerification::MatchOutdated {
+        match hash_pool::hash_password(raw_client_secret.to_owned()).await {
+            Ok(new_hash) => {
+                if let Err(e) = db::update_client_secret(pool, client_id, &new_hash).await {
+                    eprintln!("Could not save rehashed client secret: {e}");
+                }
+            },
+            Err(e) => eprintln!("Could not rehash client secret: {e}")
+        }
+    }
+
+    Ok(verification.matches())
+}
+
+
+/**
+ * The password was right but its hash uses old Argon2 settings.
+ * We have the raw password right now, so rehash it with the current ones.
+ * A failure here is only logged. The login itself already succeeded.
+ */
+pub async fn upgrade_password_hash(
+    pool: &MySqlPool,
+    user_id: i32,
+    raw_password: &String
+) {
+    let new_hash: String = match hash_pool::hash_password(raw_password.to_owned()).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("Could not rehash password: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = db::update_password_hash(pool, &new_hash, user_id).await {
+        eprintln!("Could not save rehashed password: {e}");
+    }
+}
+
+
 /* 
  * 
  * 
@@ -13,6 +13,166 @@
 use regex::Regex;
 use rand::Rng;
 use rand_regex::Regex as RandRegex;
+use actix_web::http::header::{ self, HeaderMap };
+use std::net::{ IpAddr, SocketAddr };
+use time::{ OffsetDateTime, UtcOffset };
+use url::{ Host, Url };
+
+use crate::config;
+
+
+/**
+ * A timestamp for showing on a page, ie: "2025-03-07 14:05 UTC"
+ */
+pub fn format_utc(timestamp: &OffsetDateTime) -> String {
+    let utc: OffsetDateTime = timestamp.to_offset(UtcOffset::UTC);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} UTC",
+        utc.year(),
+        utc.month() as u8,
+        utc.day(),
+        utc.hour(),
+        utc.minute()
+    )
+}
+
+
+/**
+ * The client's IP address (without the port).
+ * Normally that's just the other end of the socket. Only when the socket
+ * belongs to one of our TRUSTED_PROXIES do we read its Forwarded /
+ * X-Forwarded-For header, and then only as far back as it's still our proxies talking.
+ */
+pub fn client_ip(peer_addr: Option<SocketAddr>, headers: &HeaderMap) -> String {
+    client_ip_behind(peer_addr, headers, &config::trusted_proxies())
+}
+
+fn client_ip_behind(peer_addr: Option<SocketAddr>, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> String {
+    let mut client: IpAddr = match peer_addr {
+        Some(socket_address) => socket_address.ip(),
+        None => return String::new()
+    };
+
+    // Each proxy appends who it heard from, so read from the right.
+    // The first address that isn't one of our proxies is the client.
+    for hop in forwarded_hops(headers).iter().rev() {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+
+        match parse_hop(hop) {
+            Some(ip) => client = ip,
+            None => break
+        }
+    }
+
+    client.to_string()
+}
+
+// The addresses in the Forwarded header (for=...), or else X-Forwarded-For, in order
+fn forwarded_hops(headers: &HeaderMap) -> Vec<String> {
+    let forwarded: Vec<String> = headers.get_all(header::FORWARDED)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split([',', ';']))
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            name.eq_ignore_ascii_case("for").then(|| value.to_string())
+        })
+        .collect();
+
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    headers.get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.to_string())
+        .collect()
+}
+
+// "203.0.113.7", "203.0.113.7:4711", "\"[2001:db8::1]:4711\"", "2001:db8::1"
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop: &str = hop.trim().trim_matches('"');
+
+    hop.parse::<IpAddr>().ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|socket_address| socket_address.ip()))
+        .or_else(|| hop.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok())
+}
+
+
+// Compare every byte no matter where the first difference is
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+
+/**
+ * The network an IP address belongs to, for spotting logins from somewhere new.
+ * IPv4: the /24 (ie: "203.0.113.0/24"). IPv6: the /48.
+ * Anything that doesn't parse is its own "range".
+ */
+pub fn ip_range(ip_address: &String) -> String {
+    match ip_address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            let octets: [u8; 4] = ip.octets();
+            format!("{}.{}.{}.0/24", octets[0], octets[1], octets[2])
+        },
+        Ok(IpAddr::V6(ip)) => {
+            let segments: [u16; 8] = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+        },
+        Err(_e) => ip_address.to_owned()
+    }
+}
+
+
+/**
+ * A short readable name for a device, from its user agent string.
+ * ie: "Firefox on Windows". Only for showing to the user, so rough is fine.
+ */
+pub fn device_label(user_agent: &str) -> String {
+    // Order matters: Edge and Opera also claim to be Chrome, Chrome claims to be Safari
+    let browsers: [(&str, &str); 6] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ];
+
+    // Android says it's Linux, iOS says it's like Mac OS X
+    let systems: [(&str, &str); 6] = [
+        ("Android", "Android"),
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ];
+
+    let browser: Option<&str> = browsers.iter()
+        .find(|(marker, _name)| user_agent.contains(marker))
+        .map(|(_marker, name)| *name);
+
+    let system: Option<&str> = systems.iter()
+        .find(|(marker, _name)| user_agent.contains(marker))
+        .map(|(_marker, name)| *name);
+
+    match (browser, system) {
+        (Some(browser), Some(system)) => format!("{browser} on {system}"),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(system)) => system.to_string(),
+        (None, None) => String::from("Unknown device")
+    }
+}
 
 
 /* 
@@ -34,10 +194,6 @@ fn username_length_range() -> StringRange {
     StringRange{ min: 6, max: 20 }
 }
 
-fn password_length_range() -> StringRange {
-    StringRange{ min: 6, max: 16 }
-}
-
 fn real_name_length_range() -> StringRange {
     StringRange { min: 2, max: 50 }
 }
@@ -56,45 +212,58 @@ pub fn generate_client_secret() -> String {
     rng.sample(reg)
 }
 
-pub fn string_length_valid(range_obj: StringRange, string: &String) -> bool {
+/**
+ * A client_id for a client that registered itself (/oauth/register).
+ * 20 characters, the most a client_id can have.
+ */
+pub fn generate_client_id() -> String {
+    let mut rng: rand::prelude::ThreadRng = rand::rng();
+    let reg: RandRegex = RandRegex::compile(
+        r"[a-z0-9]{16}",
+        100
+    ).unwrap();
+
+    let suffix: String = rng.sample(reg);
+    format!("dcr_{suffix}")
+}
+
+pub fn string_length_valid(range_obj: StringRange, string: &str) -> bool {
     let string_length: usize = string.len();
     string_length >= range_obj.min && string_length <= range_obj.max
 }
 
-pub fn validate_username(username: &String) -> bool {
+pub fn validate_username(username: &str) -> bool {
     let reg: Regex = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
-    reg.is_match(&username) &&
+    reg.is_match(username) &&
         string_length_valid(
             username_length_range(),
-            &username)
+            username)
 }
 
-pub fn validate_password(password: &String) -> bool {
-    let reg: Regex = Regex::new(r"[A-Za-z0-9!@#$%^&*()_\-+=\[\]{}:;<>.,?~`|]+$").unwrap();
-    reg.is_match(&password) &&
-        string_length_valid(
-            password_length_range(),
-            &password)
+// Client-defined role names (ie: moderator, beta_tester)
+pub fn validate_role_name(role_name: &str) -> bool {
+    let reg: Regex = Regex::new(r"^[a-z0-9_.-]{1,50}$").unwrap();
+    reg.is_match(role_name)
 }
 
-pub fn has_no_whitespace(string: &String) -> bool {
+pub fn has_no_whitespace(string: &str) -> bool {
     let reg: Regex = Regex::new(r"^\S+$").unwrap();
     reg.is_match(string)
 }
 
-pub fn validate_email(email: &String) -> bool {
+pub fn validate_email(email: &str) -> bool {
     let reg: Regex = Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$").unwrap();
-    reg.is_match(&email)
+    reg.is_match(email)
 }
 
-pub fn validate_real_name(name: &String) -> bool {
+pub fn validate_real_name(name: &str) -> bool {
     string_length_valid(real_name_length_range(), name)
 }
 
 // TO DO: Move this into RESOURCES file
 pub fn auth_client_id() -> String { String::from("auth_site") }
 
-pub fn validate_url(url: &String) -> bool {
+pub fn validate_url(url: &str) -> bool {
     let lenient_regex: Regex =
         Regex::new(r"^https?://[^\s/$.?#].[^\s]*$")
         .unwrap();
@@ -105,6 +274,35 @@ pub fn validate_url(url: &String) -> bool {
     lenient_regex.is_match(url)
 }
 
+/**
+ * Does this http(s) URL point back at the user's own machine?
+ * localhost (or a name under it, with or without the trailing dot)
+ * or any loopback IP: all of 127.0.0.0/8, and ::1. Any port.
+ * The URL parser lowercases the host and reads every way of writing an IP.
+ */
+pub fn is_loopback_url(url: &str) -> bool {
+    let parsed_url: Url = match Url::parse(url) {
+        Ok(parsed_url) => parsed_url,
+        Err(_e) => return false
+    };
+
+    if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
+        return false;
+    }
+
+    match parsed_url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain: &str = domain.trim_end_matches('.');
+            domain == "localhost" || domain.ends_with(".localhost")
+        },
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => {
+            ip.is_loopback() || ip.to_ipv4_mapped().is_some_and(|ip| ip.is_loopback())
+        },
+        None => false
+    }
+}
+
 
 
 /* 
@@ -153,7 +351,100 @@ impl SupportedLangs {
         }
 
         // Default
-        return SupportedLangs::English;
+        SupportedLangs::English
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{ HeaderName, HeaderValue };
+
+    const PROXY: &str = "10.0.0.2";
+
+    fn peer(ip: &str) -> Option<SocketAddr> {
+        Some(SocketAddr::new(ip.parse().unwrap(), 4711))
+    }
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(HeaderName::from_static(name), HeaderValue::from_static(value));
+        headers
+    }
+
+    fn trusted() -> Vec<IpAddr> {
+        vec![PROXY.parse().unwrap()]
+    }
+
+    fn loopback(url: &str) -> bool {
+        is_loopback_url(url)
+    }
+
+    #[test]
+    fn loopback_urls() {
+        assert!(loopback("http://localhost"));
+        assert!(loopback("http://localhost:8080/callback?state=1"));
+        assert!(loopback("https://LOCALHOST/"));
+        assert!(loopback("http://localhost./"));
+        assert!(loopback("http://app.localhost:3000/"));
+        assert!(loopback("http://127.0.0.1:4000/"));
+        assert!(loopback("http://127.0.0.2/"));
+        assert!(loopback("http://127.1/"));
+        assert!(loopback("http://0x7f000001/"));
+        assert!(loopback("http://[::1]:4000/"));
+        assert!(loopback("http://[::ffff:127.0.0.1]/"));
+    }
+
+    #[test]
+    fn other_urls_are_not_loopback() {
+        assert!(!loopback("https://crankade.com/callback"));
+        assert!(!loopback("https://localhost.crankade.com/"));
+        assert!(!loopback("https://mylocalhost/"));
+        assert!(!loopback("http://128.0.0.1/"));
+        assert!(!loopback("http://10.0.0.1/"));
+        assert!(!loopback("http://[::2]/"));
+        assert!(!loopback("ftp://localhost/"));
+        assert!(!loopback("localhost:8080"));
+        assert!(!loopback("not a url"));
+    }
+
+    #[test]
+    fn client_ip_ignores_headers_from_untrusted_peers() {
+        let spoofed: HeaderMap = headers("x-forwarded-for", "198.51.100.1");
+        assert_eq!(client_ip_behind(peer("203.0.113.7"), &spoofed, &trusted()), "203.0.113.7");
+        assert_eq!(client_ip_behind(peer("203.0.113.7"), &spoofed, &[]), "203.0.113.7");
+    }
+
+    #[test]
+    fn client_ip_reads_x_forwarded_for_from_trusted_proxy() {
+        let forwarded: HeaderMap = headers("x-forwarded-for", "203.0.113.7");
+        assert_eq!(client_ip_behind(peer(PROXY), &forwarded, &trusted()), "203.0.113.7");
+    }
+
+    #[test]
+    fn client_ip_skips_addresses_the_client_prepended() {
+        let forwarded: HeaderMap = headers("x-forwarded-for", "198.51.100.1, 203.0.113.7");
+        assert_eq!(client_ip_behind(peer(PROXY), &forwarded, &trusted()), "203.0.113.7");
+    }
+
+    #[test]
+    fn client_ip_walks_back_through_trusted_proxies() {
+        let forwarded: HeaderMap = headers("x-forwarded-for", "203.0.113.7, 10.0.0.2");
+        assert_eq!(client_ip_behind(peer(PROXY), &forwarded, &trusted()), "203.0.113.7");
+    }
+
+    #[test]
+    fn client_ip_reads_the_forwarded_header() {
+        let forwarded: HeaderMap = headers("forwarded", "for=198.51.100.1, for=\"[2001:db8::1]:4711\";proto=https");
+        assert_eq!(client_ip_behind(peer(PROXY), &forwarded, &trusted()), "2001:db8::1");
+    }
+
+    #[test]
+    fn client_ip_stops_at_garbage() {
+        let forwarded: HeaderMap = headers("x-forwarded-for", "unknown");
+        assert_eq!(client_ip_behind(peer(PROXY), &forwarded, &trusted()), PROXY);
+        assert_eq!(client_ip_behind(None, &forwarded, &trusted()), "");
+    }
+}
@@ -1,10 +1,72 @@
 $(document).foundation()
 import * as utils from './utils.js'
 import * as globals from './globals.js'
+import * as step_up from './step_up.js'
 
 
+/**
+ * Archive, restore or delete a client site.
+ * Every action asks "are you sure?" first,
+ * and reloads the page on success so the lists show the new state.
+ **/
+
 let msgs = []
 
+const page = document.getElementById("admin_page")
+
+const routes = {
+    archive: "/admin/archive_client",
+    restore: "/admin/restore_client",
+    delete: "/admin/delete_client"
+}
+
+const confirm_messages = {
+    archive: page.dataset.confirmArchive,
+    restore: page.dataset.confirmRestore,
+    delete: page.dataset.confirmDelete
+}
+
+
+const client_action = async (action, client_id) => {
+    msgs = []
+    hide_msg_box()
+
+    await step_up.fetch_json_post(routes[action], { client_id: client_id })
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Client action failed: " + action)
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// Fill in the confirmation dialog for this client and action, and open it
+const confirm_client_action = (button) => {
+    const action = button.dataset.action
+    const modal = $('#confirm_modal')
+
+    document.getElementById("confirm_title").textContent = button.dataset.name
+    document.getElementById("confirm_message").textContent = confirm_messages[action]
+
+    document.getElementById("confirm_button").onclick = () => {
+        modal.foundation('close')
+        client_action(action, button.dataset.clientId)
+    }
+    document.getElementById("cancel_button").onclick = () => modal.foundation('close')
+
+    modal.foundation('open')
+}
+
 
 // SHOW/HIDE MESSAGE BOX
 
@@ -27,3 +89,7 @@ const show_msg_box = () => {
 // Add event listeners
 
 document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+
+for (const button of document.querySelectorAll('.client_action_button')) {
+    button.addEventListener('click', () => confirm_client_action(button))
+}
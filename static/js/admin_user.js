@@ -0,0 +1,167 @@
+$(document).foundation()
+import * as utils from './utils.js'
+import * as globals from './globals.js'
+import * as step_up from './step_up.js'
+
+
+/**
+ * The admin's actions on one user's account.
+ * Every action reloads the page on success so it shows the new state.
+ **/
+
+let msgs = []
+
+const page = document.getElementById("admin_user")
+const user_id = parseInt(page.dataset.userId, 10)
+
+
+/**
+ * POST to an admin route, then reload (or show the message) on success.
+ */
+const admin_action = async (route, data, success_msg = null) => {
+    msgs = []
+    hide_msg_box()
+
+    await step_up.fetch_json_post(route, data)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    if (data.code == 401 && !data.step_up_required) {
+                        globals.logout()
+                        return
+                    }
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Admin action failed: " + route)
+            }
+            return response.json()
+        }).then(() => {
+            if (!!success_msg) {
+                msgs.push(success_msg)
+                show_msg_box()
+            } else {
+                window.location.reload()
+            }
+        })
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+const save_names = () => admin_action("/admin/users/update_names", {
+    user_id: user_id,
+    first_name: document.getElementById("first_name").value.trim(),
+    last_name: document.getElementById("last_name").value.trim()
+})
+
+const save_role = () => admin_action("/admin/users/update_role", {
+    user_id: user_id,
+    role: document.getElementById("role").value
+})
+
+// Extra roles add to the base role's permissions
+const add_role = () => admin_action("/admin/users/add_role", {
+    user_id: user_id,
+    role: document.getElementById("extra_role").value
+})
+
+const remove_role = (role) => admin_action("/admin/users/remove_role", {
+    user_id: user_id,
+    role: role
+})
+
+const verify_email = () => admin_action("/admin/users/verify_email", { user_id: user_id })
+
+const force_reset = () => admin_action(
+    "/admin/users/force_password_reset", { user_id: user_id }, page.dataset.resetDone)
+
+const revoke_session = (session_id) => admin_action(
+    "/admin/users/revoke_session", { user_id: user_id, session_id: session_id })
+
+const revoke_all = () => admin_action("/admin/users/revoke_sessions", { user_id: user_id })
+
+// An empty "days" field means until further notice
+const suspend = () => {
+    const days_value = document.getElementById("suspend_days").value.trim()
+    admin_action("/admin/suspend", {
+        username: page.dataset.username,
+        reason: document.getElementById("suspend_reason").value.trim(),
+        days: (days_value === "") ? null : parseInt(days_value, 10)
+    })
+}
+
+const lift_suspension = () => admin_action("/admin/lift_suspension", { user_id: user_id })
+
+// The response sets the impersonation JWT cookie; then go look around as them
+const impersonate = async () => {
+    msgs = []
+    hide_msg_box()
+
+    await step_up.fetch_json_post("/admin/users/impersonate", { user_id: user_id })
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    msgs.push((!!data.error) ? data.error : "Error occurred")
+                    show_msg_box()
+                })
+                throw new Error("Impersonation failed")
+            }
+            return response.json()
+        }).then(data => {
+            window.location.href = data.redirect_uri
+        })
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE MESSAGE BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners (some buttons only show up when they apply)
+
+const on_click = (id, handler) => {
+    const element = document.getElementById(id)
+    if (!!element) {
+        element.addEventListener('click', () => handler())
+    }
+}
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+on_click('save_names_button', save_names)
+on_click('save_role_button', save_role)
+on_click('add_role_button', add_role)
+on_click('verify_email_button', verify_email)
+on_click('force_reset_button', force_reset)
+on_click('revoke_all_button', revoke_all)
+on_click('suspend_button', suspend)
+on_click('lift_button', lift_suspension)
+on_click('impersonate_button', impersonate)
+
+for (const button of document.querySelectorAll('.remove_role_button')) {
+    button.addEventListener('click', () => remove_role(button.dataset.role))
+}
+
+for (const button of document.querySelectorAll('.revoke_button')) {
+    button.addEventListener('click', () => revoke_session(parseInt(button.dataset.sessionId, 10)))
+}
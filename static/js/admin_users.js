@@ -0,0 +1,2 @@
+$(document).foundation()
+import { logout } from './globals.js'
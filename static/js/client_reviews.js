@@ -0,0 +1,67 @@
+$(document).foundation()
+import * as utils from './utils.js'
+import * as globals from './globals.js'
+import * as step_up from './step_up.js'
+
+
+/**
+ * Approve or reject a client site a developer registered.
+ * Reloads the page on success so it leaves the queue.
+ **/
+
+let msgs = []
+
+const routes = {
+    approve: "/admin/approve_client",
+    reject: "/admin/reject_client"
+}
+
+
+const review_client = async (action, client_id) => {
+    msgs = []
+    hide_msg_box()
+
+    await step_up.fetch_json_post(routes[action], { client_id: client_id })
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Client review failed: " + action)
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE MESSAGE BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+
+for (const button of document.querySelectorAll('.review_button')) {
+    button.addEventListener('click', () => review_client(button.dataset.action, button.dataset.clientId))
+}
@@ -16,12 +16,16 @@ let msgs = []
 const save_password = async () => {
     msgs = []
 
-    // get the elements where the names are stored
+    // get the elements where the passwords are stored
+    const current_password_element = document.getElementById("current_password")
     const password_element = document.getElementById("new_password")
     const confirmed_password_element = document.getElementById("new_password_confirm")
 
     // get values from input elements
-    const password_obj = { password: password_element.value.trim() }
+    const password_obj = {
+        current_password: current_password_element.value.trim(),
+        password: password_element.value.trim()
+    }
     const confirmed_password = confirmed_password_element.value.trim()
 
     // check that passwords match
@@ -50,7 +54,9 @@ const save_password = async () => {
                     if (!!data.code) {
                         if (data.code == 422){
                             // If inputs were unacceptable, backend informs us, we show the message.
-                            !data.names_valid && msgs.push(utils.password_reqs_msg)
+                            !data.password_valid && utils.push_password_problems(data, msgs)
+                        } else if (data.code == 403 && data.current_password_valid === false){
+                            msgs.push("Current password is incorrect.")
                         } else if (data.code == 401){
                             // User is not authenticated
                             globals.logout()
@@ -68,7 +74,7 @@ const save_password = async () => {
             return response.json()
         }).then(update_data => {
             if (!!update_data.success) {
-                msgs.push("Password updated.")
+                msgs.push("Password updated. All your other devices have been signed out.")
             } else {
                 msgs.push("Update failed.")
             }
@@ -150,6 +156,114 @@ const save_names = async () => {
         })
 }
 
+/**
+ * Get the user's sessions (devices + sites with a refresh token)
+ * and fill the sessions table with them.
+ * Labels come from data attributes on the table, so they're already translated.
+ */
+const load_sessions = async () => {
+    const table = document.getElementById("sessions_table")
+    const body = document.getElementById("sessions_body")
+
+    await fetch("/auth/sessions", { credentials: 'include' })
+        .then(response => {
+            if (!response.ok) {
+                if (response.status == 401) { globals.logout() }
+                throw new Error("Could not load sessions.")
+            }
+            return response.json()
+        }).then(data => {
+            body.innerHTML = ""
+
+            if (data.sessions.length == 0) {
+                const row = body.insertRow()
+                const cell = row.insertCell()
+                cell.colSpan = 7
+                cell.textContent = table.dataset.noSessions
+                return
+            }
+
+            for (let session of data.sessions) {
+                body.appendChild(build_session_row(session, table.dataset))
+            }
+        }).catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+// One row of the sessions table. textContent only: these values came from user agents.
+const build_session_row = (session, labels) => {
+    const row = document.createElement("tr")
+
+    const site_cell = row.insertCell()
+    if (!!session.client_logo_url) {
+        const logo = document.createElement("img")
+        logo.src = session.client_logo_url
+        logo.alt = ""
+        logo.width = 24
+        site_cell.appendChild(logo)
+        site_cell.append(" ")
+    }
+    site_cell.append(session.client_name)
+
+    row.insertCell().textContent = session.device_label
+    row.insertCell().textContent = session.ip_address
+    row.insertCell().textContent = format_time(session.created)
+    row.insertCell().textContent = format_time(session.last_used)
+    row.insertCell().textContent = format_time(session.expires)
+
+    const action_cell = row.insertCell()
+    if (session.is_current) {
+        // Use the normal logout button for this one
+        action_cell.textContent = labels.thisDevice
+    } else {
+        const button = document.createElement("a")
+        button.className = "button tiny hollow alert"
+        button.textContent = labels.signOut
+        button.addEventListener('click', () => revoke_session(session.id))
+        action_cell.appendChild(button)
+    }
+
+    return row
+}
+
+// unix seconds => local date and time
+const format_time = (timestamp) => new Date(timestamp * 1000).toLocaleString()
+
+/**
+ * Sign out one session, then reload the list.
+ */
+const revoke_session = async (session_id) => {
+    await utils.fetch_json_post("/auth/revoke_session", { session_id })
+        .then(response => {
+            if (!response.ok) {
+                if (response.status == 401) { globals.logout() }
+                throw new Error("Could not sign out session.")
+            }
+            return response.json()
+        }).then(() => load_sessions())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+/**
+ * Sign out every session except this one, then reload the list.
+ */
+const revoke_other_sessions = async () => {
+    await utils.fetch_json_post("/auth/revoke_other_sessions", {})
+        .then(response => {
+            if (!response.ok) {
+                if (response.status == 401) { globals.logout() }
+                throw new Error("Could not sign out other sessions.")
+            }
+            return response.json()
+        }).then(() => load_sessions())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
 // SHOW/HIDE ERROR BOX
 
 const hide_msg_box = () =>
@@ -171,12 +285,22 @@ const show_msg_box = () => {
 // Add event listeners
 
 document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.addEventListener('DOMContentLoaded', () => load_sessions())
+document.getElementById('sign_out_others_button').addEventListener(
+    'click', () => revoke_other_sessions())
+document.getElementById('save_names_button').addEventListener(
+    'click', () => save_names())
+document.getElementById('save_password_button').addEventListener(
+    'click', () => save_password())
 document.getElementById('first_name').addEventListener(
     'keydown', (e) => (e.key === 'Enter') && save_names())
 
 document.getElementById('last_name').addEventListener(
     'keydown', (e) => (e.key === 'Enter') && save_names())
 
+document.getElementById('current_password').addEventListener(
+    'keydown', (e) => (e.key === 'Enter') && save_password())
+
 document.getElementById('new_password').addEventListener(
     'keydown', (e) => (e.key === 'Enter') && save_password())
 
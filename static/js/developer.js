@@ -0,0 +1,128 @@
+$(document).foundation()
+import * as utils from './utils.js'
+import * as globals from './globals.js'
+import * as step_up from './step_up.js'
+
+
+/**
+ * The developer portal: register a client site,
+ * edit your own, and rotate their secrets.
+ * The raw secret is only ever shown once, in the message box.
+ **/
+
+let msgs = []
+
+const page = document.getElementById("developer_page")
+
+
+// Same shape as the admin's client form (ClientInputs). is_active is ignored here.
+const register_client = async () => {
+    const client_data = {
+        site_domain: document.getElementById("site_domain").value.trim(),
+        site_name: document.getElementById("site_name").value.trim(),
+        client_id: document.getElementById("client_id").value.trim(),
+        redirect_uri: document.getElementById("redirect_uri").value.trim(),
+        logo_url: document.getElementById("logo_url").value.trim(),
+        description: document.getElementById("description").value.trim(),
+        category: document.getElementById("category").value.trim(),
+        client_type: document.getElementById("client_type").value.trim(),
+        is_active: false
+    }
+
+    if (client_data.site_domain == "" || client_data.site_name == "" ||
+        client_data.client_id == "" || client_data.redirect_uri == "") {
+        msgs = [utils.new_client_req_fields_msg]
+        show_msg_box()
+        return
+    }
+
+    await post_client_action("/developer/add_client", client_data)
+}
+
+
+const save_client = async (container) => {
+    const field = (name) => container.querySelector('[data-field="' + name + '"]').value.trim()
+
+    await post_client_action("/developer/update_client", {
+        site_domain: field("site_domain"),
+        site_name: field("site_name"),
+        client_id: container.dataset.clientId,
+        redirect_uri: field("redirect_uri"),
+        logo_url: field("logo_url"),
+        description: field("description"),
+        category: field("category"),
+        client_type: field("client_type"),
+        is_active: false
+    })
+}
+
+
+const rotate_secret = async (container) =>
+    await post_client_action("/developer/req_new_client_secret", { client_id: container.dataset.clientId })
+
+
+/**
+ * Send it (asking for the password first if needed).
+ * Show the new secret if there is one, otherwise reload to show the changes.
+ */
+const post_client_action = async (route, json_obj) => {
+    msgs = []
+    hide_msg_box()
+
+    await step_up.fetch_json_post(route, json_obj)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    if (!data.step_up_required && (data.code == 403 || data.code == 401)) {
+                        window.location.href = "/error/" + data.code
+                    } else {
+                        let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                        msg += (!!data.error) ? data.error : " Error occurred"
+                        msgs.push(msg)
+                        show_msg_box()
+                    }
+                })
+                throw new Error("Developer client action failed: " + route)
+            }
+            return response.json()
+        }).then(data => {
+            if (!!data.raw_client_secret) {
+                msgs.push(page.dataset.secretMsg)
+                msgs.push(data.raw_client_secret)
+                show_msg_box()
+            } else {
+                window.location.reload()
+            }
+        }).catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE MESSAGE BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.getElementById('register_button').addEventListener('click', () => register_client())
+
+for (const container of document.querySelectorAll('.developer_client')) {
+    container.querySelector('.save_button').addEventListener('click', () => save_client(container))
+    container.querySelector('.secret_button')?.addEventListener('click', () => rotate_secret(container))
+}
@@ -1,6 +1,7 @@
 $(document).foundation()
 import * as utils from './utils.js'
 import * as globals from './globals.js'
+import * as step_up from './step_up.js'
 
 
 /**
@@ -59,11 +60,11 @@ const submit_data = async () => {
     // now send it to the login route
     const route = "/admin/update_client"
 
-    await utils.fetch_json_post(route, client_data)
+    await step_up.fetch_json_post(route, client_data)
         .then(response => {
             if(!response.ok) {
                 response.json().then(data => {
-                    if (!!data.code && data.code == 403 || data.code == 401) {
+                    if (!data.step_up_required && (data.code == 403 || data.code == 401)) {
                         const redirect_uri = "/error/" + data.code
                         window.location.href = redirect_uri
                     } else {
@@ -94,11 +95,11 @@ const request_new_secret = async () => {
     const client_id = document.getElementById("client_id").value.trim()
     const data = { "client_id": client_id };
 
-    await utils.fetch_json_post(route, data)
+    await step_up.fetch_json_post(route, data)
         .then(response => {
             if (!response.ok) {
                 response.json().then(data => {
-                    if (!!data.code && data.code == 403 || data.code == 401) {
+                    if (!data.step_up_required && (data.code == 403 || data.code == 401)) {
                         const redirect_uri = "/error/" + data.code;
                         window.location.href = redirect_uri;
                     } else {
@@ -133,6 +134,120 @@ const request_new_secret = async () => {
 }
 
 
+/**
+ * Block a user from this client site only.
+ * An empty "days" field means until further notice.
+ * Reload the page on success so the table shows the new restriction.
+ */
+const restrict_user = async () => {
+    msgs = []
+
+    const days_value = document.getElementById("restrict_days").value.trim()
+    const restriction = {
+        client_id: document.getElementById("client_id").value.trim(),
+        username: document.getElementById("restrict_username").value.trim(),
+        reason: document.getElementById("restrict_reason").value.trim(),
+        days: (days_value === "") ? null : parseInt(days_value, 10)
+    }
+
+    await utils.fetch_json_post("/admin/restrict_user", restriction)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Could not restrict user.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+const unrestrict_user = async (user_id) => {
+    msgs = []
+
+    const data = {
+        client_id: document.getElementById("client_id").value.trim(),
+        user_id: user_id
+    }
+
+    await utils.fetch_json_post("/admin/unrestrict_user", data)
+        .then(response => {
+            if (!response.ok) {
+                msgs.push("Could not remove the restriction.")
+                show_msg_box()
+                throw new Error("Could not remove restriction.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+/**
+ * Give a user a role on this client site.
+ * Reload the page on success so the table shows it.
+ */
+const add_role = async () => {
+    msgs = []
+
+    const role = {
+        client_id: document.getElementById("client_id").value.trim(),
+        username: document.getElementById("role_username").value.trim(),
+        role_name: document.getElementById("role_name").value.trim()
+    }
+
+    await step_up.fetch_json_post("/admin/add_client_role", role)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Could not add role.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+const remove_role = async (user_id, role_name) => {
+    msgs = []
+
+    const role = {
+        client_id: document.getElementById("client_id").value.trim(),
+        user_id: user_id,
+        role_name: role_name
+    }
+
+    await step_up.fetch_json_post("/admin/remove_client_role", role)
+        .then(response => {
+            if (!response.ok) {
+                msgs.push("Could not remove the role.")
+                show_msg_box()
+                throw new Error("Could not remove role.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
 // SHOW/HIDE ERROR BOX
 
 const hide_msg_box = () =>
@@ -153,6 +268,21 @@ const show_msg_box = () => {
 
 // Add event listeners
 document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.getElementById('submit_button').addEventListener('click', () => submit_data())
+// Only confidential clients have a secret (and the button)
+document.getElementById('new_secret_button')?.addEventListener('click', () => request_new_secret())
+document.getElementById('restrict_button').addEventListener('click', () => restrict_user())
+
+for (const button of document.querySelectorAll('.unrestrict_button')) {
+    button.addEventListener('click', () => unrestrict_user(parseInt(button.dataset.userId, 10)))
+}
+
+document.getElementById('add_role_button').addEventListener('click', () => add_role())
+
+for (const button of document.querySelectorAll('.remove_role_button')) {
+    button.addEventListener('click', () =>
+        remove_role(parseInt(button.dataset.userId, 10), button.dataset.roleName))
+}
 
 // Make functions available to the HTML elements (via window)
 window.submit_data = submit_data
@@ -1,3 +1,5 @@
+import * as utils from './utils.js'
+
 
 /* Log the user out and redirect back to the home page. */
 export const logout = async () => {
@@ -7,7 +9,8 @@ export const logout = async () => {
 
     await fetch(route, {
         method: 'POST',
-        credentials: 'include'
+        credentials: 'include',
+        headers: { 'X-CSRF-Token': utils.csrf_token() }
     }).then(response => {
         if(!response.ok) {
             response.json().then(data => {
@@ -30,11 +33,31 @@ export const logout = async () => {
 }
 
 
+/* An admin impersonating a user goes back to being themself. */
+export const stop_impersonating = async () => {
+    await utils.fetch_json_post("/auth/stop_impersonating", {})
+        .then(response => {
+            if (!response.ok) {
+                throw new Error("Unable to stop impersonating.")
+            }
+            return response.json()
+        }).then(data => {
+            window.location.href = data.redirect_uri
+        }).catch(error => {
+            console.log('Error: ', error)
+            window.location.href = "/"
+        })
+}
+
+
 // Everything we might want to run on page load, for any (or most) page(s).
 document.addEventListener('DOMContentLoaded', () => {
   const button = document.getElementById('logout_nav_button')
   // Checking for the button first in case use is logged in (and button doesn't exist)
   if (!!button) button.addEventListener('click', logout)
+
+  const stop_button = document.getElementById('stop_impersonating_button')
+  if (!!stop_button) stop_button.addEventListener('click', stop_impersonating)
 })
 
 
@@ -9,7 +9,12 @@ import * as globals from './globals.js'
 
 let err_msgs = []
 
-const submit_login = async () => {
+/**
+ * Send the login. After repeated failures the server answers 428:
+ * then solve a proof-of-work challenge and send it again with the solution.
+ * @param {Object} solution { pow_token, pow_nonce }, or nothing the first time
+ */
+const submit_login = async (solution = {}) => {
     err_msgs = []
     const pass_element = document.getElementById("password")
     const username_or_email_element = document.getElementById("username_or_email")
@@ -18,7 +23,14 @@ const submit_login = async () => {
     const creds = {
         password: pass_element.value.trim(),
         username_or_email: username_or_email_element.value.trim(),
-        client_id: client_id_element.value.trim()
+        client_id: client_id_element.value.trim(),
+        ...solution
+    }
+
+    // PKCE, for sites with no client secret
+    const code_challenge = document.getElementById("code_challenge").value
+    if (code_challenge) {
+        creds.code_challenge = code_challenge
     }
 
     // Check the inputs (identifier must match email OR username specifications)
@@ -43,6 +55,22 @@ const submit_login = async () => {
         .then(response => {
             if (!response.ok) {
                 response.json().then(data => {
+                    // Solve once per click. If the solution is refused too, just show the error.
+                    if (data.code == 428 && !!data.challenge_required && !solution.pow_token) {
+                        err_msgs.push(data.error)
+                        show_err_box()
+                        utils.solve_challenge()
+                            .then(new_solution => submit_login(new_solution))
+                            .catch(error => console.log('Error: ', error))
+                        return
+                    }
+
+                    // Suspended, or the site is deactivated: the server sends us to a page explaining why
+                    if (data.code == 403 && (!!data.suspended || !!data.client_inactive) && !!data.redirect_uri) {
+                        window.location.href = data.redirect_uri
+                        return
+                    }
+
                     let msg = (!!data.code) ? (data.code.toString() + " ") : ""
                     msg += (!!data.error) ? data.error : " Error occurred"
                     err_msgs.push(msg)
@@ -87,6 +115,7 @@ const show_err_box = () => {
 // Add event listeners
 
 document.addEventListener('DOMContentLoaded', () => hide_err_box())
+document.getElementById('login_button').addEventListener('click', () => submit_login())
 document.getElementById('username_or_email').addEventListener(
     'keydown', (e) => (e.key === 'Enter') && submit_login())
 document.getElementById('password').addEventListener(
@@ -1,6 +1,7 @@
 $(document).foundation()
 import * as utils from './utils.js'
 import * as globals from './globals.js'
+import * as step_up from './step_up.js'
 
 
 /**
@@ -59,11 +60,11 @@ const submit_data = async () => {
     // now send it to the login route
     const route = "/admin/add_client"
 
-    await utils.fetch_json_post(route, client_data)
+    await step_up.fetch_json_post(route, client_data)
         .then(response => {
             if(!response.ok) {
                 response.json().then(data => {
-                    if (!!data.code && data.code == 403 || data.code == 401) {
+                    if (!data.step_up_required && (data.code == 403 || data.code == 401)) {
                         const redirect_uri = "/error/" + data.code;
                         window.location.href = redirect_uri;
                     } else {
@@ -86,6 +87,10 @@ const submit_data = async () => {
                 msgs.push(secret_message)
                 msgs.push(secret_data.raw_client_secret)
                 show_msg_box()
+            } else {
+                // Public and native clients have no secret
+                msgs.push("Client site added. It isn't confidential, so it has no CLIENT_SECRET.")
+                show_msg_box()
             }
             
         }).catch(error => {
@@ -114,6 +119,7 @@ const show_msg_box = () => {
 
 // Add event listeners
 document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.getElementById('submit_button').addEventListener('click', () => submit_data())
 
 
 // Make functions available to the HTML elements (via window)
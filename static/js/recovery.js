@@ -0,0 +1,120 @@
+$(document).foundation()
+import * as utils from './utils.js'
+
+
+let msgs = []
+
+const recovery_box = document.getElementById("recovery_box")
+const token = recovery_box.dataset.token
+
+
+/**
+ * "This wasn't me": sign the account out everywhere,
+ * then go to the password reset page the backend sends back.
+ */
+const secure_account = async () => {
+    msgs = []
+
+    await utils.fetch_json_post("/auth/not_me", { token: token })
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Could not secure the account.")
+            }
+            return response.json()
+        }).then(data => {
+            window.location.href = data.redirect_uri
+        }).catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+/**
+ * Save a new password with the reset token.
+ * Checks the two passwords match and the basic format first,
+ * and shows the backend's reasons if it refuses.
+ */
+const reset_password = async () => {
+    msgs = []
+
+    const password = document.getElementById("new_password").value.trim()
+    const confirmed_password = document.getElementById("new_password_confirm").value.trim()
+
+    if (!(password === confirmed_password)) {
+        msgs.push(recovery_box.dataset.mismatchMsg)
+        show_msg_box()
+        return
+    }
+
+    if (!utils.check_password(password, msgs)) {
+        show_msg_box()
+        return
+    }
+
+    hide_msg_box()
+
+    await utils.fetch_json_post("/auth/reset_password", { token: token, password: password })
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    if (data.code == 422) {
+                        !data.password_valid && utils.push_password_problems(data, msgs)
+                    } else {
+                        let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                        msg += (!!data.error) ? data.error : " Error occurred"
+                        msgs.push(msg)
+                    }
+                    show_msg_box()
+                })
+                throw new Error("Inputs invalid or server error.")
+            }
+            return response.json()
+        }).then(() => {
+            document.getElementById("reset_form").style.display = "none"
+            document.getElementById("login_button").style.display = ""
+            msgs.push(recovery_box.dataset.doneMsg)
+            show_msg_box()
+        }).catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE ERROR BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+
+if (recovery_box.dataset.mode === "reset") {
+    document.getElementById('reset_button').addEventListener(
+        'click', () => reset_password())
+
+    document.getElementById('new_password_confirm').addEventListener(
+        'keydown', (e) => (e.key === 'Enter') && reset_password())
+} else {
+    document.getElementById('not_me_button').addEventListener(
+        'click', () => secure_account())
+}
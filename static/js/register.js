@@ -39,6 +39,22 @@ const submit_register = async () => {
         return
     } else { hide_err_box() }
 
+    // Every registration needs a solved challenge (keeps the bots out)
+    err_msgs.push("Checking you're not a bot...")
+    show_err_box()
+
+    try {
+        Object.assign(creds, await utils.solve_challenge())
+    } catch (error) {
+        console.log('Error: ', error)
+        err_msgs = ["Could not load the bot check. Please try again."]
+        show_err_box()
+        return
+    }
+
+    err_msgs = []
+    hide_err_box()
+
     // now send it to the register route
     const route = "/auth/register"
 
@@ -52,7 +68,7 @@ const submit_register = async () => {
                             // If inputs were unacceptable, backend informs us, we show the message.
                             !data.username_valid && err_msgs.push(utils.username_reqs_msg)
                             !data.email_valid && err_msgs.push(utils.email_reqs_msg)
-                            !data.password_valid && err_msgs.push(utils.password_reqs_msg)
+                            !data.password_valid && utils.push_password_problems(data, err_msgs)
                         } else if (data.code == 409){
                             // If inputs were duplicates, backend informs us, we show the message.
                             !data.username_valid && err_msgs.push("Username already taken.")
@@ -76,8 +92,13 @@ const submit_register = async () => {
             // THIS WILL BE AUTH DATA NOT USER (change "user" to "auth_data")
             console.log("User data: ", user)
             // do something with the user
-            if(!!user.user_id){
+            // A straight registration answers with the fresh login's username (there's no user_id in it)
+            if(!!user.username){
                 window.location.href = "/dashboard";
+            } else if (!!user.confirmation_sent) {
+                // Registration must be confirmed through the link in their email
+                err_msgs.push(user.message)
+                show_err_box()
             }
         }).catch(error => {
             console.log('Error: ', error)
@@ -103,6 +124,7 @@ const show_err_box = () => {
 
 
 document.addEventListener('DOMContentLoaded', () => hide_err_box())
+document.getElementById('register_button').addEventListener('click', () => submit_register())
 document.getElementById('username').addEventListener('keydown', (e) => (e.key === 'Enter') && submit_register())
 document.getElementById('email').addEventListener('keydown', (e) => (e.key === 'Enter') && submit_register())
 document.getElementById('password').addEventListener('keydown', (e) => (e.key === 'Enter') && submit_register())
@@ -0,0 +1,91 @@
+$(document).foundation()
+import * as utils from './utils.js'
+import * as globals from './globals.js'
+import * as step_up from './step_up.js'
+
+
+/**
+ * Issue and revoke initial access tokens for /oauth/register.
+ * A new token is shown once, in the message box.
+ **/
+
+let msgs = []
+
+const page = document.getElementById("registration_tokens_page")
+
+
+const issue_token = async () => {
+    msgs = []
+    hide_msg_box()
+
+    const token_request = {
+        label: document.getElementById("token_label").value.trim(),
+        max_uses: parseInt(document.getElementById("token_max_uses").value, 10) || 0
+    }
+
+    await step_up.fetch_json_post("/admin/issue_registration_token", token_request)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Could not issue registration token.")
+            }
+            return response.json()
+        }).then(data => {
+            msgs.push(page.dataset.issuedMsg)
+            msgs.push(data.token)
+            show_msg_box()
+        }).catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+const revoke_token = async (id) => {
+    msgs = []
+
+    await utils.fetch_json_post("/admin/revoke_registration_token", { id: id })
+        .then(response => {
+            if (!response.ok) {
+                msgs.push("Could not revoke the token.")
+                show_msg_box()
+                throw new Error("Could not revoke registration token.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE MESSAGE BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.getElementById('issue_button').addEventListener('click', () => issue_token())
+
+for (const button of document.querySelectorAll('.revoke_button')) {
+    button.addEventListener('click', () => revoke_token(parseInt(button.dataset.tokenId, 10)))
+}
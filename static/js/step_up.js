@@ -0,0 +1,65 @@
+import * as utils from './utils.js'
+
+
+/**
+ * Step-up re-authentication for sensitive admin actions.
+ * Those routes answer 401 with step_up_required when the admin
+ * hasn't typed their password in the last few minutes.
+ * Ask for it in the modal (templates/step_up_modal.html),
+ * confirm it with /auth/step_up, then send the action again.
+ **/
+
+
+/**
+ * Same as utils.fetch_json_post, with the step-up in between when needed.
+ * @returns HTTP response from the action (or the 401, if the admin cancelled)
+ */
+export const fetch_json_post = async (route, json_obj) => {
+    const response = await utils.fetch_json_post(route, json_obj)
+    if (response.status != 401) return response
+
+    // Look at the body without using it up: the caller may still read it
+    const data = await response.clone().json().catch(() => ({}))
+    if (!data.step_up_required) return response
+
+    const confirmed = await confirm_password()
+    return confirmed ? utils.fetch_json_post(route, json_obj) : response
+}
+
+
+/**
+ * Open the modal and wait for the admin.
+ * @returns {Promise<boolean>} true once /auth/step_up accepted the password
+ */
+const confirm_password = () => new Promise(resolve => {
+    const modal = $('#step_up_modal')
+    const password_input = document.getElementById('step_up_password')
+    const error_p = document.getElementById('step_up_error')
+    let confirmed = false
+
+    password_input.value = ""
+    error_p.style.display = "none"
+
+    document.getElementById('step_up_confirm_button').onclick = async () => {
+        const response = await utils.fetch_json_post(
+            "/auth/step_up", { password: password_input.value })
+
+        if (response.ok) {
+            confirmed = true
+            modal.foundation('close')
+            return
+        }
+
+        const data = await response.json().catch(() => ({}))
+        error_p.textContent = (!!data.error) ? data.error : "Error occurred"
+        error_p.style.display = ""
+        password_input.value = ""
+    }
+
+    document.getElementById('step_up_cancel_button').onclick = () => modal.foundation('close')
+
+    // Closed by any means (confirm, cancel, escape key...)
+    modal.one('closed.zf.reveal', () => resolve(confirmed))
+    modal.foundation('open')
+    password_input.focus()
+})
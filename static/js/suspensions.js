@@ -0,0 +1,93 @@
+$(document).foundation()
+import * as utils from './utils.js'
+import * as globals from './globals.js'
+
+
+let msgs = []
+
+
+/**
+ * Suspend the user named in the form.
+ * An empty "days" field means until further notice.
+ * Reload the page on success so the table shows the new suspension.
+ */
+const suspend_user = async () => {
+    msgs = []
+
+    const days_value = document.getElementById("suspend_days").value.trim()
+    const suspension = {
+        username: document.getElementById("suspend_username").value.trim(),
+        reason: document.getElementById("suspend_reason").value.trim(),
+        days: (days_value === "") ? null : parseInt(days_value, 10)
+    }
+
+    await utils.fetch_json_post("/admin/suspend", suspension)
+        .then(response => {
+            if (!response.ok) {
+                response.json().then(data => {
+                    if (data.code == 401) {
+                        globals.logout()
+                        return
+                    }
+                    let msg = (!!data.code) ? (data.code.toString() + " ") : ""
+                    msg += (!!data.error) ? data.error : " Error occurred"
+                    msgs.push(msg)
+                    show_msg_box()
+                })
+                throw new Error("Could not suspend user.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+/**
+ * Lift every suspension in force for this user.
+ */
+const lift_suspension = async (user_id) => {
+    msgs = []
+
+    await utils.fetch_json_post("/admin/lift_suspension", { user_id: user_id })
+        .then(response => {
+            if (!response.ok) {
+                msgs.push("Could not lift the suspension.")
+                show_msg_box()
+                throw new Error("Could not lift suspension.")
+            }
+            return response.json()
+        }).then(() => window.location.reload())
+        .catch(error => {
+            console.log('Error: ', error)
+        })
+}
+
+
+// SHOW/HIDE MESSAGE BOX
+
+const hide_msg_box = () =>
+    document.getElementById("msg_box").style.display = "none"
+
+const show_msg_box = () => {
+    const msg_box = document.getElementById("msg_box")
+    msg_box.innerHTML = "";
+
+    for (let msg of msgs) {
+        const msg_p = "<p>" + msg + "</p>"
+        msg_box.innerHTML += msg_p
+    }
+
+    msg_box.style.display = ""
+}
+
+
+// Add event listeners
+
+document.addEventListener('DOMContentLoaded', () => hide_msg_box())
+document.getElementById('suspend_button').addEventListener('click', () => suspend_user())
+
+for (const button of document.querySelectorAll('.lift_button')) {
+    button.addEventListener('click', () => lift_suspension(parseInt(button.dataset.userId, 10)))
+}
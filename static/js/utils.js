@@ -1,7 +1,6 @@
 /* REGEX for user inputs */
 
 export const username_regex = /^[A-Za-z0-9_-]+$/
-export const password_regex = /^[A-Za-z0-9!@#$%^&*()_\-+=\[\]{}:;<>.,?~`|]+$/
 export const email_regex = /^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$/
 
 /* LENGTH RANGES for inputs */
@@ -11,11 +10,6 @@ export const username_length_range = {
     max: 20
 }
 
-export const password_length_range = {
-    min: 6,
-    max: 16
-}
-
 export const first_last_name_length_range = {
     min: 1,
     max: 50
@@ -25,21 +19,35 @@ export const first_last_name_length_range = {
 export const email_reqs_msg = "Must be a legitimate email address. Check your formatting."
 export const username_reqs_msg = "Username must be 6 to 20 characters. " +
     "Only letters, numbers, underscore, and hyphen allowed."
-export const password_reqs_msg = "Password must be 6 to 16 characters with no spaces."
+export const password_reqs_msg = "Password must not be empty."
 export const name_range_err_msg = "Names must be 2 to 50 characters in length"
 
 export const new_client_req_fields_msg = "Site domain, name, auth_id, redirect_uri, " +
     "and type must not be empty. Also, redirect_uri and domain must be valid."
 
-// Make sure password matches regex and length requirements
+/**
+ * The password policy (length, strength, breached list) lives on the backend,
+ * which sends back field-level reasons. Here we only make sure there IS a password.
+ */
 export const check_password = (password, err_msgs) => {
-    const password_is_legit = password_regex.test(password) &&
-        string_in_range(password_length_range, password)
+    const password_is_legit = password.length > 0
 
     if (!password_is_legit) { err_msgs.push(password_reqs_msg) }
     return password_is_legit
 }
 
+/**
+ * Push the backend's password policy reasons into the messages array.
+ * Falls back to the generic message if the backend didn't give reasons.
+ */
+export const push_password_problems = (data, err_msgs) => {
+    if (!!data.password_problems && data.password_problems.length > 0) {
+        for (let problem of data.password_problems) { err_msgs.push(problem) }
+    } else {
+        err_msgs.push(password_reqs_msg)
+    }
+}
+
 
 // Make sure email matches regex
 export const check_email = (email, err_msgs) => {
@@ -104,12 +112,63 @@ export const fetch_json_post = async (route, json_obj) => {
     // now we return the HTTP response from a fetch call
     return fetch(route, {
         method: 'POST',
-        headers: { 'Content-Type': 'application/json; charset=utf-8' },
+        headers: {
+            'Content-Type': 'application/json; charset=utf-8',
+            'X-CSRF-Token': csrf_token()
+        },
         body: json_string
     })
 }
 
 
+/**
+ * Get a proof-of-work challenge from the server and solve it:
+ * find a nonce where SHA-256(token + ":" + nonce) starts with
+ * `difficulty` zero bits. Can take a few seconds.
+ * @returns {Object} { pow_token, pow_nonce } to add to the form's JSON
+ */
+export const solve_challenge = async () => {
+    const response = await fetch("/auth/challenge", { credentials: 'include' })
+    if (!response.ok) {
+        throw new Error("Could not get a challenge.")
+    }
+    const challenge = await response.json()
+    const encoder = new TextEncoder()
+
+    for (let nonce = 0; ; nonce++) {
+        const digest = await crypto.subtle.digest(
+            "SHA-256", encoder.encode(challenge.token + ":" + nonce))
+
+        if (leading_zero_bits(new Uint8Array(digest)) >= challenge.difficulty) {
+            return { pow_token: challenge.token, pow_nonce: nonce.toString() }
+        }
+    }
+}
+
+
+const leading_zero_bits = (bytes) => {
+    let bits = 0
+    for (const byte of bytes) {
+        if (byte === 0) {
+            bits += 8
+        } else {
+            bits += Math.clz32(byte) - 24
+            break
+        }
+    }
+    return bits
+}
+/**
+ * The CSRF token the server put in this page's <meta name="csrf-token"> tag.
+ * Every POST must send it back in the X-CSRF-Token header.
+ * @returns {String} the token, or "" if the page has none
+ */
+export const csrf_token = () => {
+    const meta = document.querySelector('meta[name="csrf-token"]')
+    return (!!meta) ? meta.content : ""
+}
+
+
 /* make sure that a string is a legit JSON string which can be parsed. */ 
 const is_valid_json_string = (json_string) => {
     try {